@@ -1,11 +1,16 @@
 use std::{
+    ffi::OsStr,
     fs::File,
-    io::BufReader,
+    io::{self, BufReader, Read, Seek},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
-use details::SourcePicker;
+use details::{Looping, SourcePicker};
 use iced::{
     futures::{
         channel::mpsc::{self, Sender},
@@ -14,28 +19,53 @@ use iced::{
     Subscription, Task,
 };
 use log::debug;
-use rodio::{mixer::Mixer, OutputStream, Source};
+use rodio::{
+    decoder::DecoderError, mixer::Mixer, source::SeekError, Decoder, OutputStream, Source,
+};
 
-use crate::{visualization, waveform};
+use crate::{
+    opus::{OpusDecoder, OpusError},
+    visualization, waveform,
+    wavpack::{WavPackDecoder, WavPackError},
+};
+
+/// How far the playback moves when seeking with the keyboard.
+const SEEK_STEP: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Initialize(Sender<AudioCommand>),
     QueryPosition,
     SetPosition(f32),
+    /// Pause or resume, a file played until the end is played again.
+    TogglePlayback,
+    /// Play the current file from the start.
+    Replay,
+    SeekBackward,
+    SeekForward,
+    ToggleLoop,
 }
 
 pub enum AudioCommand {
     Initialize(Mixer),
     Play(PathBuf),
     Stop,
+    /// Decode the file played again, from the same position.
+    Reload,
     QueryPosition,
     SetPosition(f32),
+    TogglePlayback,
+    /// Move the play position by this number of seconds, negative to go backward.
+    Seek(f32),
+    SetLooping(bool),
 }
 
 pub struct Audio {
     command_sender: Option<Sender<AudioCommand>>,
     output_stream: Option<OutputStream>,
+    /// The file played, until it is stopped.
+    path: Option<PathBuf>,
+    looping: bool,
 }
 
 impl Audio {
@@ -51,6 +81,8 @@ impl Audio {
         Self {
             command_sender: None,
             output_stream,
+            path: None,
+            looping: false,
         }
     }
 
@@ -68,6 +100,28 @@ impl Audio {
             Message::SetPosition(position) => {
                 self.send_command_if_possible(AudioCommand::SetPosition(position));
             }
+            Message::TogglePlayback => {
+                self.send_command_if_possible(AudioCommand::TogglePlayback);
+            }
+            Message::Replay => {
+                if let Some(path) = self.path.clone() {
+                    self.play(path);
+                }
+            }
+            Message::SeekBackward => {
+                self.send_command_if_possible(AudioCommand::Seek(-SEEK_STEP.as_secs_f32()));
+            }
+            Message::SeekForward => {
+                self.send_command_if_possible(AudioCommand::Seek(SEEK_STEP.as_secs_f32()));
+            }
+            Message::ToggleLoop => {
+                self.looping = !self.looping;
+                self.send_command_if_possible(AudioCommand::SetLooping(self.looping));
+
+                return Task::done(crate::Message::Waveform(waveform::Message::LoopingChanged(
+                    self.looping,
+                )));
+            }
         }
 
         Task::none()
@@ -90,10 +144,19 @@ impl Audio {
     pub fn play(&mut self, path: impl AsRef<Path>) {
         let path = path.as_ref().to_path_buf();
 
+        self.path = Some(path.clone());
         self.send_command(AudioCommand::Play(path));
     }
 
+    /// The file played has been rewritten, play its new content from the same position.
+    pub fn reload(&mut self) {
+        if self.path.is_some() {
+            self.send_command_if_possible(AudioCommand::Reload);
+        }
+    }
+
     pub fn stop(&mut self) {
+        self.path = None;
         self.send_command(AudioCommand::Stop);
     }
 
@@ -127,11 +190,18 @@ fn run_audio_player() -> impl Stream<Item = crate::Message> {
 
         let mut current_file_duration = None;
         let mut current_file_path = None;
+        // Shared with the source played, which goes back to the start at its end while it is
+        // set.
+        let looping = Arc::new(AtomicBool::new(false));
 
         let create_source_output = output.clone();
-        let create_source = |file| {
-            rodio::Decoder::new(BufReader::new(file))
-                .map(|source| SourcePicker::new(source, create_source_output.clone()))
+        let create_source = |path: &Path| {
+            open_decoder(path).map(|source| {
+                SourcePicker::new(
+                    Looping::new(source, looping.clone()),
+                    create_source_output.clone(),
+                )
+            })
         };
 
         while let Some(command) = command_receiver.next().await {
@@ -147,13 +217,18 @@ fn run_audio_player() -> impl Stream<Item = crate::Message> {
                     if let Some(mixer) = mixer.as_ref() {
                         sink = Some(rodio::Sink::connect_new(mixer));
                         if let Some(sink) = sink.as_mut() {
-                            if let Ok(file) = File::open(&path) {
-                                if let Ok(source) = create_source(file) {
+                            match create_source(&path) {
+                                Ok(source) => {
                                     current_file_path = Some(path);
                                     current_file_duration = source.total_duration();
                                     sink.append(source);
                                     sink.play();
                                 }
+                                Err(error) => log::error!(
+                                    "Failed to decode file '{}': {}",
+                                    path.display(),
+                                    error
+                                ),
                             }
                         }
                     }
@@ -179,10 +254,50 @@ fn run_audio_player() -> impl Stream<Item = crate::Message> {
                             .unwrap();
                     }
                 }
+                AudioCommand::Reload => {
+                    let (Some(mixer), Some(path), Some(old_sink)) =
+                        (mixer.as_ref(), current_file_path.as_ref(), sink.as_ref())
+                    else {
+                        continue;
+                    };
+
+                    match create_source(path) {
+                        Ok(source) => {
+                            let resume = resume_after_reload(
+                                play_position(old_sink, current_file_duration),
+                                old_sink.is_paused(),
+                                old_sink.empty(),
+                                source.total_duration(),
+                            );
+                            let new_sink = rodio::Sink::connect_new(mixer);
+
+                            current_file_duration = source.total_duration();
+
+                            if let Some(Resume { position, paused }) = resume {
+                                new_sink.append(source);
+
+                                if let Err(error) = new_sink.try_seek(position) {
+                                    log::error!("Failed to seek: {}", error);
+                                }
+
+                                if paused {
+                                    new_sink.pause();
+                                }
+                            }
+
+                            sink = Some(new_sink);
+                        }
+                        // The previous content keeps playing.
+                        Err(error) => {
+                            log::error!("Failed to decode file '{}': {}", path.display(), error)
+                        }
+                    }
+                }
                 AudioCommand::QueryPosition => {
                     if let Some(sink) = sink.as_mut() {
-                        if let Some(duration) = current_file_duration.as_ref() {
-                            let position = sink.get_pos().as_secs_f32() / duration.as_secs_f32();
+                        if let Some(duration) = current_file_duration {
+                            let position = play_position(sink, Some(duration)).as_secs_f32()
+                                / duration.as_secs_f32();
 
                             output
                                 .send(crate::Message::Waveform(waveform::Message::PlayPosition(
@@ -200,11 +315,9 @@ fn run_audio_player() -> impl Stream<Item = crate::Message> {
                                 Duration::from_secs_f32(duration.as_secs_f32() * position);
                             if sink.empty() {
                                 if let Some(path) = current_file_path.as_ref() {
-                                    if let Ok(file) = File::open(path) {
-                                        if let Ok(source) = create_source(file) {
-                                            sink.append(source);
-                                            sink.play();
-                                        }
+                                    if let Ok(source) = create_source(path) {
+                                        sink.append(source);
+                                        sink.play();
                                     }
                                 }
                             }
@@ -213,19 +326,279 @@ fn run_audio_player() -> impl Stream<Item = crate::Message> {
                         }
                     }
                 }
+                AudioCommand::TogglePlayback => {
+                    if let Some(sink) = sink.as_mut() {
+                        if sink.empty() {
+                            if let Some(path) = current_file_path.as_ref() {
+                                if let Ok(source) = create_source(path) {
+                                    sink.append(source);
+                                    sink.play();
+                                }
+                            }
+                        } else if sink.is_paused() {
+                            sink.play();
+                        } else {
+                            sink.pause();
+                        }
+                    }
+                }
+                AudioCommand::Seek(offset) => {
+                    if let Some(sink) = sink.as_mut().filter(|sink| !sink.empty()) {
+                        let mut position =
+                            (play_position(sink, current_file_duration).as_secs_f32() + offset)
+                                .max(0.0);
+
+                        if let Some(duration) = current_file_duration.as_ref() {
+                            position = position.min(duration.as_secs_f32());
+                        }
+
+                        if let Err(error) = sink.try_seek(Duration::from_secs_f32(position)) {
+                            log::error!("Failed to seek: {}", error);
+                        }
+                    }
+                }
+                AudioCommand::SetLooping(new_looping) => {
+                    looping.store(new_looping, Ordering::Relaxed);
+                }
             }
         }
     })
 }
 
+/// The position in the file played.
+/// The position of the sink keeps growing when the file loops, it is only reset by a seek.
+fn play_position(sink: &rodio::Sink, duration: Option<Duration>) -> Duration {
+    let position = sink.get_pos();
+
+    match duration.filter(|duration| !duration.is_zero()) {
+        Some(duration) => Duration::from_secs_f64(position.as_secs_f64() % duration.as_secs_f64()),
+        None => position,
+    }
+}
+
+/// How the new content of a reloaded file is played.
+#[derive(Debug, PartialEq)]
+struct Resume {
+    position: Duration,
+    paused: bool,
+}
+
+/// The new content is played from the same position, or from its end if it is shorter, and it
+/// stays paused. A file played until the end is not played again.
+fn resume_after_reload(
+    position: Duration,
+    paused: bool,
+    finished: bool,
+    duration: Option<Duration>,
+) -> Option<Resume> {
+    (!finished).then(|| Resume {
+        position: duration.map_or(position, |duration| position.min(duration)),
+        paused,
+    })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpenDecoderError {
+    #[error(transparent)]
+    Decoder(#[from] DecoderError),
+    #[error(transparent)]
+    Opus(#[from] OpusError),
+    #[error(transparent)]
+    WavPack(#[from] WavPackError),
+}
+
+/// A decoder of any supported format.
+pub enum AudioDecoder {
+    Rodio(Decoder<BufReader<File>>),
+    /// The decoding library used by rodio does not implement Opus.
+    Opus(OpusDecoder<BufReader<File>>),
+    /// The decoding library used by rodio does not implement WavPack either.
+    WavPack(WavPackDecoder<BufReader<File>>),
+}
+
+impl Iterator for AudioDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.next(),
+            AudioDecoder::Opus(decoder) => decoder.next(),
+            AudioDecoder::WavPack(decoder) => decoder.next(),
+        }
+    }
+}
+
+impl Source for AudioDecoder {
+    fn current_span_len(&self) -> Option<usize> {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.current_span_len(),
+            AudioDecoder::Opus(decoder) => decoder.current_span_len(),
+            AudioDecoder::WavPack(decoder) => decoder.current_span_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.channels(),
+            AudioDecoder::Opus(decoder) => decoder.channels(),
+            AudioDecoder::WavPack(decoder) => decoder.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.sample_rate(),
+            AudioDecoder::Opus(decoder) => decoder.sample_rate(),
+            AudioDecoder::WavPack(decoder) => decoder.sample_rate(),
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.total_duration(),
+            AudioDecoder::Opus(decoder) => decoder.total_duration(),
+            AudioDecoder::WavPack(decoder) => decoder.total_duration(),
+        }
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        match self {
+            AudioDecoder::Rodio(decoder) => decoder.try_seek(position),
+            AudioDecoder::Opus(decoder) => decoder.try_seek(position),
+            AudioDecoder::WavPack(decoder) => decoder.try_seek(position),
+        }
+    }
+}
+
+/// Open a file for decoding.
+/// The extension is given to the decoder as a hint, this helps to detect formats such as AIFF or CAF.
+/// Opus is detected from the content, as Opus files often have the `.ogg` extension, and so is
+/// WavPack.
+/// This is the only place where files are opened for decoding, so the player, the waveform and
+/// the spectrogram always support the same formats.
+pub fn open_decoder(path: impl AsRef<Path>) -> Result<AudioDecoder, OpenDecoderError> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(|error| DecoderError::IoError(error.to_string()))?;
+
+    if is_opus(&mut file).map_err(|error| DecoderError::IoError(error.to_string()))? {
+        return Ok(AudioDecoder::Opus(OpusDecoder::new(BufReader::new(file))?));
+    }
+
+    if is_wavpack(&mut file).map_err(|error| DecoderError::IoError(error.to_string()))? {
+        return Ok(AudioDecoder::WavPack(WavPackDecoder::new(BufReader::new(
+            file,
+        ))?));
+    }
+
+    let byte_len = file
+        .metadata()
+        .map_err(|error| DecoderError::IoError(error.to_string()))?
+        .len();
+    let mut builder = Decoder::builder()
+        .with_data(BufReader::new(file))
+        .with_byte_len(byte_len)
+        .with_seekable(true);
+
+    if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+        builder = builder.with_hint(&extension.to_lowercase());
+    }
+
+    Ok(AudioDecoder::Rodio(builder.build()?))
+}
+
+/// True if the file starts with an Ogg page holding an Opus identification header.
+fn is_opus(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 36];
+    let is_opus = file.read_exact(&mut header).is_ok()
+        && header.starts_with(b"OggS")
+        && header.ends_with(b"OpusHead");
+
+    file.rewind()?;
+
+    Ok(is_opus)
+}
+
+/// True if the file starts with a WavPack block.
+fn is_wavpack(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 4];
+    let is_wavpack = file.read_exact(&mut header).is_ok() && &header == b"wvpk";
+
+    file.rewind()?;
+
+    Ok(is_wavpack)
+}
+
 mod details {
-    use std::time::Duration;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
 
     use iced::futures::channel::mpsc::Sender;
     use rodio::source::SeekError;
 
     use crate::{visualization, Message};
 
+    /// Plays the source again from the start when it ends, while `looping` is set.
+    /// The loop is gapless, the source is sought instead of being decoded again.
+    pub(crate) struct Looping<S> {
+        source: S,
+        looping: Arc<AtomicBool>,
+    }
+
+    impl<S: rodio::Source> Looping<S> {
+        pub fn new(source: S, looping: Arc<AtomicBool>) -> Self {
+            Self { source, looping }
+        }
+    }
+
+    impl<S: rodio::Source> rodio::Source for Looping<S> {
+        fn current_span_len(&self) -> Option<usize> {
+            self.source.current_span_len()
+        }
+
+        fn channels(&self) -> u16 {
+            self.source.channels()
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.source.sample_rate()
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            self.source.total_duration()
+        }
+
+        fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+            self.source.try_seek(pos)
+        }
+    }
+
+    impl<S: rodio::Source> Iterator for Looping<S> {
+        type Item = S::Item;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if let Some(sample) = self.source.next() {
+                return Some(sample);
+            }
+
+            if !self.looping.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            if let Err(error) = self.source.try_seek(Duration::ZERO) {
+                log::error!("Failed to loop: {}", error);
+                return None;
+            }
+
+            // None again for an empty source, which would loop forever otherwise.
+            self.source.next()
+        }
+    }
+
     pub(crate) struct SourcePicker<S>
     where
         S: rodio::Source + Send + 'static,
@@ -322,3 +695,66 @@ mod details {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::{details::Looping, resume_after_reload, Resume};
+
+    #[test]
+    fn test_looping() {
+        let looping = Arc::new(AtomicBool::new(true));
+        let mut source = Looping::new(
+            SamplesBuffer::new(1, 44100, vec![1.0, 2.0, 3.0]),
+            looping.clone(),
+        );
+        let played = source.by_ref().take(7).collect::<Vec<_>>();
+
+        assert_eq!(played, [1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0]);
+
+        looping.store(false, Ordering::Relaxed);
+
+        assert_eq!(source.collect::<Vec<_>>(), [2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_resume_after_reload() {
+        let position = Duration::from_secs(2);
+
+        assert_eq!(
+            resume_after_reload(position, false, false, Some(Duration::from_secs(3))),
+            Some(Resume {
+                position,
+                paused: false
+            })
+        );
+        assert_eq!(
+            resume_after_reload(position, true, false, None),
+            Some(Resume {
+                position,
+                paused: true
+            })
+        );
+        // The new content is shorter than the position played.
+        assert_eq!(
+            resume_after_reload(position, false, false, Some(Duration::from_secs(1))),
+            Some(Resume {
+                position: Duration::from_secs(1),
+                paused: false
+            })
+        );
+        assert_eq!(
+            resume_after_reload(position, false, true, Some(Duration::from_secs(3))),
+            None
+        );
+    }
+}
@@ -0,0 +1,346 @@
+//! Copy files to the clipboard, so they can be pasted into a file manager.
+//!
+//! The clipboard of iced only holds text, so on Linux the selection is owned on a dedicated
+//! X11 connection, which offers the files in the formats of the file managers.
+
+#[cfg(any(target_os = "linux", test))]
+use std::path::Path;
+use std::path::PathBuf;
+
+use iced::Task;
+
+use crate::drag_out::uri_list;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClipboardError {
+    #[error("Copying files to the clipboard is only supported on X11")]
+    Unsupported,
+    #[error("X11 error: {0}")]
+    X11(String),
+}
+
+/// Copy the files to the clipboard.
+/// Where the files cannot be offered as files they are copied as a `text/uri-list`.
+pub fn copy_files<Message: Send + 'static>(paths: Vec<PathBuf>) -> Task<Message> {
+    match platform::copy_files(&paths) {
+        Ok(()) => Task::none(),
+        Err(error) => {
+            log::debug!("{}, the files are copied as text", error);
+            iced::clipboard::write(uri_list(&paths))
+        }
+    }
+}
+
+/// The content of a `x-special/gnome-copied-files`, the operation then one URI per line.
+#[cfg(any(target_os = "linux", test))]
+fn gnome_copied_files(paths: &[impl AsRef<Path>]) -> String {
+    std::iter::once("copy".to_string())
+        .chain(
+            paths
+                .iter()
+                .map(|path| crate::drag_out::file_uri(path.as_ref())),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::path::PathBuf;
+
+    use super::ClipboardError;
+
+    pub fn copy_files(_paths: &[PathBuf]) -> Result<(), ClipboardError> {
+        Err(ClipboardError::Unsupported)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::path::PathBuf;
+
+    use x11rb::{
+        connection::Connection,
+        errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+        protocol::{
+            xproto::{
+                AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+                SelectionNotifyEvent, SelectionRequestEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+            },
+            Event,
+        },
+        wrapper::ConnectionExt as _,
+        COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+    };
+
+    use super::{gnome_copied_files, uri_list, ClipboardError};
+
+    x11rb::atom_manager! {
+        pub Atoms: AtomsCookie {
+            CLIPBOARD,
+            TARGETS,
+            UTF8_STRING,
+            TEXT_URI_LIST: b"text/uri-list",
+            GNOME_COPIED_FILES: b"x-special/gnome-copied-files",
+        }
+    }
+
+    impl From<ConnectError> for ClipboardError {
+        fn from(error: ConnectError) -> Self {
+            ClipboardError::X11(error.to_string())
+        }
+    }
+
+    impl From<ConnectionError> for ClipboardError {
+        fn from(error: ConnectionError) -> Self {
+            ClipboardError::X11(error.to_string())
+        }
+    }
+
+    impl From<ReplyError> for ClipboardError {
+        fn from(error: ReplyError) -> Self {
+            ClipboardError::X11(error.to_string())
+        }
+    }
+
+    impl From<ReplyOrIdError> for ClipboardError {
+        fn from(error: ReplyOrIdError) -> Self {
+            ClipboardError::X11(error.to_string())
+        }
+    }
+
+    /// Own the clipboard, it is served by a thread until another client owns it.
+    pub fn copy_files(paths: &[PathBuf]) -> Result<(), ClipboardError> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty()) {
+            return Err(ClipboardError::Unsupported);
+        }
+
+        let (connection, screen) = x11rb::connect(None)?;
+        let root = connection.setup().roots[screen].root;
+        let atoms = Atoms::new(&connection)?.reply()?;
+        let window = connection.generate_id()?;
+
+        connection.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new(),
+        )?;
+        connection.set_selection_owner(window, atoms.CLIPBOARD, CURRENT_TIME)?;
+
+        if connection
+            .get_selection_owner(atoms.CLIPBOARD)?
+            .reply()?
+            .owner
+            != window
+        {
+            return Err(ClipboardError::X11(
+                "The clipboard was not acquired".to_string(),
+            ));
+        }
+
+        let owner = ClipboardOwner {
+            atoms,
+            uri_list: uri_list(paths).into_bytes(),
+            gnome_copied_files: gnome_copied_files(paths).into_bytes(),
+        };
+
+        std::thread::spawn(move || {
+            if let Err(error) = owner.serve(&connection) {
+                log::error!("Failed to serve the clipboard: {}", error);
+            }
+        });
+
+        Ok(())
+    }
+
+    struct ClipboardOwner {
+        atoms: Atoms,
+        uri_list: Vec<u8>,
+        gnome_copied_files: Vec<u8>,
+    }
+
+    impl ClipboardOwner {
+        /// Answer the requests until another client owns the clipboard.
+        fn serve(&self, connection: &impl Connection) -> Result<(), ClipboardError> {
+            loop {
+                match connection.wait_for_event()? {
+                    Event::SelectionRequest(request) => self.send_selection(connection, request)?,
+                    Event::SelectionClear(clear) if clear.selection == self.atoms.CLIPBOARD => {
+                        return Ok(());
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        fn send_selection(
+            &self,
+            connection: &impl Connection,
+            request: SelectionRequestEvent,
+        ) -> Result<(), ClipboardError> {
+            // Obsolete clients do not give a property, the target is used then.
+            let mut property = if request.property == NONE {
+                request.target
+            } else {
+                request.property
+            };
+            let data = if request.target == self.atoms.TEXT_URI_LIST
+                || request.target == self.atoms.UTF8_STRING
+            {
+                Some(&self.uri_list)
+            } else if request.target == self.atoms.GNOME_COPIED_FILES {
+                Some(&self.gnome_copied_files)
+            } else {
+                None
+            };
+
+            if request.selection != self.atoms.CLIPBOARD {
+                property = NONE;
+            } else if let Some(data) = data {
+                connection.change_property8(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    request.target,
+                    data,
+                )?;
+            } else if request.target == self.atoms.TARGETS {
+                connection.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &[
+                        self.atoms.TARGETS,
+                        self.atoms.TEXT_URI_LIST,
+                        self.atoms.GNOME_COPIED_FILES,
+                        self.atoms.UTF8_STRING,
+                    ],
+                )?;
+            } else {
+                property = NONE;
+            }
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property,
+            };
+
+            connection.send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+            connection.flush()?;
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::path::PathBuf;
+
+        use x11rb::{
+            connection::Connection,
+            protocol::{
+                xproto::{ConnectionExt as _, CreateWindowAux, EventMask, WindowClass},
+                Event,
+            },
+            COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME,
+        };
+
+        use super::{copy_files, Atoms};
+        use crate::clipboard::gnome_copied_files;
+
+        /// Copy a file and paste it as a file manager does.
+        #[test]
+        #[ignore = "requires an X11 display"]
+        fn test_paste_copied_files() {
+            let (connection, screen) = x11rb::connect(None).unwrap();
+            let root = connection.setup().roots[screen].root;
+            let atoms = Atoms::new(&connection).unwrap().reply().unwrap();
+            let window = connection.generate_id().unwrap();
+
+            connection
+                .create_window(
+                    COPY_DEPTH_FROM_PARENT,
+                    window,
+                    root,
+                    0,
+                    0,
+                    1,
+                    1,
+                    0,
+                    WindowClass::INPUT_ONLY,
+                    COPY_FROM_PARENT,
+                    &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )
+                .unwrap();
+
+            let paths = vec![PathBuf::from("/samples/Kick 01.wav")];
+
+            copy_files(&paths).unwrap();
+            connection
+                .convert_selection(
+                    window,
+                    atoms.CLIPBOARD,
+                    atoms.GNOME_COPIED_FILES,
+                    atoms.GNOME_COPIED_FILES,
+                    CURRENT_TIME,
+                )
+                .unwrap();
+            connection.flush().unwrap();
+
+            let received = loop {
+                if let Event::SelectionNotify(notify) = connection.wait_for_event().unwrap() {
+                    let reply = connection
+                        .get_property(
+                            true,
+                            window,
+                            notify.property,
+                            atoms.GNOME_COPIED_FILES,
+                            0,
+                            u32::MAX,
+                        )
+                        .unwrap()
+                        .reply()
+                        .unwrap();
+
+                    break String::from_utf8(reply.value).unwrap();
+                }
+            };
+
+            assert_eq!(received, gnome_copied_files(&paths));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::gnome_copied_files;
+
+    #[test]
+    fn test_gnome_copied_files() {
+        let paths = [
+            PathBuf::from("/samples/Kick 01.wav"),
+            PathBuf::from("/samples/snare.wav"),
+        ];
+
+        assert_eq!(
+            gnome_copied_files(&paths),
+            "copy\nfile:///samples/Kick%2001.wav\nfile:///samples/snare.wav"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Detection of the clipped samples and of the overs between the samples.
+
+use std::ops::Range;
+
+use crate::loudness::TRUE_PEAK_DELAY;
+
+/// The samples at least this loud are at full scale, the highest positive 16 bits sample is
+/// 32767 / 32768.
+const FULL_SCALE: f32 = 0.9999;
+/// The consecutive full scale samples that are a clip, a single one is usually a peak.
+const MIN_RUN: usize = 3;
+/// The level above which a value between the samples is an over, 0.1 dB above full scale as the
+/// interpolation is not exact.
+const OVER_LEVEL: f32 = 1.0116;
+
+/// Finds the clipped frames of interleaved samples.
+/// The overs are found with the values between the samples given by a `TruePeakMeter`, which
+/// the loudness measures share.
+pub struct ClipDetector {
+    /// The channel of the next sample.
+    channel: usize,
+    /// The frame of the next sample.
+    frame: usize,
+    /// The first frame of the current run of full scale samples of each channel.
+    run_starts: Vec<Option<usize>>,
+    /// The clipped frames found since the last `take_clips()`.
+    clips: Vec<Range<usize>>,
+}
+
+impl ClipDetector {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channel: 0,
+            frame: 0,
+            run_starts: vec![None; channels],
+            clips: Vec::new(),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.run_starts.len()
+    }
+
+    /// Add the next sample, the channels are interleaved, with the value returned for it by
+    /// `TruePeakMeter::push_sample`.
+    pub fn push_sample(&mut self, sample: f32, true_peak: f32) {
+        let channel = self.channel;
+        let frame = self.frame;
+
+        if sample.abs() >= FULL_SCALE {
+            let start = *self.run_starts[channel].get_or_insert(frame);
+
+            if frame + 1 - start >= MIN_RUN {
+                self.add(start..frame + 1);
+            }
+        } else {
+            self.run_starts[channel] = None;
+        }
+
+        // An over between two samples, which can clip once converted to analog.
+        if true_peak > OVER_LEVEL && frame >= TRUE_PEAK_DELAY {
+            let previous = frame - TRUE_PEAK_DELAY;
+
+            self.add(previous..previous + 2);
+        }
+
+        self.channel += 1;
+
+        if self.channel == self.run_starts.len() {
+            self.channel = 0;
+            self.frame += 1;
+        }
+    }
+
+    fn add(&mut self, frames: Range<usize>) {
+        match self.clips.last_mut() {
+            Some(last) if frames.start <= last.end && last.start <= frames.end => {
+                last.start = last.start.min(frames.start);
+                last.end = last.end.max(frames.end);
+            }
+            _ => self.clips.push(frames),
+        }
+    }
+
+    /// The clipped frames found since the previous call, a clip still going on can be split.
+    pub fn take_clips(&mut self) -> Vec<Range<usize>> {
+        std::mem::take(&mut self.clips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use rstest::rstest;
+
+    use super::ClipDetector;
+    use crate::loudness::TruePeakMeter;
+
+    /// The clips found in the interleaved samples.
+    fn detect(channels: usize, samples: &[f32]) -> Vec<Range<usize>> {
+        let mut true_peak = TruePeakMeter::new(channels);
+        let mut detector = ClipDetector::new(channels);
+
+        for sample in samples {
+            detector.push_sample(*sample, true_peak.push_sample(*sample));
+        }
+
+        detector.take_clips()
+    }
+
+    fn sine(amplitude: f32, frames: usize) -> impl Iterator<Item = f32> {
+        (0..frames).map(move |i| amplitude * (i as f32 * 0.05).sin())
+    }
+
+    #[test]
+    fn test_clean_signal() {
+        assert!(detect(1, &sine(0.9, 10000).collect::<Vec<_>>()).is_empty());
+    }
+
+    #[rstest]
+    // A single full scale sample is not a clip.
+    #[case(&[0.5, 1.0, 0.5], &[])]
+    #[case(&[0.0, 1.0, 1.0, 1.0, -1.0, 0.0], &[1..5])]
+    fn test_full_scale_runs(#[case] samples: &[f32], #[case] expected: &[Range<usize>]) {
+        assert_eq!(detect(1, samples), expected);
+    }
+
+    #[test]
+    fn test_clipped_sine() {
+        let mut true_peak = TruePeakMeter::new(2);
+        let mut detector = ClipDetector::new(2);
+        // The right channel is clipped at its peaks.
+        let samples: Vec<f32> = sine(1.5, 1000)
+            .flat_map(|sample| [sample / 2.0, sample.clamp(-1.0, 1.0)])
+            .collect();
+
+        for sample in &samples {
+            detector.push_sample(*sample, true_peak.push_sample(*sample));
+        }
+
+        let clips = detector.take_clips();
+        let clipped: Vec<usize> = sine(1.5, 1000)
+            .enumerate()
+            .filter(|(_, sample)| sample.abs() >= 1.0)
+            .map(|(frame, _)| frame)
+            .collect();
+
+        assert!(!clips.is_empty());
+        assert!(clipped
+            .iter()
+            .all(|frame| clips.iter().any(|clip| clip.contains(frame))));
+        assert!(detector.take_clips().is_empty());
+    }
+
+    #[test]
+    fn test_inter_sample_over() {
+        // A quarter of the sample rate, the samples are at 45° of the peaks.
+        let samples: Vec<f32> = (0..100)
+            .map(|i| {
+                (i as f32 * std::f32::consts::FRAC_PI_2 + std::f32::consts::FRAC_PI_4).sin() * 1.2
+            })
+            .collect();
+
+        assert!(samples.iter().all(|sample| sample.abs() < 0.9));
+        assert!(!detect(1, &samples).is_empty());
+    }
+}
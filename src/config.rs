@@ -0,0 +1,731 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use iced::{
+    keyboard::{key::Named, Key, Modifiers},
+    Theme,
+};
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::fft_processor::{FftSettings, WindowFunction};
+
+const APPLICATION_DIRECTORY: &str = "sex";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const MIN_FFT_SIZE: usize = 512;
+const MAX_FFT_SIZE: usize = 16384;
+const MAX_FFT_OVERLAP: usize = 90;
+const MIN_SPECTRUM_RANGE: f32 = 20.0;
+const MAX_SPECTRUM_RANGE: f32 = 140.0;
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum ConfigError {
+    #[error("Failed to read configuration file '{}': {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: Arc<std::io::Error>,
+    },
+    #[error("Invalid configuration file '{}': {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Unknown theme '{0}', expected one of: {1}")]
+    UnknownTheme(String, String),
+    #[error("Unknown log level '{0}', expected one of: off, error, warn, info, debug, trace")]
+    UnknownLogLevel(String),
+    #[error("Invalid extension '{0}', an extension must not be empty and must not contain a dot")]
+    InvalidExtension(String),
+    #[error(
+        "Invalid FFT size {0}, expected a power of two between {min} and {max}",
+        min = MIN_FFT_SIZE,
+        max = MAX_FFT_SIZE
+    )]
+    InvalidFftSize(usize),
+    #[error("Unknown FFT window '{0}', expected one of: {1}")]
+    UnknownFftWindow(String, String),
+    #[error(
+        "Invalid FFT overlap {0}%, expected a percentage between 0 and {max}",
+        max = MAX_FFT_OVERLAP
+    )]
+    InvalidFftOverlap(usize),
+    #[error(
+        "Invalid spectrum range {0} dB, expected a range between {min} and {max} dB",
+        min = MIN_SPECTRUM_RANGE,
+        max = MAX_SPECTRUM_RANGE
+    )]
+    InvalidSpectrumRange(f32),
+    #[error("Invalid key binding '{binding}' for action '{action}': {reason}")]
+    InvalidKeyBinding {
+        action: &'static str,
+        binding: String,
+        reason: String,
+    },
+}
+
+/// The validated configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub theme: Theme,
+    pub log_level: log::LevelFilter,
+    /// Extensions of the files displayed, lowercase and without the leading dot.
+    pub extensions: Arc<Vec<String>>,
+    pub fft_size: usize,
+    pub fft_window: WindowFunction,
+    /// The percentage of a block of the FFT analyzed again with the next block.
+    pub fft_overlap: usize,
+    /// The levels displayed by the spectrum, in dB below 0 dB.
+    pub spectrum_range: f32,
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        ConfigFile::default()
+            .validate()
+            .expect("default configuration is valid")
+    }
+}
+
+impl Config {
+    pub fn parse(content: &str, path: &Path) -> Result<Self, ConfigError> {
+        let file: ConfigFile = toml::from_str(content).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        file.validate()
+    }
+
+    pub fn fft_settings(&self) -> FftSettings {
+        FftSettings {
+            size: self.fft_size,
+            window: self.fft_window,
+            overlap: self.fft_overlap,
+        }
+    }
+}
+
+/// Path of the configuration file, in the platform configuration directory.
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(APPLICATION_DIRECTORY).join(CONFIG_FILE_NAME))
+}
+
+/// Load the configuration file.
+/// If there is no configuration file the default configuration is returned.
+pub async fn load() -> Result<Config, ConfigError> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => Config::parse(&content, &path),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(ConfigError::Read {
+            path,
+            source: Arc::new(error),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SelectNext,
+    SelectPrevious,
+    /// Collapse the directory or select the parent.
+    SelectParent,
+    /// Expand the directory or select its first child.
+    SelectChild,
+    SelectFirst,
+    SelectLast,
+    PageUp,
+    PageDown,
+    ExpandCollapse,
+    /// Pause or resume the playback.
+    TogglePlayback,
+    /// Play the current file from the start.
+    Replay,
+    SeekBackward,
+    SeekForward,
+    ToggleLoop,
+    ReloadConfig,
+    Rename,
+    Duplicate,
+    MoveTo,
+    NewFolder,
+    Trash,
+    Undo,
+    Cancel,
+    CopyPath,
+    CopyFile,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    key: Key,
+    modifiers: Modifiers,
+}
+
+impl KeyBinding {
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let mut modifiers = Modifiers::empty();
+        // A trailing "+" is the key itself, as in "+" or "Ctrl++".
+        let (prefix, key) = match text.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.trim_end().ends_with('+') => {
+                (rest.trim_end().strip_suffix('+').unwrap_or_default(), "+")
+            }
+            _ => text
+                .rsplit_once('+')
+                .map_or(("", text), |(prefix, key)| (prefix, key.trim())),
+        };
+
+        if key.is_empty() {
+            return Err("missing key".into());
+        }
+
+        let parts = prefix
+            .split('+')
+            .map(str::trim)
+            .filter(|_| !prefix.is_empty());
+
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "logo" | "cmd" | "command" | "super" => Modifiers::LOGO,
+                _ => return Err(format!("unknown modifier '{}'", modifier)),
+            };
+        }
+
+        let key = match parse_named_key(key) {
+            Some(named) => Key::Named(named),
+            None if key.chars().count() == 1 => Key::Character(key.to_lowercase().into()),
+            None => return Err(format!("unknown key '{}'", key)),
+        };
+
+        Ok(Self { key, modifiers })
+    }
+
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        if self.modifiers != modifiers {
+            return false;
+        }
+
+        match (&self.key, key) {
+            (Key::Character(expected), Key::Character(pressed)) => {
+                expected.as_str() == pressed.to_lowercase()
+            }
+            (expected, pressed) => expected == pressed,
+        }
+    }
+}
+
+fn parse_named_key(name: &str) -> Option<Named> {
+    let named = match name {
+        "ArrowDown" | "Down" => Named::ArrowDown,
+        "ArrowUp" | "Up" => Named::ArrowUp,
+        "ArrowLeft" | "Left" => Named::ArrowLeft,
+        "ArrowRight" | "Right" => Named::ArrowRight,
+        "Enter" | "Return" => Named::Enter,
+        "Space" => Named::Space,
+        "Escape" | "Esc" => Named::Escape,
+        "Tab" => Named::Tab,
+        "Backspace" => Named::Backspace,
+        "Delete" => Named::Delete,
+        "Insert" => Named::Insert,
+        "Home" => Named::Home,
+        "End" => Named::End,
+        "PageUp" => Named::PageUp,
+        "PageDown" => Named::PageDown,
+        "F1" => Named::F1,
+        "F2" => Named::F2,
+        "F3" => Named::F3,
+        "F4" => Named::F4,
+        "F5" => Named::F5,
+        "F6" => Named::F6,
+        "F7" => Named::F7,
+        "F8" => Named::F8,
+        "F9" => Named::F9,
+        "F10" => Named::F10,
+        "F11" => Named::F11,
+        "F12" => Named::F12,
+        _ => return None,
+    };
+
+    Some(named)
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, KeyBinding)>,
+}
+
+impl KeyBindings {
+    /// Find the action bound to a key press.
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, binding)| binding.matches(key, modifiers))
+            .map(|(action, _)| *action)
+    }
+}
+
+/// The configuration file as written by the user, before validation.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    theme: String,
+    log_level: String,
+    extensions: Vec<String>,
+    fft_size: usize,
+    fft_window: String,
+    fft_overlap: usize,
+    spectrum_range: f32,
+    key_bindings: KeyBindingsFile,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            theme: Theme::CatppuccinFrappe.to_string(),
+            log_level: "debug".into(),
+            extensions: [
+                "wav", "flac", "ogg", "mp3", "aif", "aiff", "aifc", "caf", "m4a", "opus", "wv",
+            ]
+            .map(String::from)
+            .to_vec(),
+            fft_size: 2048,
+            fft_window: WindowFunction::Hann.name().into(),
+            fft_overlap: 0,
+            spectrum_range: 60.0,
+            key_bindings: KeyBindingsFile::default(),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn validate(self) -> Result<Config, ConfigError> {
+        Ok(Config {
+            theme: parse_theme(&self.theme)?,
+            log_level: parse_log_level(&self.log_level)?,
+            extensions: Arc::new(parse_extensions(self.extensions)?),
+            fft_size: parse_fft_size(self.fft_size)?,
+            fft_window: parse_fft_window(&self.fft_window)?,
+            fft_overlap: parse_fft_overlap(self.fft_overlap)?,
+            spectrum_range: parse_spectrum_range(self.spectrum_range)?,
+            key_bindings: self.key_bindings.validate()?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KeyBindingsFile {
+    select_next: String,
+    select_previous: String,
+    select_parent: String,
+    select_child: String,
+    select_first: String,
+    select_last: String,
+    page_up: String,
+    page_down: String,
+    expand_collapse: String,
+    toggle_playback: String,
+    replay: String,
+    seek_backward: String,
+    seek_forward: String,
+    toggle_loop: String,
+    reload_config: String,
+    rename: String,
+    duplicate: String,
+    move_to: String,
+    new_folder: String,
+    trash: String,
+    undo: String,
+    cancel: String,
+    copy_path: String,
+    copy_file: String,
+}
+
+impl Default for KeyBindingsFile {
+    fn default() -> Self {
+        Self {
+            select_next: "ArrowDown".into(),
+            select_previous: "ArrowUp".into(),
+            select_parent: "ArrowLeft".into(),
+            select_child: "ArrowRight".into(),
+            select_first: "Home".into(),
+            select_last: "End".into(),
+            page_up: "PageUp".into(),
+            page_down: "PageDown".into(),
+            expand_collapse: "Enter".into(),
+            toggle_playback: "Space".into(),
+            replay: "Shift+Space".into(),
+            seek_backward: "Shift+ArrowLeft".into(),
+            seek_forward: "Shift+ArrowRight".into(),
+            toggle_loop: "Ctrl+L".into(),
+            reload_config: "F5".into(),
+            rename: "F2".into(),
+            duplicate: "Ctrl+D".into(),
+            move_to: "Ctrl+M".into(),
+            new_folder: "Ctrl+Shift+N".into(),
+            trash: "Delete".into(),
+            undo: "Ctrl+Z".into(),
+            cancel: "Escape".into(),
+            copy_path: "Ctrl+C".into(),
+            copy_file: "Ctrl+Shift+C".into(),
+        }
+    }
+}
+
+impl KeyBindingsFile {
+    fn validate(self) -> Result<KeyBindings, ConfigError> {
+        let bindings = [
+            ("select_next", Action::SelectNext, self.select_next),
+            (
+                "select_previous",
+                Action::SelectPrevious,
+                self.select_previous,
+            ),
+            ("select_parent", Action::SelectParent, self.select_parent),
+            ("select_child", Action::SelectChild, self.select_child),
+            ("select_first", Action::SelectFirst, self.select_first),
+            ("select_last", Action::SelectLast, self.select_last),
+            ("page_up", Action::PageUp, self.page_up),
+            ("page_down", Action::PageDown, self.page_down),
+            (
+                "expand_collapse",
+                Action::ExpandCollapse,
+                self.expand_collapse,
+            ),
+            (
+                "toggle_playback",
+                Action::TogglePlayback,
+                self.toggle_playback,
+            ),
+            ("replay", Action::Replay, self.replay),
+            ("seek_backward", Action::SeekBackward, self.seek_backward),
+            ("seek_forward", Action::SeekForward, self.seek_forward),
+            ("toggle_loop", Action::ToggleLoop, self.toggle_loop),
+            ("reload_config", Action::ReloadConfig, self.reload_config),
+            ("rename", Action::Rename, self.rename),
+            ("duplicate", Action::Duplicate, self.duplicate),
+            ("move_to", Action::MoveTo, self.move_to),
+            ("new_folder", Action::NewFolder, self.new_folder),
+            ("trash", Action::Trash, self.trash),
+            ("undo", Action::Undo, self.undo),
+            ("cancel", Action::Cancel, self.cancel),
+            ("copy_path", Action::CopyPath, self.copy_path),
+            ("copy_file", Action::CopyFile, self.copy_file),
+        ];
+
+        let mut parsed: Vec<(&'static str, Action, KeyBinding)> =
+            Vec::with_capacity(bindings.len());
+
+        for (name, action, binding) in bindings {
+            // An empty binding or "none" leaves the action without a key.
+            if binding.trim().is_empty() || binding.trim().eq_ignore_ascii_case("none") {
+                continue;
+            }
+
+            let key_binding =
+                KeyBinding::parse(&binding).map_err(|reason| ConfigError::InvalidKeyBinding {
+                    action: name,
+                    binding: binding.clone(),
+                    reason,
+                })?;
+
+            // The first binding would always win, the other action could not be used.
+            if let Some((other, _, _)) = parsed
+                .iter()
+                .find(|(_, _, other_binding)| *other_binding == key_binding)
+            {
+                return Err(ConfigError::InvalidKeyBinding {
+                    action: name,
+                    binding,
+                    reason: format!("already bound to action '{}'", other),
+                });
+            }
+
+            parsed.push((name, action, key_binding));
+        }
+
+        Ok(KeyBindings {
+            bindings: parsed
+                .into_iter()
+                .map(|(_, action, key_binding)| (action, key_binding))
+                .collect(),
+        })
+    }
+}
+
+fn parse_theme(name: &str) -> Result<Theme, ConfigError> {
+    let normalize = |name: &str| name.replace(' ', "").to_lowercase();
+    let expected = normalize(name);
+
+    Theme::ALL
+        .iter()
+        .find(|theme| normalize(&theme.to_string()) == expected)
+        .cloned()
+        .ok_or_else(|| ConfigError::UnknownTheme(name.to_string(), Theme::ALL.iter().join(", ")))
+}
+
+fn parse_log_level(level: &str) -> Result<log::LevelFilter, ConfigError> {
+    level
+        .parse()
+        .map_err(|_| ConfigError::UnknownLogLevel(level.to_string()))
+}
+
+fn parse_extensions(extensions: Vec<String>) -> Result<Vec<String>, ConfigError> {
+    extensions
+        .into_iter()
+        .map(|extension| {
+            if extension.is_empty() || extension.contains('.') {
+                Err(ConfigError::InvalidExtension(extension))
+            } else {
+                Ok(extension.to_lowercase())
+            }
+        })
+        .collect()
+}
+
+fn parse_fft_size(fft_size: usize) -> Result<usize, ConfigError> {
+    if fft_size.is_power_of_two() && (MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&fft_size) {
+        Ok(fft_size)
+    } else {
+        Err(ConfigError::InvalidFftSize(fft_size))
+    }
+}
+
+fn parse_fft_window(name: &str) -> Result<WindowFunction, ConfigError> {
+    WindowFunction::ALL
+        .into_iter()
+        .find(|window| window.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            ConfigError::UnknownFftWindow(name.to_string(), WindowFunction::ALL.iter().join(", "))
+        })
+}
+
+fn parse_fft_overlap(overlap: usize) -> Result<usize, ConfigError> {
+    if overlap <= MAX_FFT_OVERLAP {
+        Ok(overlap)
+    } else {
+        Err(ConfigError::InvalidFftOverlap(overlap))
+    }
+}
+
+fn parse_spectrum_range(range: f32) -> Result<f32, ConfigError> {
+    if (MIN_SPECTRUM_RANGE..=MAX_SPECTRUM_RANGE).contains(&range) {
+        Ok(range)
+    } else {
+        Err(ConfigError::InvalidSpectrumRange(range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use iced::{
+        keyboard::{key::Named, Key, Modifiers},
+        Theme,
+    };
+    use rstest::rstest;
+
+    use super::{Action, Config, ConfigError, KeyBinding};
+    use crate::fft_processor::WindowFunction;
+
+    #[test]
+    fn test_empty_config_is_default() {
+        let config = Config::parse("", Path::new("config.toml")).unwrap();
+
+        assert_eq!(config.theme, Theme::CatppuccinFrappe);
+        assert_eq!(config.log_level, log::LevelFilter::Debug);
+        assert_eq!(config.fft_size, 2048);
+        assert_eq!(config.fft_window, WindowFunction::Hann);
+        assert_eq!(config.fft_overlap, 0);
+        assert_eq!(config.spectrum_range, 60.0);
+        assert_eq!(
+            config.extensions.as_slice(),
+            &["wav", "flac", "ogg", "mp3", "aif", "aiff", "aifc", "caf", "m4a", "opus", "wv"]
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Character("c".into()), Modifiers::CTRL),
+            Some(Action::CopyPath)
+        );
+        assert_eq!(
+            config.key_bindings.action(
+                &Key::Character("C".into()),
+                Modifiers::CTRL | Modifiers::SHIFT
+            ),
+            Some(Action::CopyFile)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::ArrowLeft), Modifiers::empty()),
+            Some(Action::SelectParent)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::PageDown), Modifiers::empty()),
+            Some(Action::PageDown)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::Space), Modifiers::SHIFT),
+            Some(Action::Replay)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::ArrowRight), Modifiers::SHIFT),
+            Some(Action::SeekForward)
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let content = r#"
+            theme = "Tokyo Night"
+            log_level = "warn"
+            extensions = ["wav", "AIFF"]
+            fft_size = 4096
+            fft_window = "Blackman-Harris"
+            fft_overlap = 75
+            spectrum_range = 96.0
+
+            [key_bindings]
+            select_next = "j"
+            select_previous = "Ctrl+K"
+        "#;
+
+        let config = Config::parse(content, Path::new("config.toml")).unwrap();
+
+        assert_eq!(config.theme, Theme::TokyoNight);
+        assert_eq!(config.log_level, log::LevelFilter::Warn);
+        assert_eq!(config.fft_size, 4096);
+        assert_eq!(config.fft_window, WindowFunction::BlackmanHarris);
+        assert_eq!(config.fft_overlap, 75);
+        assert_eq!(config.spectrum_range, 96.0);
+        assert_eq!(config.extensions.as_slice(), &["wav", "aiff"]);
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Character("j".into()), Modifiers::empty()),
+            Some(Action::SelectNext)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Character("K".into()), Modifiers::CTRL),
+            Some(Action::SelectPrevious)
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::Enter), Modifiers::empty()),
+            Some(Action::ExpandCollapse)
+        );
+        assert_eq!(
+            config.key_bindings.action(
+                &Key::Character("n".into()),
+                Modifiers::CTRL | Modifiers::SHIFT
+            ),
+            Some(Action::NewFolder)
+        );
+    }
+
+    #[rstest]
+    #[case("theme = \"Nope\"")]
+    #[case("log_level = \"loud\"")]
+    #[case("fft_size = 1000")]
+    #[case("fft_size = 65536")]
+    #[case("fft_window = \"square\"")]
+    #[case("fft_overlap = 100")]
+    #[case("spectrum_range = 0.0")]
+    #[case("extensions = [\".wav\"]")]
+    #[case("unknown = 1")]
+    #[case("[key_bindings]\nselect_next = \"Hyper+A\"")]
+    #[case("[key_bindings]\nselect_next = \"Ctrl+\"")]
+    #[case("[key_bindings]\nselect_next = \"F2\"")]
+    fn test_invalid_config(#[case] content: &str) {
+        assert!(Config::parse(content, Path::new("config.toml")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_key_binding_message() {
+        let error = Config::parse(
+            "[key_bindings]\nselect_next = \"Nope\"",
+            Path::new("config.toml"),
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, ConfigError::InvalidKeyBinding { .. }));
+        assert_eq!(
+            error.to_string(),
+            "Invalid key binding 'Nope' for action 'select_next': unknown key 'Nope'"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_key_binding_message() {
+        let error = Config::parse(
+            "[key_bindings]\nselect_next = \"Ctrl+shift+c\"",
+            Path::new("config.toml"),
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid key binding 'Ctrl+Shift+C' for action 'copy_file': \
+             already bound to action 'select_next'"
+        );
+    }
+
+    #[test]
+    fn test_unbound_key_binding() {
+        let content = r#"
+            [key_bindings]
+            toggle_playback = ""
+            copy_path = "None"
+            copy_file = "Ctrl+C"
+        "#;
+
+        let config = Config::parse(content, Path::new("config.toml")).unwrap();
+
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Named(Named::Space), Modifiers::empty()),
+            None
+        );
+        assert_eq!(
+            config
+                .key_bindings
+                .action(&Key::Character("c".into()), Modifiers::CTRL),
+            Some(Action::CopyFile)
+        );
+    }
+
+    #[rstest]
+    #[case("ArrowDown", Key::Named(Named::ArrowDown), Modifiers::empty())]
+    #[case("Shift+Space", Key::Named(Named::Space), Modifiers::SHIFT)]
+    #[case("ctrl+shift+c", Key::Character("c".into()), Modifiers::CTRL | Modifiers::SHIFT)]
+    #[case("+", Key::Character("+".into()), Modifiers::empty())]
+    #[case("Ctrl++", Key::Character("+".into()), Modifiers::CTRL)]
+    #[case("Ctrl + +", Key::Character("+".into()), Modifiers::CTRL)]
+    fn test_parse_key_binding(#[case] text: &str, #[case] key: Key, #[case] modifiers: Modifiers) {
+        let binding = KeyBinding::parse(text).unwrap();
+
+        assert!(binding.matches(&key, modifiers));
+    }
+}
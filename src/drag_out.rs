@@ -0,0 +1,720 @@
+//! Drag files out of the application, into a DAW or a file manager.
+//!
+//! Neither iced nor winit can start a drag and drop, so on Linux the XDND protocol
+//! is implemented directly on a dedicated X11 connection. The pointer is polled because
+//! the window that received the mouse press keeps the implicit grab.
+
+use std::path::{Path, PathBuf};
+
+use iced::{Point, Task};
+
+/// Minimum distance, in pixels, the cursor must move while pressed to start a drag.
+const DRAG_THRESHOLD: f32 = 8.0;
+
+#[derive(thiserror::Error, Debug)]
+pub enum DragError {
+    #[error("Dragging files out of the application is only supported on X11")]
+    Unsupported,
+    #[error("X11 error: {0}")]
+    X11(String),
+}
+
+/// Detects when the cursor moves far enough after a press to start a drag.
+#[derive(Debug, Default)]
+pub struct DragGesture {
+    pressed: bool,
+    origin: Option<Point>,
+}
+
+impl DragGesture {
+    pub fn press(&mut self) {
+        self.pressed = true;
+        self.origin = None;
+    }
+
+    /// Returns true when the drag starts, then the gesture is reset.
+    pub fn moved(&mut self, position: Point) -> bool {
+        if !self.pressed {
+            return false;
+        }
+
+        match self.origin {
+            // The first position received after the press is the origin.
+            None => {
+                self.origin = Some(position);
+                false
+            }
+            Some(origin) if origin.distance(position) >= DRAG_THRESHOLD => {
+                self.release();
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.pressed = false;
+        self.origin = None;
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// Start dragging the files, the task finishes when they are dropped.
+pub fn drag_files<Message: Send + 'static>(paths: Vec<PathBuf>) -> Task<Message> {
+    Task::future(async move {
+        let result = tokio::task::spawn_blocking(move || platform::drag_files(&paths))
+            .await
+            .unwrap_or_else(|error| Err(DragError::X11(error.to_string())));
+
+        if let Err(error) = result {
+            log::error!("Failed to drag the files: {}", error);
+        }
+    })
+    .discard()
+}
+
+/// The content of a `text/uri-list` with one `file://` URI per path.
+pub fn uri_list(paths: &[impl AsRef<Path>]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{}\r\n", file_uri(path.as_ref())))
+        .collect()
+}
+
+/// The `file://` URI of an absolute path.
+pub(crate) fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+
+    for byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(*byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::path::PathBuf;
+
+    use super::DragError;
+
+    pub fn drag_files(_paths: &[PathBuf]) -> Result<(), DragError> {
+        Err(DragError::Unsupported)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::{
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    use x11rb::{
+        connection::Connection,
+        errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+        protocol::{
+            xproto::{
+                AtomEnum, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask,
+                KeyButMask, PropMode, SelectionNotifyEvent, SelectionRequestEvent, Window,
+                WindowClass, SELECTION_NOTIFY_EVENT,
+            },
+            Event,
+        },
+        wrapper::ConnectionExt as _,
+        COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+    };
+
+    use super::{uri_list, DragError};
+
+    const XDND_VERSION: u32 = 5;
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+    /// How long to wait for the target after the drop.
+    const FINISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+    x11rb::atom_manager! {
+        pub Atoms: AtomsCookie {
+            XdndAware,
+            XdndSelection,
+            XdndEnter,
+            XdndPosition,
+            XdndStatus,
+            XdndLeave,
+            XdndDrop,
+            XdndFinished,
+            XdndActionCopy,
+            TARGETS,
+            _NET_WM_PID,
+            TEXT_URI_LIST: b"text/uri-list",
+        }
+    }
+
+    impl From<ConnectError> for DragError {
+        fn from(error: ConnectError) -> Self {
+            DragError::X11(error.to_string())
+        }
+    }
+
+    impl From<ConnectionError> for DragError {
+        fn from(error: ConnectionError) -> Self {
+            DragError::X11(error.to_string())
+        }
+    }
+
+    impl From<ReplyError> for DragError {
+        fn from(error: ReplyError) -> Self {
+            DragError::X11(error.to_string())
+        }
+    }
+
+    impl From<ReplyOrIdError> for DragError {
+        fn from(error: ReplyOrIdError) -> Self {
+            DragError::X11(error.to_string())
+        }
+    }
+
+    pub fn drag_files(paths: &[PathBuf]) -> Result<(), DragError> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty()) {
+            return Err(DragError::Unsupported);
+        }
+
+        let (connection, screen) = x11rb::connect(None)?;
+        let root = connection.setup().roots[screen].root;
+        let mut source = DragSource::new(&connection, root, uri_list(paths).into_bytes())?;
+
+        source.run(|connection| {
+            let pointer = connection.query_pointer(root)?.reply()?;
+
+            Ok(pointer
+                .mask
+                .contains(KeyButMask::BUTTON1)
+                .then_some((pointer.root_x, pointer.root_y)))
+        })
+    }
+
+    struct Target {
+        window: Window,
+        version: u32,
+        accepted: bool,
+        waiting_status: bool,
+    }
+
+    pub struct DragSource<'a, C: Connection> {
+        connection: &'a C,
+        atoms: Atoms,
+        root: Window,
+        window: Window,
+        data: Vec<u8>,
+        target: Option<Target>,
+        position: (i16, i16),
+    }
+
+    impl<'a, C: Connection> DragSource<'a, C> {
+        pub fn new(connection: &'a C, root: Window, data: Vec<u8>) -> Result<Self, DragError> {
+            let atoms = Atoms::new(connection)?.reply()?;
+            let window = connection.generate_id()?;
+
+            connection.create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )?;
+            connection.set_selection_owner(window, atoms.XdndSelection, CURRENT_TIME)?;
+            connection.flush()?;
+
+            Ok(Self {
+                connection,
+                atoms,
+                root,
+                window,
+                data,
+                target: None,
+                position: (0, 0),
+            })
+        }
+
+        /// Run the drag until the button is released.
+        /// `pointer` returns the position of the pointer, or `None` when the button is released.
+        pub fn run(
+            &mut self,
+            mut pointer: impl FnMut(&C) -> Result<Option<(i16, i16)>, DragError>,
+        ) -> Result<(), DragError> {
+            while let Some(position) = pointer(self.connection)? {
+                self.process_events()?;
+
+                if position != self.position || self.target.is_none() {
+                    self.position = position;
+                    self.move_to(position)?;
+                }
+
+                std::thread::sleep(POLL_INTERVAL);
+            }
+
+            self.finish()
+        }
+
+        /// Drop on the current target if it accepts the files.
+        fn finish(&mut self) -> Result<(), DragError> {
+            let deadline = Instant::now() + FINISH_TIMEOUT;
+
+            // The target may not have answered the last position yet.
+            while self
+                .target
+                .as_ref()
+                .is_some_and(|target| target.waiting_status)
+                && Instant::now() < deadline
+            {
+                self.process_events()?;
+                std::thread::sleep(POLL_INTERVAL);
+            }
+
+            let Some(target) = self.target.take() else {
+                return Ok(());
+            };
+
+            if !target.accepted {
+                self.send(
+                    target.window,
+                    self.atoms.XdndLeave,
+                    [self.window, 0, 0, 0, 0],
+                )?;
+                return Ok(());
+            }
+
+            self.send(
+                target.window,
+                self.atoms.XdndDrop,
+                [self.window, 0, CURRENT_TIME, 0, 0],
+            )?;
+
+            // Serve the data until the target is done.
+            while Instant::now() < deadline {
+                if let Some(event) = self.connection.poll_for_event()? {
+                    match event {
+                        Event::SelectionRequest(request) => self.send_selection(request)?,
+                        Event::ClientMessage(message)
+                            if message.type_ == self.atoms.XdndFinished =>
+                        {
+                            return Ok(());
+                        }
+                        _ => (),
+                    }
+                } else {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            }
+
+            log::warn!("The drop target did not finish the drop");
+
+            Ok(())
+        }
+
+        fn move_to(&mut self, (x, y): (i16, i16)) -> Result<(), DragError> {
+            let new_target = self.find_target(x, y)?;
+            let current_window = self.target.as_ref().map(|target| target.window);
+
+            if new_target.map(|(window, _)| window) != current_window {
+                if let Some(target) = self.target.take() {
+                    self.send(
+                        target.window,
+                        self.atoms.XdndLeave,
+                        [self.window, 0, 0, 0, 0],
+                    )?;
+                }
+
+                if let Some((window, version)) = new_target {
+                    self.send(
+                        window,
+                        self.atoms.XdndEnter,
+                        [self.window, version << 24, self.atoms.TEXT_URI_LIST, 0, 0],
+                    )?;
+                    self.target = Some(Target {
+                        window,
+                        version,
+                        accepted: false,
+                        waiting_status: false,
+                    });
+                }
+            }
+
+            if let Some(target) = self.target.as_ref() {
+                // A new position is only sent once the previous one is answered.
+                if !target.waiting_status {
+                    let window = target.window;
+                    let action = if target.version >= 2 {
+                        self.atoms.XdndActionCopy
+                    } else {
+                        0
+                    };
+
+                    self.send(
+                        window,
+                        self.atoms.XdndPosition,
+                        [
+                            self.window,
+                            0,
+                            ((x as u16 as u32) << 16) | y as u16 as u32,
+                            CURRENT_TIME,
+                            action,
+                        ],
+                    )?;
+
+                    if let Some(target) = self.target.as_mut() {
+                        target.waiting_status = true;
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        fn process_events(&mut self) -> Result<(), DragError> {
+            while let Some(event) = self.connection.poll_for_event()? {
+                match event {
+                    Event::SelectionRequest(request) => self.send_selection(request)?,
+                    Event::ClientMessage(message) if message.type_ == self.atoms.XdndStatus => {
+                        let [window, flags, ..] = message.data.as_data32();
+
+                        if let Some(target) = self
+                            .target
+                            .as_mut()
+                            .filter(|target| target.window == window)
+                        {
+                            target.accepted = flags & 1 == 1;
+                            target.waiting_status = false;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Find the window under the cursor that accepts drops, and its XDND version.
+        /// The windows of this process are ignored.
+        fn find_target(&self, x: i16, y: i16) -> Result<Option<(Window, u32)>, DragError> {
+            let mut window = self.root;
+
+            loop {
+                let child = self
+                    .connection
+                    .translate_coordinates(self.root, window, x, y)?
+                    .reply()?
+                    .child;
+
+                if child == NONE {
+                    return Ok(None);
+                }
+
+                if let Some(version) = self.xdnd_version(child)? {
+                    if self.is_own_window(child)? {
+                        return Ok(None);
+                    }
+
+                    return Ok(Some((child, version.min(XDND_VERSION))));
+                }
+
+                window = child;
+            }
+        }
+
+        fn xdnd_version(&self, window: Window) -> Result<Option<u32>, DragError> {
+            let reply = self
+                .connection
+                .get_property(false, window, self.atoms.XdndAware, AtomEnum::ATOM, 0, 1)?
+                .reply()?;
+
+            Ok(reply.value32().and_then(|mut values| values.next()))
+        }
+
+        fn is_own_window(&self, window: Window) -> Result<bool, DragError> {
+            let reply = self
+                .connection
+                .get_property(
+                    false,
+                    window,
+                    self.atoms._NET_WM_PID,
+                    AtomEnum::CARDINAL,
+                    0,
+                    1,
+                )?
+                .reply()?;
+
+            Ok(reply
+                .value32()
+                .and_then(|mut values| values.next())
+                .is_some_and(|pid| pid == std::process::id()))
+        }
+
+        fn send_selection(&self, request: SelectionRequestEvent) -> Result<(), DragError> {
+            let mut property = request.property;
+
+            if request.selection != self.atoms.XdndSelection {
+                property = NONE;
+            } else if request.target == self.atoms.TEXT_URI_LIST {
+                self.connection.change_property8(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    self.atoms.TEXT_URI_LIST,
+                    &self.data,
+                )?;
+            } else if request.target == self.atoms.TARGETS {
+                self.connection.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &[self.atoms.TARGETS, self.atoms.TEXT_URI_LIST],
+                )?;
+            } else {
+                property = NONE;
+            }
+
+            let notify = SelectionNotifyEvent {
+                response_type: SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property,
+            };
+
+            self.connection
+                .send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+            self.connection.flush()?;
+
+            Ok(())
+        }
+
+        fn send(&self, window: Window, message_type: u32, data: [u32; 5]) -> Result<(), DragError> {
+            let event = ClientMessageEvent::new(32, window, message_type, data);
+
+            self.connection
+                .send_event(false, window, EventMask::NO_EVENT, event)?;
+            self.connection.flush()?;
+
+            Ok(())
+        }
+    }
+
+    impl<C: Connection> Drop for DragSource<'_, C> {
+        fn drop(&mut self) {
+            let _ = self.connection.destroy_window(self.window);
+            let _ = self.connection.flush();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{path::PathBuf, thread};
+
+        use x11rb::{
+            connection::Connection,
+            protocol::{
+                xproto::{
+                    AtomEnum, ClientMessageEvent, ConnectionExt as _, CreateWindowAux, EventMask,
+                    PropMode, WindowClass,
+                },
+                Event,
+            },
+            wrapper::ConnectionExt as _,
+            COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME,
+        };
+
+        use super::{Atoms, DragSource};
+        use crate::drag_out::uri_list;
+
+        /// Drag a file on a local drop target and check the target receives the URI.
+        #[test]
+        #[ignore = "requires an X11 display"]
+        fn test_drag_to_local_target() {
+            let (connection, screen) = x11rb::connect(None).unwrap();
+            let root = connection.setup().roots[screen].root;
+            let atoms = Atoms::new(&connection).unwrap().reply().unwrap();
+            let target = connection.generate_id().unwrap();
+
+            connection
+                .create_window(
+                    COPY_DEPTH_FROM_PARENT,
+                    target,
+                    root,
+                    0,
+                    0,
+                    100,
+                    100,
+                    0,
+                    WindowClass::INPUT_OUTPUT,
+                    COPY_FROM_PARENT,
+                    &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                )
+                .unwrap();
+            connection
+                .change_property32(
+                    PropMode::REPLACE,
+                    target,
+                    atoms.XdndAware,
+                    AtomEnum::ATOM,
+                    &[5],
+                )
+                .unwrap();
+            connection.map_window(target).unwrap();
+            connection.sync().unwrap();
+
+            let paths = vec![PathBuf::from("/samples/Kick 01.wav")];
+            let expected = uri_list(&paths);
+            let source = thread::spawn(move || {
+                let (connection, screen) = x11rb::connect(None).unwrap();
+                let root = connection.setup().roots[screen].root;
+                let mut source =
+                    DragSource::new(&connection, root, uri_list(&paths).into_bytes()).unwrap();
+                let mut positions = vec![(50, 50), (60, 60)].into_iter();
+
+                source.run(|_| Ok(positions.next())).unwrap();
+            });
+
+            let mut received = None;
+
+            while received.is_none() {
+                match connection.wait_for_event().unwrap() {
+                    Event::ClientMessage(message) if message.type_ == atoms.XdndPosition => {
+                        let [source, ..] = message.data.as_data32();
+                        let status = ClientMessageEvent::new(
+                            32,
+                            source,
+                            atoms.XdndStatus,
+                            [target, 1, 0, 0, atoms.XdndActionCopy],
+                        );
+
+                        connection
+                            .send_event(false, source, EventMask::NO_EVENT, status)
+                            .unwrap();
+                        connection.flush().unwrap();
+                    }
+                    Event::ClientMessage(message) if message.type_ == atoms.XdndDrop => {
+                        connection
+                            .convert_selection(
+                                target,
+                                atoms.XdndSelection,
+                                atoms.TEXT_URI_LIST,
+                                atoms.XdndSelection,
+                                CURRENT_TIME,
+                            )
+                            .unwrap();
+                        connection.flush().unwrap();
+                    }
+                    Event::SelectionNotify(notify) => {
+                        let reply = connection
+                            .get_property(
+                                true,
+                                target,
+                                notify.property,
+                                atoms.TEXT_URI_LIST,
+                                0,
+                                u32::MAX,
+                            )
+                            .unwrap()
+                            .reply()
+                            .unwrap();
+                        let source = connection
+                            .get_selection_owner(atoms.XdndSelection)
+                            .unwrap()
+                            .reply()
+                            .unwrap()
+                            .owner;
+                        let finished = ClientMessageEvent::new(
+                            32,
+                            source,
+                            atoms.XdndFinished,
+                            [target, 1, atoms.XdndActionCopy, 0, 0],
+                        );
+
+                        connection
+                            .send_event(false, source, EventMask::NO_EVENT, finished)
+                            .unwrap();
+                        connection.flush().unwrap();
+                        received = Some(String::from_utf8(reply.value).unwrap());
+                    }
+                    _ => (),
+                }
+            }
+
+            source.join().unwrap();
+
+            assert_eq!(received.unwrap(), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use iced::Point;
+
+    use super::{file_uri, uri_list, DragGesture};
+
+    #[test]
+    fn test_uri_list() {
+        let paths = [
+            PathBuf::from("/samples/Kick 01.wav"),
+            PathBuf::from("/samples/snare#2.wav"),
+        ];
+
+        assert_eq!(
+            uri_list(&paths),
+            "file:///samples/Kick%2001.wav\r\nfile:///samples/snare%232.wav\r\n"
+        );
+    }
+
+    #[test]
+    fn test_file_uri_non_ascii() {
+        // Each byte of the UTF-8 encoding is escaped.
+        assert_eq!(
+            file_uri(Path::new("/samples/Caisse claire é.wav")),
+            "file:///samples/Caisse%20claire%20%C3%A9.wav"
+        );
+        assert_eq!(
+            file_uri(Path::new("/サンプル/kick.wav")),
+            "file:///%E3%82%B5%E3%83%B3%E3%83%97%E3%83%AB/kick.wav"
+        );
+    }
+
+    #[test]
+    fn test_drag_gesture() {
+        let mut gesture = DragGesture::default();
+
+        assert!(!gesture.moved(Point::new(0.0, 0.0)));
+
+        gesture.press();
+
+        assert!(!gesture.moved(Point::new(10.0, 10.0)));
+        assert!(!gesture.moved(Point::new(12.0, 12.0)));
+        assert!(gesture.moved(Point::new(20.0, 10.0)));
+        assert!(!gesture.moved(Point::new(40.0, 10.0)));
+
+        gesture.press();
+        gesture.release();
+
+        assert!(!gesture.moved(Point::new(10.0, 10.0)));
+        assert!(!gesture.moved(Point::new(40.0, 10.0)));
+    }
+}
@@ -1,8 +1,93 @@
-use std::sync::Arc;
+use std::{f32::consts::PI, fmt, sync::Arc};
 
 use rustfft::{num_complex::Complex, Fft, FftPlanner};
 
-pub struct FftProcessor<const FFT_SIZE: usize> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowFunction {
+    #[default]
+    Hann,
+    /// Lower leakage than Hann, at the cost of wider peaks.
+    BlackmanHarris,
+    /// Accurate amplitudes, very wide peaks.
+    FlatTop,
+}
+
+impl WindowFunction {
+    pub const ALL: [WindowFunction; 3] = [
+        WindowFunction::Hann,
+        WindowFunction::BlackmanHarris,
+        WindowFunction::FlatTop,
+    ];
+
+    /// The name used in the configuration file.
+    pub fn name(self) -> &'static str {
+        match self {
+            WindowFunction::Hann => "hann",
+            WindowFunction::BlackmanHarris => "blackman-harris",
+            WindowFunction::FlatTop => "flat-top",
+        }
+    }
+
+    /// The coefficients of the window as a sum of cosines.
+    fn coefficients(self) -> &'static [f32] {
+        match self {
+            WindowFunction::Hann => &[0.5, 0.5],
+            WindowFunction::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            WindowFunction::FlatTop => &[
+                0.215_578_95,
+                0.416_631_58,
+                0.277_263_16,
+                0.083_578_95,
+                0.006_947_37,
+            ],
+        }
+    }
+
+    /// The mean of the window, the magnitude of a sine is scaled by it.
+    fn coherent_gain(self) -> f32 {
+        self.coefficients()[0]
+    }
+
+    fn samples(self, size: usize) -> impl Iterator<Item = f32> {
+        let denominator = size.saturating_sub(1).max(1) as f32;
+
+        (0..size).map(move |n| {
+            self.coefficients()
+                .iter()
+                .enumerate()
+                .map(|(k, coefficient)| {
+                    let sign = if k % 2 == 0 { 1f32 } else { -1f32 };
+
+                    sign * coefficient * (2.0 * PI * k as f32 * n as f32 / denominator).cos()
+                })
+                .sum::<f32>()
+        })
+    }
+}
+
+impl fmt::Display for WindowFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FftSettings {
+    pub size: usize,
+    pub window: WindowFunction,
+    /// The percentage of a block analyzed again with the next block.
+    pub overlap: usize,
+}
+
+impl FftSettings {
+    /// The number of samples between two blocks.
+    pub fn hop_size(&self) -> usize {
+        (self.size - self.size * self.overlap / 100).max(1)
+    }
+}
+
+pub struct FftProcessor {
+    settings: FftSettings,
     scratch_buffer: Box<[Complex<f32>]>,
     fft_input_buffer: Box<[Complex<f32>]>,
     temporary: Vec<f32>,
@@ -10,19 +95,18 @@ pub struct FftProcessor<const FFT_SIZE: usize> {
     fft: Arc<dyn Fft<f32>>,
 }
 
-impl<const FFT_SIZE: usize> FftProcessor<FFT_SIZE> {
-    pub fn new() -> Self {
+impl FftProcessor {
+    pub fn new(settings: FftSettings) -> Self {
+        let fft_size = settings.size;
         let mut fft_planer = FftPlanner::new();
-        let fft = fft_planer.plan_fft_forward(FFT_SIZE);
+        let fft = fft_planer.plan_fft_forward(fft_size);
 
         Self {
-            scratch_buffer: Box::new([Complex::ZERO; FFT_SIZE]),
-            fft_input_buffer: Box::new([Complex::ZERO; FFT_SIZE]),
-            window: apodize::hanning_iter(FFT_SIZE)
-                .map(|n| n as f32)
-                .collect::<Vec<_>>()
-                .into_boxed_slice(),
-            temporary: Vec::with_capacity(FFT_SIZE),
+            settings,
+            scratch_buffer: vec![Complex::ZERO; fft.get_inplace_scratch_len()].into_boxed_slice(),
+            fft_input_buffer: vec![Complex::ZERO; fft_size].into_boxed_slice(),
+            window: settings.window.samples(fft_size).collect(),
+            temporary: Vec::with_capacity(fft_size),
             fft,
         }
     }
@@ -31,14 +115,18 @@ impl<const FFT_SIZE: usize> FftProcessor<FFT_SIZE> {
         self.temporary.clear();
     }
 
+    /// Add the buffer to the samples to analyze and process the next block if there are enough
+    /// samples. Call it again with an empty buffer to process the following blocks.
     pub fn process(&mut self, buffer: &[f32]) -> Option<std::slice::Iter<'_, Complex<f32>>> {
+        let fft_size = self.settings.size;
+
         self.temporary.extend(buffer);
 
-        if self.temporary.len() >= FFT_SIZE {
+        if self.temporary.len() >= fft_size {
             for ((result, window), fft_input_buffer) in self
                 .temporary
                 .iter()
-                .take(FFT_SIZE)
+                .take(fft_size)
                 .zip(self.window.iter())
                 .zip(self.fft_input_buffer.iter_mut())
             {
@@ -48,7 +136,7 @@ impl<const FFT_SIZE: usize> FftProcessor<FFT_SIZE> {
             self.fft
                 .process_with_scratch(&mut self.fft_input_buffer, &mut self.scratch_buffer);
 
-            self.temporary.drain(0..FFT_SIZE);
+            self.temporary.drain(0..self.settings.hop_size());
 
             Some(self.fft_input_buffer.iter())
         } else {
@@ -57,6 +145,71 @@ impl<const FFT_SIZE: usize> FftProcessor<FFT_SIZE> {
     }
 
     pub const fn fft_size(&self) -> usize {
-        FFT_SIZE
+        self.settings.size
+    }
+
+    pub const fn settings(&self) -> FftSettings {
+        self.settings
+    }
+
+    /// The magnitude of a full scale sine, with the Hann window it is half the FFT size.
+    pub fn magnitude_zero_db(&self) -> f32 {
+        self.settings.size as f32 * self.settings.window.coherent_gain()
+    }
+
+    /// The number of bins over which the power of a sine is spread by the window.
+    pub fn noise_bandwidth(&self) -> f32 {
+        let sum = self.window.iter().sum::<f32>();
+        let sum_of_squares = self
+            .window
+            .iter()
+            .map(|sample| sample * sample)
+            .sum::<f32>();
+
+        self.settings.size as f32 * sum_of_squares / (sum * sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{FftProcessor, FftSettings, WindowFunction};
+
+    #[rstest]
+    #[case(WindowFunction::Hann)]
+    #[case(WindowFunction::BlackmanHarris)]
+    #[case(WindowFunction::FlatTop)]
+    fn test_window(#[case] window: WindowFunction) {
+        let samples: Vec<f32> = window.samples(1025).collect();
+
+        assert!(samples[0].abs() < 0.001);
+        assert!(samples[1024].abs() < 0.001);
+        assert!((samples[512] - 1.0).abs() < 0.001);
+
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+
+        assert!((mean - window.coherent_gain()).abs() < 0.001);
+    }
+
+    #[rstest]
+    #[case(0, 4)]
+    #[case(50, 7)]
+    #[case(75, 13)]
+    fn test_overlap(#[case] overlap: usize, #[case] expected_blocks: usize) {
+        let mut processor = FftProcessor::new(FftSettings {
+            size: 512,
+            window: WindowFunction::Hann,
+            overlap,
+        });
+        let mut input: &[f32] = &[0.0; 512 * 4];
+        let mut blocks = 0;
+
+        while processor.process(input).is_some() {
+            blocks += 1;
+            input = &[];
+        }
+
+        assert_eq!(blocks, expected_blocks);
     }
 }
@@ -0,0 +1,282 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use iced::futures::{SinkExt, Stream};
+use rodio::Source;
+use symphonia::core::{
+    formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint,
+};
+
+use crate::wavpack::WavPackDecoder;
+
+/// Information about a file that is expensive to get so it is loaded only when needed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileDetails {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// The audio properties were read, they are still unknown if the file can't be decoded.
+    pub probed: bool,
+    /// The audio properties are only known for the files that can be decoded.
+    pub duration: Option<Duration>,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+/// The files read before their details are sent.
+const CHUNK_SIZE: usize = 32;
+
+/// Load the details of the files, they are sent by chunks as they are read.
+/// The audio properties are only read if `probe` is true.
+/// The files that can't be read are ignored.
+pub fn load_file_details<T: Send + 'static>(
+    files: Vec<(T, PathBuf)>,
+    probe: bool,
+) -> impl Stream<Item = Vec<(T, FileDetails)>> {
+    iced::stream::channel(4, async move |mut output| {
+        let mut files = files.into_iter();
+
+        loop {
+            let chunk: Vec<(T, PathBuf)> = files.by_ref().take(CHUNK_SIZE).collect();
+
+            if chunk.is_empty() {
+                break;
+            }
+
+            // Reading the headers is blocking.
+            let details = tokio::task::spawn_blocking(move || {
+                chunk
+                    .into_iter()
+                    .filter_map(|(key, path)| {
+                        read_file_details(&path, probe).map(|details| (key, details))
+                    })
+                    .collect()
+            })
+            .await
+            .unwrap_or_default();
+
+            if output.send(details).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+fn read_file_details(path: &Path, probe: bool) -> Option<FileDetails> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mut details = FileDetails {
+        size: metadata.len(),
+        modified: metadata.modified().ok(),
+        probed: probe,
+        ..Default::default()
+    };
+
+    if probe {
+        read_audio_properties(path, &mut details);
+    }
+
+    Some(details)
+}
+
+/// Only the headers are read, nothing is decoded.
+fn read_audio_properties(path: &Path, details: &mut FileDetails) {
+    let Ok(file) = File::open(path) else {
+        return;
+    };
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+
+    if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+        hint.with_extension(&extension.to_lowercase());
+    }
+
+    let Ok(probed) = symphonia::default::get_probe().format(
+        &hint,
+        stream,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        read_wavpack_properties(path, details);
+        return;
+    };
+    let Some(track) = probed.format.default_track() else {
+        return;
+    };
+    let parameters = &track.codec_params;
+
+    details.sample_rate = parameters.sample_rate;
+    details.bit_depth = parameters.bits_per_sample;
+    details.channels = parameters.channels.map(|channels| channels.count() as u16);
+    details.duration = parameters
+        .n_frames
+        .zip(parameters.sample_rate)
+        .map(|(frames, sample_rate)| Duration::from_secs_f64(frames as f64 / sample_rate as f64));
+}
+
+/// WavPack is not implemented by symphonia, the properties are read from the first blocks.
+fn read_wavpack_properties(path: &Path, details: &mut FileDetails) {
+    let Ok(file) = File::open(path) else {
+        return;
+    };
+    let Ok(decoder) = WavPackDecoder::new(BufReader::new(file)) else {
+        return;
+    };
+
+    details.sample_rate = Some(decoder.sample_rate());
+    details.bit_depth = Some(decoder.bits_per_sample());
+    details.channels = Some(decoder.channels());
+    details.duration = decoder.total_duration();
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    format!(
+        "{}:{:02}.{:03}",
+        seconds / 60,
+        seconds % 60,
+        duration.subsec_millis()
+    )
+}
+
+pub fn format_sample_rate(sample_rate: u32) -> String {
+    if sample_rate.is_multiple_of(1000) {
+        format!("{} kHz", sample_rate / 1000)
+    } else {
+        format!("{:.1} kHz", sample_rate as f32 / 1000.0)
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if size < 1000 {
+        return format!("{size} B");
+    }
+
+    let mut size = size as f64 / 1000.0;
+    let mut unit = 0;
+
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Format as "YYYY-MM-DD HH:MM" in UTC.
+pub fn format_modified(modified: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(modified)
+        .to_string()
+        .replace('T', " ")
+        .chars()
+        .take(16)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use iced::futures::StreamExt;
+    use rstest::rstest;
+
+    use super::{
+        format_duration, format_modified, format_sample_rate, format_size, load_file_details,
+        CHUNK_SIZE,
+    };
+
+    #[tokio::test]
+    async fn test_load_file_details() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("audio")
+            .join("test_sine_mono.wav");
+
+        let details: Vec<_> = load_file_details(
+            vec![(0, path.clone()), (1, PathBuf::from("missing.wav"))],
+            true,
+        )
+        .concat()
+        .await;
+
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].0, 0);
+        assert!(details[0].1.size > 0);
+        assert!(details[0].1.probed);
+        assert!(details[0].1.duration.is_some());
+        assert!(details[0].1.sample_rate.is_some());
+        assert_eq!(details[0].1.channels, Some(1));
+
+        let details: Vec<_> = load_file_details(vec![(0, path)], false).concat().await;
+
+        assert!(details[0].1.size > 0);
+        assert!(!details[0].1.probed);
+        assert_eq!(details[0].1.duration, None);
+    }
+
+    #[tokio::test]
+    async fn test_load_wavpack_details() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("audio")
+            .join("test_sine_mono.wv");
+
+        let details: Vec<_> = load_file_details(vec![(0, path)], true).concat().await;
+
+        assert_eq!(details[0].1.duration, Some(Duration::from_secs(2)));
+        assert_eq!(details[0].1.sample_rate, Some(48000));
+        assert_eq!(details[0].1.bit_depth, Some(16));
+        assert_eq!(details[0].1.channels, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_load_file_details_chunks() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("audio")
+            .join("test_sine_mono.wav");
+        let files = (0..CHUNK_SIZE + 1).map(|key| (key, path.clone())).collect();
+        let chunks: Vec<Vec<_>> = load_file_details(files, false).collect().await;
+
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            [CHUNK_SIZE, 1]
+        );
+    }
+
+    #[rstest]
+    #[case(Duration::from_millis(1500), "0:01.500")]
+    #[case(Duration::from_secs(125), "2:05.000")]
+    fn test_format_duration(#[case] duration: Duration, #[case] expected: &str) {
+        assert_eq!(format_duration(duration), expected);
+    }
+
+    #[rstest]
+    #[case(44100, "44.1 kHz")]
+    #[case(48000, "48 kHz")]
+    fn test_format_sample_rate(#[case] sample_rate: u32, #[case] expected: &str) {
+        assert_eq!(format_sample_rate(sample_rate), expected);
+    }
+
+    #[rstest]
+    #[case(999, "999 B")]
+    #[case(1500, "1.5 KB")]
+    #[case(2_500_000, "2.5 MB")]
+    fn test_format_size(#[case] size: u64, #[case] expected: &str) {
+        assert_eq!(format_size(size), expected);
+    }
+
+    #[test]
+    fn test_format_modified() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(86400 + 3600 + 60);
+
+        assert_eq!(format_modified(modified), "1970-01-02 01:01");
+    }
+}
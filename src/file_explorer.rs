@@ -1,82 +1,709 @@
 use std::{
-    cell::RefCell,
-    collections::{BTreeMap, VecDeque},
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
     ffi::{OsStr, OsString},
-    ops::Deref,
     path::{Path, PathBuf},
-    rc::{Rc, Weak},
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
 };
 
 use iced::{
-    widget::{row, scrollable, svg, text, Column, MouseArea, Space},
-    Element, Length, Task,
+    alignment::Vertical,
+    mouse,
+    widget::{
+        column, container, row, scrollable,
+        scrollable::{AbsoluteOffset, Viewport},
+        svg, text,
+        text::Wrapping,
+        text_input, Column, MouseArea, Row, Space,
+    },
+    Element, Length, Padding, Point, Task, Theme,
+};
+
+use crate::{
+    drag_out::{self, DragGesture},
+    file_details::{self, load_file_details, FileDetails},
+    file_operations, load_directory_entries, load_entry, new_entry,
+    tags::{self, Tags},
+    ui,
 };
 
-use crate::{load_directory_entries, ui};
+static RENAME_INPUT: LazyLock<text_input::Id> = LazyLock::new(|| text_input::Id::new("rename"));
+static TREE_SCROLLABLE: LazyLock<scrollable::Id> =
+    LazyLock::new(|| scrollable::Id::new("explorer_tree"));
+
+/// The rows moved by PageUp and PageDown until the size of the tree is known.
+const DEFAULT_PAGE_ROWS: usize = 10;
+
+/// Options controlling which entries are listed by `load_directory_entries()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListingOptions {
+    /// List the files that can't be played, they are displayed greyed-out.
+    pub show_all_files: bool,
+    /// List the files and directories whose name starts with a dot.
+    pub show_hidden: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Duration,
+    SampleRate,
+    BitDepth,
+    Channels,
+}
+
+impl SortKey {
+    /// The keys displayed in the header when the details are hidden.
+    const COMPACT: [SortKey; 4] = [
+        SortKey::Name,
+        SortKey::Size,
+        SortKey::Modified,
+        SortKey::Duration,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Size => "Size",
+            SortKey::Modified => "Date",
+            SortKey::Duration => "Duration",
+            SortKey::SampleRate => "Rate",
+            SortKey::BitDepth => "Bits",
+            SortKey::Channels => "Channels",
+        }
+    }
+
+    /// Returns true if sorting requires the details of the files.
+    fn needs_details(&self) -> bool {
+        !matches!(self, SortKey::Name)
+    }
+
+    /// Returns true if sorting requires the audio properties, which are read from the headers.
+    fn needs_audio_properties(&self) -> bool {
+        matches!(
+            self,
+            SortKey::Duration | SortKey::SampleRate | SortKey::BitDepth | SortKey::Channels
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    fn apply(&self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+
+    fn toggle(&self) -> SortOrder {
+        match self {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    /// Directories are always before the files, they are sorted by name
+    /// unless the sort is by name.
+    fn compare(&self, left: &Node, right: &Node) -> Ordering {
+        let by_name = || compare_names(left.path_component(), right.path_component());
+
+        match (left.is_directory(), right.is_directory()) {
+            (true, false) => return Ordering::Less,
+            (false, true) => return Ordering::Greater,
+            (true, true) if self.key != SortKey::Name => return by_name(),
+            _ => (),
+        }
+
+        let (left, right) = (left.details(), right.details());
+
+        match self.key {
+            SortKey::Name => self.order.apply(by_name()),
+            SortKey::Size => compare_values(
+                left.map(|details| details.size),
+                right.map(|details| details.size),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Modified => compare_values(
+                left.and_then(|details| details.modified),
+                right.and_then(|details| details.modified),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Duration => compare_values(
+                left.and_then(|details| details.duration),
+                right.and_then(|details| details.duration),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::SampleRate => compare_values(
+                left.and_then(|details| details.sample_rate),
+                right.and_then(|details| details.sample_rate),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::BitDepth => compare_values(
+                left.and_then(|details| details.bit_depth),
+                right.and_then(|details| details.bit_depth),
+                self.order,
+            )
+            .then_with(by_name),
+            SortKey::Channels => compare_values(
+                left.and_then(|details| details.channels),
+                right.and_then(|details| details.channels),
+                self.order,
+            )
+            .then_with(by_name),
+        }
+    }
+}
+
+/// The missing values are always last, whatever the order.
+fn compare_values<T: Ord>(left: Option<T>, right: Option<T>, order: SortOrder) -> Ordering {
+    match (left, right) {
+        (Some(left), Some(right)) => order.apply(left.cmp(&right)),
+        (left, right) => left.is_none().cmp(&right.is_none()),
+    }
+}
+
+/// The columns of the details view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetailsColumn {
+    Duration,
+    SampleRate,
+    BitDepth,
+    Channels,
+    Size,
+    Modified,
+}
+
+impl DetailsColumn {
+    const ALL: [DetailsColumn; 6] = [
+        DetailsColumn::Duration,
+        DetailsColumn::SampleRate,
+        DetailsColumn::BitDepth,
+        DetailsColumn::Channels,
+        DetailsColumn::Size,
+        DetailsColumn::Modified,
+    ];
+    const MIN_WIDTH: f32 = 30.0;
+
+    fn sort_key(&self) -> SortKey {
+        match self {
+            DetailsColumn::Duration => SortKey::Duration,
+            DetailsColumn::SampleRate => SortKey::SampleRate,
+            DetailsColumn::BitDepth => SortKey::BitDepth,
+            DetailsColumn::Channels => SortKey::Channels,
+            DetailsColumn::Size => SortKey::Size,
+            DetailsColumn::Modified => SortKey::Modified,
+        }
+    }
+
+    fn default_width(&self) -> f32 {
+        match self {
+            DetailsColumn::Duration => 70.0,
+            DetailsColumn::SampleRate => 70.0,
+            DetailsColumn::BitDepth => 40.0,
+            DetailsColumn::Channels => 60.0,
+            DetailsColumn::Size => 70.0,
+            DetailsColumn::Modified => 120.0,
+        }
+    }
+
+    fn format(&self, details: &FileDetails) -> String {
+        match self {
+            DetailsColumn::Duration => details.duration.map(file_details::format_duration),
+            DetailsColumn::SampleRate => details.sample_rate.map(file_details::format_sample_rate),
+            DetailsColumn::BitDepth => details.bit_depth.map(|bit_depth| bit_depth.to_string()),
+            DetailsColumn::Channels => details.channels.map(|channels| channels.to_string()),
+            DetailsColumn::Size => Some(file_details::format_size(details.size)),
+            DetailsColumn::Modified => details.modified.map(file_details::format_modified),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// Width of the handle used to resize a column of the details view.
+const RESIZE_HANDLE_WIDTH: f32 = 5.0;
+
+/// State of the details view.
+struct Details {
+    visible: bool,
+    widths: [f32; DetailsColumn::ALL.len()],
+    /// The column being resized and the last position of the cursor.
+    resizing: Option<(usize, Option<f32>)>,
+}
+
+impl Default for Details {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            widths: DetailsColumn::ALL.map(|column| column.default_width()),
+            resizing: None,
+        }
+    }
+}
+
+/// The characters typed to jump to a file, they are forgotten after a pause.
+#[derive(Default)]
+struct TypeAhead {
+    prefix: String,
+    last_input: Option<Instant>,
+}
+
+impl TypeAhead {
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Append the text to the prefix, or start a new prefix after a pause.
+    fn push(&mut self, text: &str, now: Instant) -> &str {
+        if !self.is_active(now) {
+            self.prefix.clear();
+        }
+
+        self.prefix.push_str(&text.to_lowercase());
+        self.last_input = Some(now);
+
+        &self.prefix
+    }
+
+    /// A prefix is being typed, the next text typed is appended to it.
+    fn is_active(&self, now: Instant) -> bool {
+        self.last_input
+            .is_some_and(|last_input| now.duration_since(last_input) <= Self::TIMEOUT)
+    }
+}
+
+/// Natural and case-insensitive order, "Kick 2" is before "Kick 10".
+pub fn compare_names(left: &OsStr, right: &OsStr) -> Ordering {
+    natord::compare_ignore_case(&left.to_string_lossy(), &right.to_string_lossy())
+        .then_with(|| left.cmp(right))
+}
 
 pub struct FileExplorer {
     model: Option<FileExplorerModel>,
     directory_icon: svg::Handle,
+    extensions: Arc<Vec<String>>,
+    options: ListingOptions,
+    sort: Sort,
+    details: Details,
+    drag: DragGesture,
+    /// The file to select once its directory is loaded.
+    pending_selection: Option<PathBuf>,
+    /// The node being renamed and the name edited by the user.
+    renaming: Option<(NodeId, String)>,
+    type_ahead: TypeAhead,
+    /// The visible part of the tree, known once the tree is displayed.
+    viewport: Option<Viewport>,
 }
 
 impl FileExplorer {
-    pub fn new(directory_icon: svg::Handle) -> Self {
+    pub fn new(directory_icon: svg::Handle, extensions: Arc<Vec<String>>) -> Self {
         Self {
             model: None,
             directory_icon,
+            extensions,
+            options: ListingOptions::default(),
+            sort: Sort::default(),
+            details: Details::default(),
+            drag: DragGesture::default(),
+            pending_selection: None,
+            renaming: None,
+            type_ahead: TypeAhead::default(),
+            viewport: None,
+        }
+    }
+
+    /// A name is being typed to jump to a file, the keys typed go to it before the key bindings.
+    pub fn is_typing_ahead(&self) -> bool {
+        self.type_ahead.is_active(Instant::now())
+    }
+
+    /// Change the extensions of the files displayed.
+    /// The loaded directories are reloaded if the extensions changed.
+    pub fn set_extensions(&mut self, extensions: Arc<Vec<String>>) -> Task<crate::Message> {
+        if self.extensions == extensions {
+            return Task::none();
+        }
+
+        self.extensions = extensions;
+        self.reload()
+    }
+
+    /// Reload the content of every loaded directory.
+    /// The expanded and collapsed directories are preserved.
+    fn reload(&self) -> Task<crate::Message> {
+        let Some(model) = self.model.as_ref() else {
+            return Task::none();
+        };
+
+        Task::batch(
+            model
+                .loaded_directories()
+                .into_iter()
+                .map(|id| self.reload_directory(id)),
+        )
+    }
+
+    /// Reload the content of a directory, its children keep their state.
+    fn reload_directory(&self, id: NodeId) -> Task<crate::Message> {
+        let Some(model) = self.model.as_ref() else {
+            return Task::none();
+        };
+
+        Task::perform(
+            load_directory_entries(model.path(id), self.extensions.clone(), self.options),
+            move |entries| crate::Message::FileExplorer(Message::ChildrenReloaded(id, entries)),
+        )
+    }
+
+    /// Expand a collapsed directory. It is reloaded because it is not watched while collapsed.
+    fn expand(&mut self, id: NodeId) -> Task<crate::Message> {
+        let Some(model) = self
+            .model
+            .as_mut()
+            .filter(|model| matches!(model.status(id), Some(ContainerStatus::Collapsed)))
+        else {
+            return Task::none();
+        };
+
+        model.set_status(id, ContainerStatus::Expanded);
+        self.reload_directory(id)
+    }
+
+    /// Load the details of the files of the directories if the sort or the details view need them.
+    /// The audio properties are only read for the playable files, when the sort or the details
+    /// view display them.
+    fn load_details(&self, directories: Vec<NodeId>) -> Task<crate::Message> {
+        let Some(model) = self.model.as_ref() else {
+            return Task::none();
+        };
+
+        if !self.sort.key.needs_details() && !self.details.visible {
+            return Task::none();
+        }
+
+        let probe = self.details.visible || self.sort.key.needs_audio_properties();
+        let (playable, others): (Vec<(NodeId, PathBuf)>, Vec<(NodeId, PathBuf)>) = directories
+            .into_iter()
+            .flat_map(|id| model.files_without_details(id, probe))
+            .partition(|(id, _)| model.is_playable(*id));
+
+        Task::batch(
+            [(playable, probe), (others, false)]
+                .into_iter()
+                .filter(|(files, _)| !files.is_empty())
+                .map(|(files, probe)| {
+                    Task::run(load_file_details(files, probe), |details| {
+                        crate::Message::FileExplorer(Message::DetailsLoaded(details))
+                    })
+                }),
+        )
+    }
+
+    /// Select the file when its directory is loaded.
+    pub fn select_when_loaded(&mut self, path: PathBuf) {
+        self.pending_selection = Some(path);
+    }
+
+    /// Select the path, its parent directories are loaded and expanded if needed.
+    pub fn reveal(&mut self, path: PathBuf) -> Task<crate::Message> {
+        self.pending_selection = Some(path);
+        self.load_pending_selection()
+    }
+
+    /// Select the pending selection if it is in the tree, otherwise
+    /// load the directory containing it, one level at a time.
+    fn load_pending_selection(&mut self) -> Task<crate::Message> {
+        let (Some(model), Some(path)) = (self.model.as_mut(), self.pending_selection.as_ref())
+        else {
+            return Task::none();
+        };
+
+        if let Some(id) = model.node(path) {
+            let mut ancestors = Vec::new();
+            let mut ancestor = model.parent(id);
+
+            while let Some(ancestor_id) = ancestor {
+                ancestors.push(ancestor_id);
+                ancestor = model.parent(ancestor_id);
+            }
+
+            self.pending_selection = None;
+
+            let expand = Task::batch(
+                ancestors
+                    .into_iter()
+                    .map(|ancestor_id| self.expand(ancestor_id)),
+            );
+
+            return Task::batch([expand, self.set_selection(Some(id))]);
+        }
+
+        let not_loaded = path
+            .ancestors()
+            .skip(1)
+            .find_map(|ancestor| model.node(ancestor).map(|id| (id, ancestor)))
+            .filter(|(id, _)| matches!(model.status(*id), Some(ContainerStatus::NotLoaded)))
+            .map(|(id, directory)| (id, directory.to_path_buf()));
+
+        match not_loaded {
+            Some((id, directory)) => Task::done(crate::Message::FileExplorer(
+                Message::RequestLoad(id, directory),
+            )),
+            // The path does not exist or it is filtered out.
+            None => {
+                self.pending_selection = None;
+                Task::none()
+            }
         }
     }
 
+    pub fn selection(&self) -> Option<NodeId> {
+        self.model.as_ref().and_then(FileExplorerModel::selection)
+    }
+
+    pub fn is_renaming(&self) -> bool {
+        self.renaming.is_some()
+    }
+
+    /// The path of the selected file or directory.
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        let model = self.model.as_ref()?;
+
+        model.selection().map(|id| model.path(id))
+    }
+
+    /// The directories to watch, the root and the expanded directories.
+    pub fn expanded_directories(&self) -> BTreeSet<PathBuf> {
+        self.model
+            .as_ref()
+            .map(FileExplorerModel::expanded_directories)
+            .unwrap_or_default()
+    }
+
+    /// True if the directories to watch changed since the last call, after a directory was
+    /// expanded, collapsed, removed or moved, or another root was opened.
+    pub fn take_expanded_changed(&mut self) -> bool {
+        self.model
+            .as_mut()
+            .is_some_and(FileExplorerModel::take_expanded_changed)
+    }
+
+    pub fn root_path(&self) -> Option<PathBuf> {
+        self.model.as_ref().map(|model| model.path(model.root_id()))
+    }
+
     pub fn set_root_path(&mut self, path: impl AsRef<Path>) -> Task<crate::Message> {
-        self.model = Some(FileExplorerModel::new(
-            path.as_ref().as_os_str().to_os_string(),
-        ));
+        self.pending_selection = None;
+        self.renaming = None;
+
+        let mut model = FileExplorerModel::new(path.as_ref().as_os_str().to_os_string());
+
+        model.set_sort(self.sort);
+        self.model = Some(model);
 
         let root = self.model.as_ref().unwrap().root_id();
 
         Task::perform(
-            load_directory_entries(path.as_ref().to_path_buf()),
+            load_directory_entries(
+                path.as_ref().to_path_buf(),
+                self.extensions.clone(),
+                self.options,
+            ),
             move |entries| crate::Message::FileExplorer(Message::ChildrenLoaded(root, entries)),
         )
     }
 
-    pub fn view(&self) -> Element<crate::Message> {
-        self::view(self.model.as_ref(), self.directory_icon.clone())
+    pub fn view<'a>(&'a self, tags: &'a Tags) -> Element<'a, crate::Message> {
+        let details = self.details.visible.then_some(&self.details.widths);
+        let header = match details {
+            Some(widths) => view_details_header(self.sort, widths),
+            None => view_sort_header(self.sort),
+        };
+        let content = column![
+            view_toolbar(self.options, self.details.visible),
+            header,
+            self::view(
+                self.model.as_ref(),
+                self.directory_icon.clone(),
+                details,
+                self.drag.is_pressed(),
+                self.renaming.as_ref(),
+                self.viewport.as_ref(),
+                tags,
+            ),
+        ];
+
+        if self.details.resizing.is_some() {
+            MouseArea::new(content)
+                .on_move(|position| {
+                    crate::Message::FileExplorer(Message::ColumnResized(position.x))
+                })
+                .on_release(crate::Message::FileExplorer(Message::ResizeEnded))
+                .into()
+        } else {
+            content.into()
+        }
     }
 
     pub fn update(&mut self, message: Message) -> Task<crate::Message> {
         match message {
             Message::RequestLoad(id, path) => {
-                return Task::perform(load_directory_entries(path), move |entries| {
-                    crate::Message::FileExplorer(Message::ChildrenLoaded(id, entries))
-                });
+                return Task::perform(
+                    load_directory_entries(path, self.extensions.clone(), self.options),
+                    move |entries| {
+                        crate::Message::FileExplorer(Message::ChildrenLoaded(id, entries))
+                    },
+                );
             }
             Message::ChildrenLoaded(parent_id, new_entries) => {
                 if let Some(model) = self.model.as_mut() {
                     model.add(parent_id, new_entries);
-                    model.update_linear_index();
+
+                    let contains_pending_selection = self
+                        .pending_selection
+                        .as_ref()
+                        .is_some_and(|path| path.starts_with(model.path(parent_id)));
+                    let load_details = self.load_details(vec![parent_id]);
+
+                    if contains_pending_selection {
+                        return Task::batch([load_details, self.load_pending_selection()]);
+                    }
+
+                    return load_details;
+                }
+            }
+            Message::ChildrenReloaded(parent_id, new_entries) => {
+                if let Some(model) = self.model.as_mut() {
+                    model.replace_children(parent_id, new_entries);
+
+                    return self.load_details(vec![parent_id]);
+                }
+            }
+            Message::DetailsLoaded(details) => {
+                if let Some(model) = self.model.as_mut() {
+                    model.set_details(details);
+                }
+            }
+            Message::SortBy(key) => {
+                self.sort = if self.sort.key == key {
+                    Sort {
+                        key,
+                        order: self.sort.order.toggle(),
+                    }
+                } else {
+                    Sort {
+                        key,
+                        order: SortOrder::Ascending,
+                    }
+                };
+
+                if let Some(model) = self.model.as_mut() {
+                    model.set_sort(self.sort);
+
+                    let directories = model.loaded_directories();
+
+                    return self.load_details(directories);
+                }
+            }
+            Message::ShowAllFiles(show_all_files) => {
+                self.options.show_all_files = show_all_files;
+
+                return self.reload();
+            }
+            Message::ShowHidden(show_hidden) => {
+                self.options.show_hidden = show_hidden;
+
+                return self.reload();
+            }
+            Message::ShowDetails(show_details) => {
+                self.details.visible = show_details;
+
+                if let Some(model) = self.model.as_ref() {
+                    return self.load_details(model.loaded_directories());
                 }
             }
+            Message::ResizeStarted(column) => {
+                self.details.resizing = Some((column, None));
+            }
+            Message::ColumnResized(x) => {
+                if let Some((column, last_x)) = self.details.resizing.as_mut() {
+                    // The handle is on the left of the column so moving it
+                    // to the left makes the column wider.
+                    if let Some(last_x) = last_x {
+                        let width = &mut self.details.widths[*column];
+
+                        *width = (*width - (x - *last_x)).max(DetailsColumn::MIN_WIDTH);
+                    }
+                    *last_x = Some(x);
+                }
+            }
+            Message::ResizeEnded => {
+                self.details.resizing = None;
+            }
             Message::Collapse(id) => {
                 if let Some(model) = self.model.as_mut() {
                     model.set_status(id, ContainerStatus::Collapsed);
-                    model.update_linear_index();
                 }
             }
             Message::Expand(id) => {
-                if let Some(model) = self.model.as_mut() {
-                    model.set_status(id, ContainerStatus::Expanded);
-                    model.update_linear_index();
-                }
+                return self.expand(id);
             }
             Message::Select(id) => {
+                let is_file = self
+                    .model
+                    .as_ref()
+                    .zip(id)
+                    .is_some_and(|(model, id)| !model.is_directory(id));
+
+                if is_file {
+                    self.drag.press();
+                } else {
+                    self.drag.release();
+                }
+
+                if self.renaming.as_ref().map(|(renamed, _)| Some(*renamed)) != Some(id) {
+                    self.renaming = None;
+                }
+
                 return self.set_selection(id);
             }
+            Message::CursorMoved(position) => {
+                if self.drag.moved(position) {
+                    if let Some(model) = self.model.as_ref() {
+                        if let Some(id) = model.selection() {
+                            return drag_out::drag_files(vec![model.path(id)]);
+                        }
+                    }
+                }
+            }
+            Message::Released => {
+                self.drag.release();
+            }
             Message::SelectNext => {
                 if let Some(model) = self.model.as_mut() {
                     if let Some(current_id) = model.selection() {
                         if let Some(id) = model.next(current_id) {
-                            return self.set_selection(Some(id));
+                            return self.select_and_scroll(id);
                         }
                     }
                 }
@@ -85,17 +712,116 @@ impl FileExplorer {
                 if let Some(model) = self.model.as_mut() {
                     if let Some(current_id) = model.selection() {
                         if let Some(id) = model.previous(current_id) {
-                            return self.set_selection(Some(id));
+                            return self.select_and_scroll(id);
                         }
                     }
                 }
             }
-            Message::ExpandCollapseCurrent => {
+            Message::SelectParent => {
+                if let Some(model) = self.model.as_mut() {
+                    if let Some(current_id) = model.selection() {
+                        if matches!(model.status(current_id), Some(ContainerStatus::Expanded)) {
+                            model.set_status(current_id, ContainerStatus::Collapsed);
+                        } else if let Some(parent_id) = model
+                            .parent(current_id)
+                            .filter(|parent_id| *parent_id != model.root_id())
+                        {
+                            return self.select_and_scroll(parent_id);
+                        }
+                    }
+                }
+            }
+            Message::SelectChild => {
                 if let Some(model) = self.model.as_mut() {
                     if let Some(current_id) = model.selection() {
-                        let mut task = model.expand_collapse(current_id);
+                        match model.status(current_id) {
+                            Some(ContainerStatus::Expanded) => {
+                                if let Some(child_id) = model.first_child(current_id) {
+                                    return self.select_and_scroll(child_id);
+                                }
+                            }
+                            Some(ContainerStatus::Collapsed) => {
+                                return self.expand(current_id);
+                            }
+                            Some(ContainerStatus::NotLoaded) => {
+                                let task = model.expand_collapse(
+                                    current_id,
+                                    self.extensions.clone(),
+                                    self.options,
+                                );
+
+                                return task.unwrap_or_else(Task::none);
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+            }
+            Message::SelectFirst => {
+                if let Some(id) = self.model.as_ref().and_then(|model| model.row_node(0)) {
+                    return self.select_and_scroll(id);
+                }
+            }
+            Message::SelectLast => {
+                if let Some(id) = self
+                    .model
+                    .as_ref()
+                    .and_then(|model| model.row_node(model.row_count().checked_sub(1)?))
+                {
+                    return self.select_and_scroll(id);
+                }
+            }
+            Message::PageUp => {
+                let page_rows = self.page_rows();
+
+                if let Some(model) = self.model.as_ref() {
+                    let row = model
+                        .selection()
+                        .and_then(|id| model.row(id))
+                        .map_or(0, |row| row.saturating_sub(page_rows));
 
-                        model.update_linear_index();
+                    if let Some(id) = model.row_node(row) {
+                        return self.select_and_scroll(id);
+                    }
+                }
+            }
+            Message::PageDown => {
+                let page_rows = self.page_rows();
+
+                if let Some(model) = self.model.as_ref() {
+                    let last_row = model.row_count().saturating_sub(1);
+                    let row = model
+                        .selection()
+                        .and_then(|id| model.row(id))
+                        .map_or(0, |row| (row + page_rows).min(last_row));
+
+                    if let Some(id) = model.row_node(row) {
+                        return self.select_and_scroll(id);
+                    }
+                }
+            }
+            Message::TypeAhead(text) => {
+                let prefix = self.type_ahead.push(&text, Instant::now());
+
+                if let Some(id) = self
+                    .model
+                    .as_ref()
+                    .and_then(|model| model.find_sibling(prefix))
+                {
+                    return self.select_and_scroll(id);
+                }
+            }
+            Message::Scrolled(viewport) => {
+                self.viewport = Some(viewport);
+            }
+            Message::ExpandCollapseCurrent => {
+                if let Some(model) = self.model.as_mut() {
+                    if let Some(current_id) = model.selection() {
+                        let mut task = model.expand_collapse(
+                            current_id,
+                            self.extensions.clone(),
+                            self.options,
+                        );
 
                         if task.is_some() {
                             return task.take().unwrap();
@@ -110,27 +836,179 @@ impl FileExplorer {
                     }
                 }
             }
-            Message::Added(path_buf) => {
+            Message::Play(id) => {
+                self.drag.release();
+
+                return self.set_selection(Some(id));
+            }
+            Message::StartRename(id) => {
+                if let Some(model) = self.model.as_ref().filter(|model| id != model.root_id()) {
+                    if let Some(name) = model.path_component(id) {
+                        self.renaming = Some((id, name.to_string_lossy().to_string()));
+
+                        return Task::batch([
+                            text_input::focus(RENAME_INPUT.clone()),
+                            text_input::select_all(RENAME_INPUT.clone()),
+                        ]);
+                    }
+                }
+            }
+            Message::RenameChanged(name) => {
+                if let Some((_, new_name)) = self.renaming.as_mut() {
+                    *new_name = name;
+                }
+            }
+            Message::RenameSubmitted => {
+                if let Some((model, (id, new_name))) = self.model.as_ref().zip(self.renaming.take())
+                {
+                    let path = model.path(id);
+
+                    if path.file_name() != Some(OsStr::new(&new_name)) {
+                        return Task::done(crate::Message::FileOperation(
+                            file_operations::Message::Request(file_operations::Request::Rename {
+                                path,
+                                new_name,
+                            }),
+                        ));
+                    }
+                }
+            }
+            Message::CancelRename => {
+                self.renaming = None;
+            }
+            Message::SelectWhenLoaded(path) => {
+                self.select_when_loaded(path);
+            }
+            Message::Added(path) => {
+                if let Some(model) = self.model.as_ref() {
+                    let in_loaded_directory = path
+                        .parent()
+                        .and_then(|parent| model.node(parent))
+                        .is_some_and(|parent_id| model.is_loaded(parent_id));
+
+                    if in_loaded_directory && model.node(&path).is_none() {
+                        return Task::perform(
+                            load_entry(path.clone(), self.extensions.clone(), self.options),
+                            move |entry| {
+                                crate::Message::FileExplorer(Message::EntryLoaded(path, entry))
+                            },
+                        );
+                    }
+
+                    return self.entry_added(&path);
+                }
+            }
+            Message::EntryLoaded(path, entry) => {
+                if let Some(model) = self.model.as_mut() {
+                    if let Some(entry) = entry {
+                        model.insert(&path, entry);
+                    }
+
+                    return self.entry_added(&path);
+                }
+            }
+            Message::Modified(path) => {
                 if let Some(model) = self.model.as_mut() {
-                    if let Some(parent_path) = path_buf.parent() {
-                        if let Some(id) = model.node(parent_path) {
-                            return Task::perform(
-                                load_directory_entries(parent_path.to_path_buf()),
-                                move |entries| {
-                                    crate::Message::FileExplorer(Message::ChildrenLoaded(
-                                        id, entries,
-                                    ))
-                                },
-                            );
-                        }
+                    if let Some(id) = model.node(&path) {
+                        model.clear_details(id);
+
+                        return self.entry_added(&path);
                     }
                 }
             }
+            Message::Rescan => {
+                return self.reload();
+            }
+            Message::Renamed { from, to } => {
+                let Some(model) = self.model.as_mut() else {
+                    return Task::none();
+                };
+                let Some(id) = model.node(&from) else {
+                    return self.update(Message::Added(to));
+                };
+                let entry = new_entry(&to, model.is_directory(id), &self.extensions, self.options);
+                // A file renamed with another extension may become playable, or not.
+                let same_kind = entry.as_ref().is_some_and(|entry| {
+                    entry.is_directory()
+                        || matches!(entry, NewEntry::File { .. }) == model.is_playable(id)
+                });
+
+                if !(same_kind && model.rename(id, &to)) {
+                    model.remove(id);
+
+                    if let Some(entry) = entry {
+                        model.insert(&to, entry);
+                    }
+                }
+
+                return self.entry_added(&to);
+            }
         }
 
         Task::none()
     }
 
+    /// Load the details of the files next to the new or modified entry and select it if it is
+    /// the pending selection.
+    fn entry_added(&mut self, path: &Path) -> Task<crate::Message> {
+        let Some(parent_id) = self
+            .model
+            .as_ref()
+            .and_then(|model| model.node(path.parent()?))
+        else {
+            return Task::none();
+        };
+        let load_details = self.load_details(vec![parent_id]);
+
+        if self
+            .pending_selection
+            .as_ref()
+            .is_some_and(|pending| pending.starts_with(path))
+        {
+            return Task::batch([load_details, self.load_pending_selection()]);
+        }
+
+        load_details
+    }
+
+    /// Select the node and scroll the tree to show it.
+    fn select_and_scroll(&mut self, id: NodeId) -> Task<crate::Message> {
+        Task::batch([self.set_selection(Some(id)), self.scroll_to(id)])
+    }
+
+    /// Scroll the tree just enough to show the row of the node.
+    fn scroll_to(&self, id: NodeId) -> Task<crate::Message> {
+        let (Some(model), Some(viewport)) = (self.model.as_ref(), self.viewport.as_ref()) else {
+            return Task::none();
+        };
+        let Some(row) = model.row(id) else {
+            return Task::none();
+        };
+        let top = row as f32 * ui::ROW_HEIGHT;
+        let bottom = top + ui::ROW_HEIGHT;
+        let offset = viewport.absolute_offset();
+        let height = viewport.bounds().height;
+
+        let y = if top < offset.y {
+            top
+        } else if bottom > offset.y + height {
+            bottom - height
+        } else {
+            return Task::none();
+        };
+
+        scrollable::scroll_to(TREE_SCROLLABLE.clone(), AbsoluteOffset { x: offset.x, y })
+    }
+
+    /// The number of rows visible in the tree.
+    fn page_rows(&self) -> usize {
+        self.viewport
+            .as_ref()
+            .map_or(DEFAULT_PAGE_ROWS, |viewport| {
+                ((viewport.bounds().height / ui::ROW_HEIGHT) as usize).max(1)
+            })
+    }
+
     fn set_selection(&mut self, id: Option<NodeId>) -> Task<crate::Message> {
         if let Some(model) = self.model.as_mut() {
             model.set_selection(id);
@@ -146,20 +1024,75 @@ impl FileExplorer {
 pub enum Message {
     RequestLoad(NodeId, PathBuf),
     ChildrenLoaded(NodeId, Vec<NewEntry>),
+    /// Like `ChildrenLoaded` but the children missing from the new entries are removed.
+    ChildrenReloaded(NodeId, Vec<NewEntry>),
+    DetailsLoaded(Vec<(NodeId, FileDetails)>),
+    /// Sort by this key, the order is reversed if the key is already used.
+    SortBy(SortKey),
     Collapse(NodeId),
     Expand(NodeId),
     Select(Option<NodeId>),
     SelectNext,
     SelectPrevious,
+    /// Collapse the selected directory, or select the parent.
+    SelectParent,
+    /// Expand the selected directory, or select its first child.
+    SelectChild,
+    SelectFirst,
+    SelectLast,
+    PageUp,
+    PageDown,
+    /// Text typed in the tree, select the first sibling whose name starts with the typed characters.
+    TypeAhead(String),
+    Scrolled(Viewport),
     ExpandCollapseCurrent,
     Removed(PathBuf),
     Added(PathBuf),
+    /// The entry of an added path, `None` if it is not listed.
+    EntryLoaded(PathBuf, Option<NewEntry>),
+    /// A file or a directory has been renamed or moved.
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// The content of a file changed, its details are outdated.
+    Modified(PathBuf),
+    /// Too many changes to handle them one by one, reload the loaded directories.
+    Rescan,
+    /// Select the path once its directory is loaded, see `FileExplorer::select_when_loaded`.
+    SelectWhenLoaded(PathBuf),
+    /// Select and play a file, without starting a drag gesture.
+    Play(NodeId),
+    /// Show a text input to rename the node.
+    StartRename(NodeId),
+    RenameChanged(String),
+    RenameSubmitted,
+    CancelRename,
+    ShowAllFiles(bool),
+    ShowHidden(bool),
+    ShowDetails(bool),
+    /// Start resizing a column of the details view.
+    ResizeStarted(usize),
+    /// The horizontal position of the cursor while resizing a column.
+    ColumnResized(f32),
+    ResizeEnded,
+    /// The cursor moved over the tree while a file is pressed.
+    CursorMoved(Point),
+    Released,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum NewEntry {
-    Directory { path_component: OsString },
-    File { path_component: OsString },
+    Directory {
+        path_component: OsString,
+    },
+    File {
+        path_component: OsString,
+    },
+    /// A file that can't be played.
+    UnsupportedFile {
+        path_component: OsString,
+    },
 }
 
 impl NewEntry {
@@ -167,8 +1100,13 @@ impl NewEntry {
         match self {
             NewEntry::Directory { path_component, .. } => path_component,
             NewEntry::File { path_component, .. } => path_component,
+            NewEntry::UnsupportedFile { path_component, .. } => path_component,
         }
     }
+
+    pub fn is_directory(&self) -> bool {
+        matches!(self, NewEntry::Directory { .. })
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -179,52 +1117,225 @@ pub enum ContainerStatus {
     Empty,
 }
 
+/// The index of a node in the arena, and the generation of its slot.
+/// The slot of a removed node is reused, the generation tells apart the identifiers of its
+/// successive nodes so a former identifier does not designate the new node.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
-pub struct NodeId(usize);
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
 
 #[cfg(test)]
 impl NodeId {
-    pub fn new(id: usize) -> Self {
-        Self(id)
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            generation: 0,
+        }
     }
 }
 
-fn view(tree: Option<&FileExplorerModel>, directory_icon: svg::Handle) -> Element<crate::Message> {
-    const DEPTH_OFFSET: f32 = 20f32;
+fn view<'a>(
+    tree: Option<&'a FileExplorerModel>,
+    directory_icon: svg::Handle,
+    details: Option<&[f32; DetailsColumn::ALL.len()]>,
+    drag_pressed: bool,
+    renaming: Option<&'a (NodeId, String)>,
+    viewport: Option<&Viewport>,
+    tags: &'a Tags,
+) -> Element<'a, crate::Message> {
+    let main_column = match tree {
+        Some(tree) => ui::virtual_list(tree.row_count(), viewport, |index| {
+            let (id, depth) = tree.row_entry(index).expect("the row is in the tree");
+
+            view_row(
+                tree,
+                id,
+                depth,
+                directory_icon.clone(),
+                details,
+                renaming,
+                tags,
+            )
+        }),
+        None => Column::new(),
+    };
 
-    let mut main_column = Column::new();
+    let mouse_area = MouseArea::new(
+        scrollable(main_column)
+            .id(TREE_SCROLLABLE.clone())
+            .on_scroll(|viewport| crate::Message::FileExplorer(Message::Scrolled(viewport)))
+            .width(Length::Fill)
+            .height(Length::Fill),
+    )
+    .on_press(crate::Message::FileExplorer(Message::Select(None)));
+
+    // Only track the cursor while a file is pressed, it could be dragged out.
+    if drag_pressed {
+        mouse_area
+            .on_move(|position| crate::Message::FileExplorer(Message::CursorMoved(position)))
+            .on_release(crate::Message::FileExplorer(Message::Released))
+            .into()
+    } else {
+        mouse_area.into()
+    }
+}
 
-    if let Some(tree) = tree {
-        for (id, depth) in tree.linear_visit() {
-            if id == &tree.root_id() {
-                continue;
+fn view_row<'a>(
+    tree: &'a FileExplorerModel,
+    id: NodeId,
+    depth: usize,
+    directory_icon: svg::Handle,
+    details: Option<&[f32; DetailsColumn::ALL.len()]>,
+    renaming: Option<&'a (NodeId, String)>,
+    tags: &'a Tags,
+) -> Element<'a, crate::Message> {
+    const DEPTH_OFFSET: f32 = 20f32;
+    const DETAILS_FONT_SIZE: u32 = 12;
+
+    let status = tree.status(id).unwrap();
+    let selectable_part = make_selectable_part(tree, id, directory_icon, renaming, tags);
+    let row = row![
+        Space::new(Length::Fixed(depth as f32 * DEPTH_OFFSET), Length::Shrink),
+        show_children_control(tree, id, status),
+        Space::new(Length::Fixed(5f32), Length::Shrink),
+        selectable_part,
+    ]
+    .align_y(Vertical::Center);
+
+    match details {
+        Some(widths) => {
+            let node_details = tree.details(id);
+            let mut row = Row::new()
+                .push(container(row).width(Length::Fill).clip(true))
+                .align_y(Vertical::Center);
+
+            for (column, width) in DetailsColumn::ALL.iter().zip(widths) {
+                let value = node_details
+                    .as_ref()
+                    .map(|details| column.format(details))
+                    .unwrap_or_default();
+
+                row = row.push(
+                    container(text(value).size(DETAILS_FONT_SIZE).wrapping(Wrapping::None))
+                        .width(Length::Fixed(*width + RESIZE_HANDLE_WIDTH))
+                        .clip(true),
+                );
             }
-            let status = tree.status(*id).unwrap();
-            let selectable_part = make_selectable_part(tree, *id, directory_icon.clone());
-            let row = row![
-                Space::new(Length::Fixed(*depth as f32 * DEPTH_OFFSET), Length::Shrink),
-                show_children_control(tree, *id, status),
-                Space::new(Length::Fixed(5f32), Length::Shrink),
-                selectable_part,
-            ];
 
-            main_column = main_column.push(row);
+            row.into()
         }
+        None => row.into(),
     }
-    MouseArea::new(
-        scrollable(main_column)
-            .width(Length::Fill)
-            .height(Length::Fill),
-    )
-    .on_press(crate::Message::FileExplorer(Message::Select(None)))
+}
+
+fn view_toolbar(options: ListingOptions, show_details: bool) -> Element<'static, crate::Message> {
+    row![
+        ui::toggle(
+            "All files",
+            options.show_all_files,
+            crate::Message::FileExplorer(Message::ShowAllFiles(!options.show_all_files)),
+        ),
+        ui::toggle(
+            "Hidden",
+            options.show_hidden,
+            crate::Message::FileExplorer(Message::ShowHidden(!options.show_hidden)),
+        ),
+        ui::toggle(
+            "Details",
+            show_details,
+            crate::Message::FileExplorer(Message::ShowDetails(!show_details)),
+        ),
+    ]
+    .spacing(4)
     .into()
 }
 
-fn make_selectable_part(
-    model: &FileExplorerModel,
+fn sort_label(key: SortKey, sort: Sort) -> String {
+    const ASCENDING: &str = "▲";
+    const DESCENDING: &str = "▼";
+
+    match (sort.key == key, sort.order) {
+        (false, _) => key.label().to_string(),
+        (true, SortOrder::Ascending) => format!("{} {ASCENDING}", key.label()),
+        (true, SortOrder::Descending) => format!("{} {DESCENDING}", key.label()),
+    }
+}
+
+fn view_sort_header(sort: Sort) -> Element<'static, crate::Message> {
+    let mut header = Row::new().spacing(4);
+
+    for key in SortKey::COMPACT {
+        header = header.push(ui::toggle(
+            sort_label(key, sort),
+            sort.key == key,
+            crate::Message::FileExplorer(Message::SortBy(key)),
+        ));
+    }
+
+    header.into()
+}
+
+/// The header of the details view, each column can be sorted and resized
+/// by dragging the handle on its left.
+fn view_details_header(
+    sort: Sort,
+    widths: &[f32; DetailsColumn::ALL.len()],
+) -> Element<'static, crate::Message> {
+    const HEADER_HEIGHT: f32 = 18.0;
+
+    let mut header = Row::new().push(
+        container(ui::toggle(
+            sort_label(SortKey::Name, sort),
+            sort.key == SortKey::Name,
+            crate::Message::FileExplorer(Message::SortBy(SortKey::Name)),
+        ))
+        .width(Length::Fill),
+    );
+
+    for (index, (column, width)) in DetailsColumn::ALL.iter().zip(widths).enumerate() {
+        let key = column.sort_key();
+        let handle = MouseArea::new(
+            container(Space::new(Length::Fixed(1.0), Length::Fixed(HEADER_HEIGHT)))
+                .padding(Padding::from([0.0, (RESIZE_HANDLE_WIDTH - 1.0) / 2.0]))
+                .style(|theme: &Theme| container::Style {
+                    background: Some(theme.extended_palette().background.strong.color.into()),
+                    ..Default::default()
+                }),
+        )
+        .interaction(mouse::Interaction::ResizingHorizontally)
+        .on_press(crate::Message::FileExplorer(Message::ResizeStarted(index)));
+
+        header = header.push(handle).push(
+            container(ui::toggle(
+                sort_label(key, sort),
+                sort.key == key,
+                crate::Message::FileExplorer(Message::SortBy(key)),
+            ))
+            .width(Length::Fixed(*width))
+            .clip(true),
+        );
+    }
+
+    header.into()
+}
+
+fn make_selectable_part<'a>(
+    model: &'a FileExplorerModel,
     id: NodeId,
     directory_icon: svg::Handle,
-) -> Element<crate::Message> {
+    renaming: Option<&'a (NodeId, String)>,
+    tags: &Tags,
+) -> Element<'a, crate::Message> {
+    if let Some((_, new_name)) = renaming.filter(|(renamed, _)| *renamed == id) {
+        return ui::file_entry_input("Name", new_name)
+            .id(RENAME_INPUT.clone())
+            .on_input(|name| crate::Message::FileExplorer(Message::RenameChanged(name)))
+            .on_submit(crate::Message::FileExplorer(Message::RenameSubmitted))
+            .into();
+    }
+
     let path_component = model.path_component(id).unwrap();
     let icon = if model.is_directory(id) {
         Some(directory_icon)
@@ -233,15 +1344,101 @@ fn make_selectable_part(
     };
     let is_selected = model.selection.is_some_and(|selection| selection == id);
     let select_message = crate::Message::FileExplorer(Message::Select(Some(id)));
-
-    ui::file_entry(
-        path_component.into_string().unwrap(),
-        select_message,
-        icon,
-        is_selected,
+    let is_directory = model.is_directory(id);
+    let is_playable = model.is_playable(id);
+    let path = model.path(id);
+    let is_starred = tags.is_starred(&path);
+
+    ui::context_menu(
+        ui::file_entry(
+            tags.label(&path, path_component.to_string_lossy()),
+            select_message,
+            icon,
+            is_selected,
+            !is_directory && !is_playable,
+        ),
+        move || context_menu_items(id, &path, is_directory, is_playable, is_starred),
     )
 }
 
+fn context_menu_items<'a>(
+    id: NodeId,
+    path: &Path,
+    is_directory: bool,
+    is_playable: bool,
+    is_starred: bool,
+) -> Vec<Element<'a, crate::Message>> {
+    use file_operations::Request;
+
+    let request =
+        |request| crate::Message::FileOperation(file_operations::Message::Request(request));
+    let mut items = Vec::new();
+
+    if is_playable {
+        items.push(ui::menu_item(
+            "Play",
+            crate::Message::FileExplorer(Message::Play(id)),
+        ));
+    }
+
+    items.push(ui::menu_item(
+        "Copy path",
+        crate::Message::CopyPath(path.to_path_buf()),
+    ));
+    if !is_directory {
+        items.push(ui::menu_item(
+            "Copy file",
+            crate::Message::CopyFile(path.to_path_buf()),
+        ));
+    }
+
+    items.push(ui::menu_item(
+        "Show in file manager",
+        crate::Message::ShowInFileManager(path.to_path_buf()),
+    ));
+    items.extend(tags::menu_items(path, is_starred));
+    items.push(ui::menu_item(
+        "Rename",
+        crate::Message::FileExplorer(Message::StartRename(id)),
+    ));
+
+    if !is_directory {
+        items.push(ui::menu_item(
+            "Duplicate",
+            request(Request::Duplicate(path.to_path_buf())),
+        ));
+        items.push(ui::menu_item(
+            "Export…",
+            request(Request::Export(path.to_path_buf())),
+        ));
+    }
+
+    let parent = if is_directory {
+        Some(path)
+    } else {
+        path.parent()
+    };
+
+    items.push(ui::menu_item(
+        "Move to…",
+        request(Request::Move(path.to_path_buf())),
+    ));
+    items.extend(parent.map(|parent| {
+        ui::menu_item(
+            "New folder",
+            request(Request::CreateFolder {
+                parent: parent.to_path_buf(),
+            }),
+        )
+    }));
+    items.push(ui::menu_item(
+        "Move to trash",
+        request(Request::Trash(vec![path.to_path_buf()])),
+    ));
+
+    items
+}
+
 fn show_children_control(
     tree: &FileExplorerModel,
     id: NodeId,
@@ -268,304 +1465,569 @@ fn show_children_control(
     }
 }
 
-enum Node {
-    Root {
-        id: NodeId,
-        children: Vec<Rc<RefCell<Node>>>,
-        path_component: OsString,
-    },
+enum NodeKind {
+    Root,
     Directory {
-        id: NodeId,
-        parent: Weak<RefCell<Node>>,
-        children: Vec<Rc<RefCell<Node>>>,
-        path_component: OsString,
         status: ContainerStatus,
     },
     File {
-        id: NodeId,
-        parent: Weak<RefCell<Node>>,
-        path_component: OsString,
+        playable: bool,
+        details: Option<FileDetails>,
     },
 }
 
+struct Node {
+    kind: NodeKind,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    path_component: OsString,
+    path: PathBuf,
+    /// The index of the node in the linear index, while it is visible.
+    row: Option<usize>,
+}
+
 impl Node {
-    fn id(&self) -> NodeId {
-        match self {
-            Node::Root { id, .. } => *id,
-            Node::Directory { id, .. } => *id,
-            Node::File { id, .. } => *id,
+    fn path_component(&self) -> &OsStr {
+        &self.path_component
+    }
+
+    fn status(&self) -> ContainerStatus {
+        match &self.kind {
+            NodeKind::Root => ContainerStatus::Expanded,
+            NodeKind::Directory { status } => *status,
+            NodeKind::File { .. } => ContainerStatus::Empty,
         }
     }
 
-    fn parent(&self) -> Option<NodeId> {
-        match self {
-            Node::Root { .. } => None,
-            Node::Directory { parent, .. } => parent.upgrade().map(|node| node.borrow().id()),
-            Node::File { parent, .. } => parent.upgrade().map(|node| node.borrow().id()),
+    fn set_status(&mut self, new_status: ContainerStatus) {
+        if let NodeKind::Directory { status } = &mut self.kind {
+            *status = new_status;
         }
     }
 
-    fn set_parent(&mut self, new_parent: Weak<RefCell<Node>>) {
-        match self {
-            Node::Root { .. } => {
-                panic!("Trying to set parent of the root.")
+    fn is_directory(&self) -> bool {
+        matches!(self.kind, NodeKind::Directory { .. })
+    }
+
+    fn is_file(&self) -> bool {
+        matches!(self.kind, NodeKind::File { .. })
+    }
+
+    fn is_playable(&self) -> bool {
+        matches!(self.kind, NodeKind::File { playable: true, .. })
+    }
+
+    fn details(&self) -> Option<&FileDetails> {
+        match &self.kind {
+            NodeKind::File { details, .. } => details.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn set_details(&mut self, new_details: Option<FileDetails>) {
+        if let NodeKind::File { details, .. } = &mut self.kind {
+            *details = new_details;
+        }
+    }
+}
+
+/// A slot of the arena of the nodes.
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// The tree of the explorer.
+/// The nodes are stored in an arena, a `NodeId` is the index of its node. The slot of a
+/// removed node is reused by the next node added, with a new generation.
+/// The rows displayed, the linear index, are updated by the methods changing the tree. Each
+/// visible node knows its row, so finding it does not search the linear index.
+struct FileExplorerModel {
+    nodes: Vec<Slot>,
+    /// The slots of the removed nodes.
+    free_slots: Vec<usize>,
+    /// The identifier of each node by its path.
+    paths: HashMap<PathBuf, NodeId>,
+    /// The visible nodes in the display order with their depth, starting with the root.
+    linear_index: Vec<(NodeId, usize)>,
+    /// The root and the expanded directories.
+    expanded: BTreeSet<NodeId>,
+    /// The paths of the expanded directories changed since they were last taken.
+    expanded_changed: bool,
+    selection: Option<NodeId>,
+    sort: Sort,
+}
+
+impl FileExplorerModel {
+    pub fn new(root_path_component: OsString) -> Self {
+        // The root is using the first slot.
+        let root_id = NodeId {
+            index: 0,
+            generation: 0,
+        };
+        let root = Node {
+            kind: NodeKind::Root,
+            parent: None,
+            children: Vec::new(),
+            path_component: root_path_component.clone(),
+            path: PathBuf::from(&root_path_component),
+            row: Some(0),
+        };
+
+        Self {
+            nodes: vec![Slot {
+                generation: 0,
+                node: Some(root),
+            }],
+            free_slots: Vec::new(),
+            paths: HashMap::from([(PathBuf::from(root_path_component), root_id)]),
+            linear_index: vec![(root_id, 0)],
+            expanded: BTreeSet::from([root_id]),
+            expanded_changed: true,
+            selection: None,
+            sort: Sort::default(),
+        }
+    }
+
+    pub fn root_id(&self) -> NodeId {
+        NodeId {
+            index: 0,
+            generation: 0,
+        }
+    }
+
+    pub fn add(&mut self, parent_id: NodeId, entries: Vec<NewEntry>) {
+        if self.get_node(parent_id).is_none() {
+            return;
+        }
+
+        let parent_path = self.path(parent_id);
+
+        for new_entry in entries {
+            let path = parent_path.join(new_entry.path_component());
+
+            // Check for duplicate
+            if self.paths.contains_key(&path) {
+                continue;
             }
-            Node::Directory { parent, .. } => {
-                *parent = new_parent;
+
+            let id = self.push_node(parent_id, path, &new_entry);
+
+            if let Some(parent) = self.get_node_mut(parent_id) {
+                parent.children.push(id);
             }
-            Node::File { parent, .. } => {
-                *parent = new_parent;
+        }
+
+        self.sort_children(parent_id);
+        self.set_status(parent_id, ContainerStatus::Expanded);
+    }
+
+    /// Insert a new entry at its sorted position in a loaded directory.
+    /// Returns `None` if the directory is not loaded or if the path is already in the tree.
+    pub fn insert(&mut self, path: &Path, entry: NewEntry) -> Option<NodeId> {
+        let parent_id = self.node(path.parent()?)?;
+
+        if !self.is_loaded(parent_id) || self.paths.contains_key(path) {
+            return None;
+        }
+
+        let id = self.push_node(parent_id, path.to_path_buf(), &entry);
+
+        self.insert_child(parent_id, id);
+        self.update_rows(parent_id);
+
+        Some(id)
+    }
+
+    /// Move a node to a new path, in its directory or in another loaded directory.
+    /// The node keeps its identifier, its descendants, its expansion and the selection.
+    /// Returns false if the node can't be moved there, it is left unchanged.
+    pub fn rename(&mut self, id: NodeId, new_path: &Path) -> bool {
+        let (Some(old_parent_id), Some(new_parent_id), Some(new_component)) = (
+            self.parent(id),
+            new_path.parent().and_then(|parent| self.node(parent)),
+            new_path.file_name(),
+        ) else {
+            return false;
+        };
+
+        if !self.is_loaded(new_parent_id) || self.paths.contains_key(new_path) {
+            return false;
+        }
+
+        let subtree = self.subtree(id);
+
+        // A directory can't be moved into itself.
+        if subtree.contains(&new_parent_id) {
+            return false;
+        }
+
+        for node_id in &subtree {
+            let path = self.path(*node_id);
+
+            self.paths.remove(&path);
+        }
+
+        if let Some(old_parent) = self.get_node_mut(old_parent_id) {
+            old_parent.children.retain(|child_id| *child_id != id);
+        }
+
+        if let Some(node) = self.get_node_mut(id) {
+            node.parent = Some(new_parent_id);
+            node.path_component = new_component.to_os_string();
+        }
+
+        self.expanded_changed |= subtree.iter().any(|id| self.expanded.contains(id));
+
+        // The subtree starts with the node, a parent is before its children.
+        for node_id in subtree {
+            let path = self
+                .parent(node_id)
+                .map(|parent_id| self.path(parent_id))
+                .unwrap_or_default()
+                .join(self.path_component(node_id).unwrap_or_default());
+
+            if let Some(node) = self.get_node_mut(node_id) {
+                node.path = path.clone();
             }
+
+            self.paths.insert(path, node_id);
         }
+
+        self.insert_child(new_parent_id, id);
+        self.update_rows(old_parent_id);
+        self.update_rows(new_parent_id);
+
+        true
     }
 
-    fn add_child(&mut self, child: Rc<RefCell<Node>>) {
-        match self {
-            Node::Root { children, .. } => {
-                children.push(child);
+    /// Add a node to the arena, it is not added to the children of its parent.
+    fn push_node(&mut self, parent_id: NodeId, path: PathBuf, entry: &NewEntry) -> NodeId {
+        let kind = match entry {
+            NewEntry::Directory { .. } => NodeKind::Directory {
+                status: ContainerStatus::NotLoaded,
+            },
+            NewEntry::File { .. } => NodeKind::File {
+                playable: true,
+                details: None,
+            },
+            NewEntry::UnsupportedFile { .. } => NodeKind::File {
+                playable: false,
+                details: None,
+            },
+        };
+
+        let node = Node {
+            kind,
+            parent: Some(parent_id),
+            children: Vec::new(),
+            path_component: entry.path_component().to_os_string(),
+            path: path.clone(),
+            row: None,
+        };
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.nodes[index];
+
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
             }
-            Node::Directory { children, .. } => {
-                children.push(child);
+            None => {
+                self.nodes.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.nodes.len() - 1,
+                    generation: 0,
+                }
             }
-            Node::File { .. } => {
-                panic!("Trying to add a child to a leaf")
+        };
+
+        self.paths.insert(path, id);
+
+        id
+    }
+
+    /// Add the node to the sorted children of the parent, the rows are not updated.
+    fn insert_child(&mut self, parent_id: NodeId, id: NodeId) {
+        let (Some(parent), Some(node)) = (self.get_node(parent_id), self.get_node(id)) else {
+            return;
+        };
+        let position = parent.children.partition_point(|child_id| {
+            self.get_node(*child_id)
+                .is_some_and(|child| self.sort.compare(child, node).is_lt())
+        });
+
+        if let Some(parent) = self.get_node_mut(parent_id) {
+            parent.children.insert(position, id);
+        }
+    }
+
+    pub fn set_sort(&mut self, sort: Sort) {
+        self.sort = sort;
+
+        let parents: Vec<NodeId> = self.ids().collect();
+
+        for parent_id in parents {
+            self.sort_children(parent_id);
+        }
+
+        self.update_linear_index();
+    }
+
+    /// Sort the children of a node, the rows are not updated.
+    fn sort_children(&mut self, parent_id: NodeId) {
+        let Some(parent) = self.get_node_mut(parent_id) else {
+            return;
+        };
+        let mut children = std::mem::take(&mut parent.children);
+
+        children.sort_by(
+            |left, right| match (self.get_node(*left), self.get_node(*right)) {
+                (Some(left), Some(right)) => self.sort.compare(left, right),
+                _ => Ordering::Equal,
+            },
+        );
+
+        if let Some(parent) = self.get_node_mut(parent_id) {
+            parent.children = children;
+        }
+    }
+
+    /// The files of a directory whose details are not loaded yet, or whose audio properties are
+    /// not read yet if `probe` is true.
+    pub fn files_without_details(&self, parent_id: NodeId, probe: bool) -> Vec<(NodeId, PathBuf)> {
+        self.children(parent_id)
+            .iter()
+            .filter(|id| {
+                self.get_node(**id)
+                    .is_some_and(|node| match node.details() {
+                        None => node.is_file(),
+                        // The audio properties are read later, when they are displayed.
+                        Some(details) => probe && node.is_playable() && !details.probed,
+                    })
+            })
+            .map(|id| (*id, self.path(*id)))
+            .collect()
+    }
+
+    /// Setting the details changes the order of the children.
+    pub fn set_details(&mut self, details: Vec<(NodeId, FileDetails)>) {
+        let mut parents = BTreeSet::new();
+
+        for (id, details) in details {
+            if let Some(node) = self.get_node_mut(id) {
+                node.set_details(Some(details));
+                parents.extend(node.parent);
             }
         }
+
+        for parent_id in parents {
+            self.sort_children(parent_id);
+            self.update_rows(parent_id);
+        }
     }
 
-    fn remove_child(&mut self, id: NodeId) {
-        let remove = |id: NodeId, children: &mut Vec<Rc<RefCell<Node>>>| {
-            if let Some(to_remove) = children
-                .iter()
-                .enumerate()
-                .find(|(_, child)| child.borrow().id() == id)
-                .map(|(index, _)| index)
-            {
-                children.remove(to_remove);
+    /// Forget the details of a file, they are loaded again by `load_details()`.
+    pub fn clear_details(&mut self, id: NodeId) {
+        if let Some(node) = self.get_node_mut(id) {
+            node.set_details(None);
+        }
+    }
+
+    /// Replace the children of a directory by the new entries.
+    /// The children still present keep their identifier and their state, the children
+    /// missing from the new entries are removed.
+    pub fn replace_children(&mut self, parent_id: NodeId, entries: Vec<NewEntry>) {
+        let Some(status) = self.status(parent_id) else {
+            return;
+        };
+
+        for child_id in self.children(parent_id) {
+            let keep = self.get_node(child_id).is_some_and(|child| {
+                entries.iter().any(|entry| {
+                    entry.path_component() == child.path_component()
+                        && match entry {
+                            NewEntry::Directory { .. } => child.is_directory(),
+                            NewEntry::File { .. } => child.is_playable(),
+                            NewEntry::UnsupportedFile { .. } => {
+                                child.is_file() && !child.is_playable()
+                            }
+                        }
+                })
+            });
+
+            if !keep {
+                self.remove(child_id);
             }
-        };
+        }
 
-        match self {
-            Node::Root { children, .. } => {
-                remove(id, children);
-            }
-            Node::Directory { children, .. } => {
-                remove(id, children);
-            }
-            Node::File { .. } => {}
-        };
-    }
+        self.add(parent_id, entries);
 
-    fn children(&self) -> Box<dyn Iterator<Item = NodeId> + '_> {
-        match self {
-            Node::Root { children, .. } => Box::new(children.iter().map(|node| node.borrow().id())),
-            Node::Directory { children, .. } => {
-                Box::new(children.iter().map(|node| node.borrow().id()))
-            }
-            Node::File { .. } => Box::new(std::iter::empty::<NodeId>()),
+        if !matches!(status, ContainerStatus::NotLoaded) {
+            self.set_status(parent_id, status);
         }
     }
 
-    fn path_component(&self) -> OsString {
-        match self {
-            Node::Root { path_component, .. } => path_component,
-            Node::Directory { path_component, .. } => path_component,
-            Node::File { path_component, .. } => path_component,
-        }
-        .clone()
+    /// The root and the directories whose children are loaded.
+    pub fn loaded_directories(&self) -> Vec<NodeId> {
+        self.ids().filter(|id| self.is_loaded(*id)).collect()
     }
 
-    fn status(&self) -> ContainerStatus {
-        match self {
-            Node::Root { .. } => ContainerStatus::Expanded,
-            Node::Directory { status, .. } => *status,
-            Node::File { .. } => ContainerStatus::Empty,
-        }
+    /// The paths of the root and of the expanded directories.
+    pub fn expanded_directories(&self) -> BTreeSet<PathBuf> {
+        self.expanded.iter().map(|id| self.path(*id)).collect()
     }
 
-    fn set_status(&mut self, new_status: ContainerStatus) {
-        if let Node::Directory { status, .. } = self {
-            *status = new_status;
-        }
+    /// True if the paths of the expanded directories changed since the last call.
+    pub fn take_expanded_changed(&mut self) -> bool {
+        std::mem::take(&mut self.expanded_changed)
     }
 
-    fn is_directory(&self) -> bool {
-        matches!(self, Node::Directory { .. })
+    /// True for the root and the directories whose children are loaded.
+    pub fn is_loaded(&self, id: NodeId) -> bool {
+        self.get_node(id).is_some_and(|node| {
+            !node.is_file()
+                && matches!(
+                    node.status(),
+                    ContainerStatus::Expanded | ContainerStatus::Collapsed
+                )
+        })
     }
-}
 
-struct FileExplorerModel {
-    root: Rc<RefCell<Node>>,
-    index: BTreeMap<NodeId, Rc<RefCell<Node>>>,
-    linear_index: Vec<(NodeId, usize)>,
-    next_node_id: usize,
-    selection: Option<NodeId>,
-}
+    /// Remove the node and its descendants, the selection is cleared if it is removed.
+    pub fn remove(&mut self, id: NodeId) {
+        let Some(parent_id) = self.parent(id) else {
+            return;
+        };
+        let removed = self.subtree(id);
 
-impl FileExplorerModel {
-    pub fn new(root_path_component: OsString) -> Self {
-        let mut next_node_id = 0;
-        let root_id = NodeId(next_node_id);
-        let root = Rc::new(RefCell::new(Node::Root {
-            id: root_id,
-            children: Vec::new(),
-            path_component: root_path_component,
-        }));
+        if self
+            .selection
+            .is_some_and(|selection| removed.contains(&selection))
+        {
+            self.selection = None;
+        }
 
-        // The root is using the identifier 0.
-        next_node_id += 1;
+        for removed_id in &removed {
+            let path = self.path(*removed_id);
 
-        Self {
-            index: BTreeMap::from([(root_id, root.clone())]),
-            root,
-            next_node_id,
-            selection: None,
-            linear_index: Vec::new(),
+            self.paths.remove(&path);
         }
-    }
 
-    pub fn root_id(&self) -> NodeId {
-        let root = self.root.borrow();
+        for removed_id in removed {
+            self.expanded_changed |= self.expanded.remove(&removed_id);
 
-        if let Node::Root { id, .. } = &*root {
-            *id
-        } else {
-            panic!("The root node is not a Root")
+            let slot = &mut self.nodes[removed_id.index];
+
+            slot.node = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_slots.push(removed_id.index);
         }
-    }
 
-    pub fn add(&mut self, parent_id: NodeId, entries: Vec<NewEntry>) {
-        for new_entry in entries {
-            let new_path_component = new_entry.path_component();
+        if let Some(parent) = self.get_node_mut(parent_id) {
+            parent.children.retain(|child_id| *child_id != id);
+        }
 
-            // Check for duplicate
-            if let Some(parent_node) = self.get_node(parent_id).cloned() {
-                let child_with_path_component = parent_node.borrow().children().find(|child| {
-                    let child = self.get_node(*child).unwrap();
+        self.update_rows(parent_id);
+    }
 
-                    child.borrow().path_component() == new_path_component
-                });
+    /// The node and all its descendants.
+    fn subtree(&self, id: NodeId) -> Vec<NodeId> {
+        let mut subtree = vec![id];
+        let mut index = 0;
 
-                if child_with_path_component.is_none() {
-                    match new_entry {
-                        NewEntry::File { path_component } => {
-                            self.add_leaf(parent_id, path_component);
-                        }
-                        NewEntry::Directory { path_component } => {
-                            self.add_container(parent_id, path_component);
-                        }
-                    }
-                }
-            }
+        while let Some(node_id) = subtree.get(index).copied() {
+            subtree.extend(self.children(node_id));
+            index += 1;
         }
 
-        self.set_status(parent_id, ContainerStatus::Expanded);
+        subtree
     }
 
-    /// Adding a node changes the tree structure so
-    /// linear index must be updated using update_linear_index().
-    fn add_container(&mut self, parent: NodeId, path_component: OsString) -> NodeId {
-        let new_node_id = NodeId(self.next_node_id);
-        self.next_node_id += 1;
-        let parent_node = self.get_node(parent).unwrap();
-        let mut new_node = Node::Directory {
-            id: new_node_id,
-            parent: Rc::downgrade(parent_node),
-            children: Vec::new(),
-            path_component,
-            status: ContainerStatus::NotLoaded,
-        };
+    /// The visible nodes in the display order with their depth, starting with the root.
+    pub fn linear_visit(&self) -> impl Iterator<Item = &(NodeId, usize)> {
+        self.linear_index.iter()
+    }
 
-        new_node.set_parent(Rc::downgrade(parent_node));
+    /// Rebuild all the rows.
+    pub fn update_linear_index(&mut self) {
+        let mut rows = vec![(self.root_id(), 0)];
 
-        let new_node = Rc::new(RefCell::new(new_node));
+        self.visible_descendants(self.root_id(), 0, &mut rows);
 
-        parent_node.borrow_mut().add_child(new_node.clone());
-        self.index.insert(new_node_id, new_node);
+        let hidden = std::mem::replace(&mut self.linear_index, rows);
 
-        new_node_id
+        self.number_rows(hidden, 0);
     }
 
-    /// Adding a node changes the tree structure so
-    /// linear index must be updated using update_linear_index().
-    fn add_leaf(&mut self, parent: NodeId, path_component: OsString) -> NodeId {
-        let new_node_id = NodeId(self.next_node_id);
-        self.next_node_id += 1;
-        let parent_node = self.get_node(parent).unwrap();
-        let mut new_node = Node::File {
-            id: new_node_id,
-            parent: Rc::downgrade(parent_node),
-            path_component,
+    /// Rebuild the rows of the descendants of a node, if the node is visible.
+    fn update_rows(&mut self, id: NodeId) {
+        let Some(row) = self.get_node(id).and_then(|node| node.row) else {
+            return;
         };
+        let depth = self.linear_index[row].1;
+        let end = self.linear_index[row + 1..]
+            .iter()
+            .position(|(_, row_depth)| *row_depth <= depth)
+            .map_or(self.linear_index.len(), |position| row + 1 + position);
+        let mut rows = Vec::new();
 
-        new_node.set_parent(Rc::downgrade(parent_node));
-
-        let new_node = Rc::new(RefCell::new(new_node));
+        self.visible_descendants(id, depth, &mut rows);
 
-        parent_node.borrow_mut().add_child(new_node.clone());
-        self.index.insert(new_node_id, new_node);
+        let hidden: Vec<_> = self.linear_index.splice(row + 1..end, rows).collect();
 
-        new_node_id
+        self.number_rows(hidden, row + 1);
     }
 
-    pub fn remove(&mut self, id: NodeId) {
-        if let Some(node) = self.get_node(id) {
-            if let Some(parent) = node.borrow().parent() {
-                let parent_node = self.get_node(parent).unwrap();
-
-                parent_node.borrow_mut().remove_child(id);
+    /// Forget the rows of the nodes removed from the linear index, then store their row in the
+    /// nodes from this row, the rows after a change are shifted.
+    fn number_rows(&mut self, hidden: Vec<(NodeId, usize)>, start: usize) {
+        for (id, _) in hidden {
+            if let Some(node) = self.get_node_mut(id) {
+                node.row = None;
             }
-
-            self.index.remove(&id);
-            self.update_linear_index();
         }
-    }
-
-    /// You must call update_linear_index() to ensure the data is up to date.
-    pub fn linear_visit(&self) -> impl Iterator<Item = &(NodeId, usize)> {
-        self.linear_index.iter()
-    }
 
-    pub fn update_linear_index(&mut self) {
-        let initial_depth = 0;
-        let mut stack = VecDeque::from([(self.root_id(), initial_depth)]);
+        for row in start..self.linear_index.len() {
+            let (id, _) = self.linear_index[row];
 
-        self.linear_index.clear();
-        while let Some((current, current_depth)) = stack.pop_front() {
-            self.linear_index.push((current, current_depth));
+            if let Some(node) = self.get_node_mut(id) {
+                node.row = Some(row);
+            }
+        }
+    }
 
-            let current_node = self.get_node(current).unwrap();
+    /// Push the rows of the descendants of the node that are visible, in the display order.
+    fn visible_descendants(&self, id: NodeId, depth: usize, rows: &mut Vec<(NodeId, usize)>) {
+        let Some(node) = self.get_node(id) else {
+            return;
+        };
 
-            if matches!(current_node.borrow().status(), ContainerStatus::Expanded) {
-                for (index, child_id) in current_node.borrow().children().enumerate() {
-                    stack.insert(index, (child_id, current_depth + 1));
-                }
+        if matches!(node.status(), ContainerStatus::Expanded) {
+            for child_id in &node.children {
+                rows.push((*child_id, depth + 1));
+                self.visible_descendants(*child_id, depth + 1, rows);
             }
         }
     }
 
     pub fn parent(&self, id: NodeId) -> Option<NodeId> {
-        let node = self.get_node(id)?;
-
-        node.borrow().parent()
+        self.get_node(id)?.parent
     }
 
     pub fn next(&self, id: NodeId) -> Option<NodeId> {
-        let (index, _) = self
-            .linear_index
-            .iter()
-            .enumerate()
-            .find(|(_index, (node_id, _))| node_id == &id)?;
+        let index = self.get_node(id)?.row?;
 
         self.linear_index.get(index + 1).map(|(id, _)| *id)
     }
 
     pub fn previous(&self, id: NodeId) -> Option<NodeId> {
-        let (index, _) = self
-            .linear_index
-            .iter()
-            .enumerate()
-            .find(|(_index, (node_id, _))| node_id == &id)?;
+        let index = self.get_node(id)?.row?;
 
         if index == 0 {
             return None;
@@ -574,52 +2036,106 @@ impl FileExplorerModel {
         self.linear_index.get(index - 1).map(|(id, _)| *id)
     }
 
+    /// The index of the row displaying the node, the root is not displayed.
+    pub fn row(&self, id: NodeId) -> Option<usize> {
+        self.get_node(id)?.row?.checked_sub(1)
+    }
+
+    /// The node displayed at this row, see `row()`.
+    pub fn row_node(&self, row: usize) -> Option<NodeId> {
+        self.row_entry(row).map(|(id, _)| id)
+    }
+
+    /// The node displayed at this row and its depth in the tree.
+    pub fn row_entry(&self, row: usize) -> Option<(NodeId, usize)> {
+        self.linear_index.get(row + 1).copied()
+    }
+
+    /// The number of rows displayed.
+    pub fn row_count(&self) -> usize {
+        self.linear_index.len().saturating_sub(1)
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.get_node(id)?.children.first().copied()
+    }
+
+    /// The first sibling of the selection whose name starts with the prefix, case-insensitively.
+    /// Without selection the children of the root are searched.
+    pub fn find_sibling(&self, prefix: &str) -> Option<NodeId> {
+        let parent_id = match self.selection {
+            Some(id) => self.parent(id)?,
+            None => self.root_id(),
+        };
+
+        self.children(parent_id).into_iter().find(|id| {
+            self.get_node(*id).is_some_and(|node| {
+                node.path_component()
+                    .to_string_lossy()
+                    .to_lowercase()
+                    .starts_with(prefix)
+            })
+        })
+    }
+
     pub fn path_component(&self, id: NodeId) -> Option<OsString> {
         let node = self.get_node(id)?;
 
-        Some(node.borrow().path_component())
+        Some(node.path_component().to_os_string())
     }
 
-    /// Changing the status changes the structure of the tree so
-    /// linear index must be updated using update_linear_index().
     pub fn set_status(&mut self, id: NodeId, status: ContainerStatus) {
-        let node = self.get_node(id).unwrap();
+        if let Some(node) = self.get_node_mut(id) {
+            node.set_status(status);
+
+            let changed = if matches!(node.status(), ContainerStatus::Expanded) {
+                self.expanded.insert(id)
+            } else {
+                self.expanded.remove(&id)
+            };
 
-        node.borrow_mut().set_status(status);
+            self.expanded_changed |= changed;
+            self.update_rows(id);
+        }
     }
 
     pub fn status(&self, id: NodeId) -> Option<ContainerStatus> {
         let node = self.get_node(id)?;
 
-        Some(node.borrow().status())
+        Some(node.status())
     }
 
-    pub fn expand_collapse(&self, id: NodeId) -> Option<Task<crate::Message>> {
-        if let Some(node) = self.get_node(id) {
-            if let Node::Directory { status, .. } = node.borrow().deref() {
-                match status {
-                    ContainerStatus::Expanded => {
-                        return Some(Task::done(crate::Message::FileExplorer(Message::Collapse(
-                            id,
-                        ))))
-                    }
-                    ContainerStatus::Collapsed => {
-                        return Some(Task::done(crate::Message::FileExplorer(Message::Expand(
-                            id,
-                        ))))
-                    }
-                    ContainerStatus::NotLoaded => {
-                        let path = self.path(id);
+    pub fn expand_collapse(
+        &self,
+        id: NodeId,
+        extensions: Arc<Vec<String>>,
+        options: ListingOptions,
+    ) -> Option<Task<crate::Message>> {
+        let node = self.get_node(id)?;
 
-                        return Some(Task::perform(
-                            load_directory_entries(path),
-                            move |entries| {
-                                crate::Message::FileExplorer(Message::ChildrenLoaded(id, entries))
-                            },
-                        ));
-                    }
-                    _ => (),
+        if let NodeKind::Directory { status } = &node.kind {
+            match status {
+                ContainerStatus::Expanded => {
+                    return Some(Task::done(crate::Message::FileExplorer(Message::Collapse(
+                        id,
+                    ))))
                 }
+                ContainerStatus::Collapsed => {
+                    return Some(Task::done(crate::Message::FileExplorer(Message::Expand(
+                        id,
+                    ))))
+                }
+                ContainerStatus::NotLoaded => {
+                    let path = self.path(id);
+
+                    return Some(Task::perform(
+                        load_directory_entries(path, extensions, options),
+                        move |entries| {
+                            crate::Message::FileExplorer(Message::ChildrenLoaded(id, entries))
+                        },
+                    ));
+                }
+                _ => (),
             }
         }
 
@@ -627,85 +2143,47 @@ impl FileExplorerModel {
     }
 
     pub fn path(&self, id: NodeId) -> PathBuf {
-        let mut current = Some(id);
-        let mut path_components = Vec::new();
-
-        while let Some(current_id) = current.take() {
-            let path_component = self.path_component(current_id).unwrap();
-
-            current = self.parent(current_id);
-            path_components.push(path_component);
-        }
+        self.get_node(id)
+            .map(|node| node.path.clone())
+            .unwrap_or_default()
+    }
 
-        let mut result = PathBuf::new();
+    fn get_node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
 
-        for path_component in path_components.iter().rev() {
-            result = result.join(path_component);
-        }
+    fn get_node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
 
-        result
+    /// The identifiers of the nodes in the tree.
+    fn ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.node.is_some())
+            .map(|(index, slot)| NodeId {
+                index,
+                generation: slot.generation,
+            })
     }
 
-    fn get_node(&self, id: NodeId) -> Option<&Rc<RefCell<Node>>> {
-        self.index.get(&id)
+    fn children(&self, id: NodeId) -> Vec<NodeId> {
+        self.get_node(id)
+            .map(|node| node.children.clone())
+            .unwrap_or_default()
     }
 
     /// Get the `NodeId` from a `Path`.  
     /// Mirror of `FileExplorer::path()`.
-    pub fn node(&self, path_buf: &Path) -> Option<NodeId> {
-        let mut path_buf = path_buf.to_path_buf();
-        let mut parent_node_id: Option<NodeId> = None;
-
-        while !path_buf.as_os_str().is_empty() {
-            match parent_node_id {
-                Some(current_node_id) => {
-                    if let Some(parent_node) = self.get_node(current_node_id) {
-                        if let Some(component_path_to_find) = path_buf
-                            .components()
-                            .next()
-                            .map(|component| component.as_os_str().to_os_string())
-                        {
-                            let mut have_result = false;
-                            for child_id in parent_node.borrow().children() {
-                                if let Some(child) = self.get_node(child_id) {
-                                    if component_path_to_find == child.borrow().path_component() {
-                                        parent_node_id = Some(child_id);
-                                        let temp_path_buf = path_buf
-                                            .strip_prefix(&component_path_to_find)
-                                            .unwrap()
-                                            .to_path_buf();
-
-                                        path_buf = temp_path_buf;
-                                        have_result = true;
-                                        break;
-                                    }
-                                }
-                            }
-
-                            if !have_result {
-                                return None;
-                            }
-                        }
-                    }
-                }
-                None => {
-                    let component_path = self
-                        .index
-                        .get(&self.root_id())
-                        .unwrap()
-                        .borrow()
-                        .path_component();
-
-                    path_buf = path_buf
-                        .strip_prefix(&component_path)
-                        .unwrap()
-                        .to_path_buf();
-                    parent_node_id = Some(self.root_id());
-                }
-            }
-        }
-
-        parent_node_id
+    pub fn node(&self, path: &Path) -> Option<NodeId> {
+        self.paths.get(path).copied()
     }
 
     pub fn set_selection(&mut self, selection: Option<NodeId>) {
@@ -717,22 +2195,38 @@ impl FileExplorerModel {
     }
 
     pub fn is_directory(&self, id: NodeId) -> bool {
-        if let Some(node) = self.get_node(id) {
-            return node.borrow().is_directory();
-        }
+        self.get_node(id).is_some_and(Node::is_directory)
+    }
+
+    pub fn details(&self, id: NodeId) -> Option<FileDetails> {
+        self.get_node(id)?.details().cloned()
+    }
 
-        false
+    pub fn is_playable(&self, id: NodeId) -> bool {
+        self.get_node(id).is_some_and(Node::is_playable)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        collections::BTreeSet,
+        path::{Path, PathBuf},
+    };
+
+    use iced::widget::svg;
     use iced_test::{selector::text, Error};
+    use rstest::rstest;
     use temp_dir_builder::TempDirectoryBuilder;
 
     use crate::{
-        file_explorer::{self, NewEntry, NodeId},
-        tests::simulator,
+        config::Config,
+        file_details::FileDetails,
+        file_explorer::{
+            self, ContainerStatus, FileExplorer, FileExplorerModel, NewEntry, NodeId, Sort,
+            SortKey, SortOrder,
+        },
+        tests::{simulator, snapshot_path},
         Message, SEx,
     };
 
@@ -758,7 +2252,7 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_load_file")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_load_file"))?);
 
         Ok(())
     }
@@ -795,7 +2289,46 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_load_tree")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_load_tree"))?);
+
+        Ok(())
+    }
+
+    /// The name edited fits in the row of the file.
+    #[test]
+    fn test_rename_snapshot() -> Result<(), Error> {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let (mut app, _task) = SEx::new();
+
+        let root_node_id = NodeId::new(0);
+        let file_node_id = NodeId::new(1);
+        let _ = app.update(Message::OpenDirectory(Some(test_dir.path().to_path_buf())));
+        let _ = app.update(Message::FileExplorer(
+            file_explorer::Message::ChildrenLoaded(
+                root_node_id,
+                vec![
+                    NewEntry::File {
+                        path_component: "test_sine_L.wav".into(),
+                    },
+                    NewEntry::File {
+                        path_component: "test_sine_R.wav".into(),
+                    },
+                ],
+            ),
+        ));
+        let _ = app.update(Message::FileExplorer(file_explorer::Message::StartRename(
+            file_node_id,
+        )));
+
+        assert!(app.explorer.is_renaming());
+
+        let mut ui = simulator(&app);
+
+        ui.find(text("test_sine_R.wav")).unwrap();
+
+        let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
+
+        assert!(snapshot.matches_hash(snapshot_path("test_rename"))?);
 
         Ok(())
     }
@@ -840,7 +2373,7 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_collapse")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_collapse"))?);
 
         Ok(())
     }
@@ -868,7 +2401,7 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_select")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_select"))?);
 
         Ok(())
     }
@@ -902,7 +2435,7 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_select_next")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_select_next"))?);
 
         Ok(())
     }
@@ -938,7 +2471,7 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_select_previous")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_select_previous"))?);
 
         Ok(())
     }
@@ -995,8 +2528,749 @@ mod tests {
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_removed")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_removed"))?);
 
         Ok(())
     }
+
+    #[test]
+    fn test_only_visible_rows_are_built() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let (mut app, _task) = SEx::new();
+
+        let root_node_id = NodeId::new(0);
+        let _ = app.update(Message::OpenDirectory(Some(test_dir.path().to_path_buf())));
+        let _ = app.update(Message::FileExplorer(
+            file_explorer::Message::ChildrenLoaded(
+                root_node_id,
+                (0..10_000)
+                    .map(|index| NewEntry::File {
+                        path_component: format!("sample {index:05}.wav").into(),
+                    })
+                    .collect(),
+            ),
+        ));
+
+        let mut ui = simulator(&app);
+
+        ui.find(text("sample 00000.wav")).unwrap();
+        ui.find(text("sample 09999.wav")).unwrap_err();
+    }
+
+    #[test]
+    fn test_node_outside_of_root() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            vec![NewEntry::File {
+                path_component: "a.wav".into(),
+            }],
+        );
+
+        assert!(model.node(Path::new("root/a.wav")).is_some());
+        assert_eq!(model.node(Path::new("elsewhere/a.wav")), None);
+    }
+
+    #[test]
+    fn test_replace_children() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "dir".into(),
+                },
+                NewEntry::File {
+                    path_component: "b.wav".into(),
+                },
+                NewEntry::File {
+                    path_component: "c.wav".into(),
+                },
+            ],
+        );
+        let dir_id = model.node(Path::new("root/dir")).unwrap();
+        let b_id = model.node(Path::new("root/b.wav")).unwrap();
+        let c_id = model.node(Path::new("root/c.wav")).unwrap();
+        model.set_selection(Some(c_id));
+
+        model.replace_children(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "dir".into(),
+                },
+                NewEntry::UnsupportedFile {
+                    path_component: "a.txt".into(),
+                },
+                NewEntry::File {
+                    path_component: "b.wav".into(),
+                },
+            ],
+        );
+        model.update_linear_index();
+
+        let a_id = model.node(Path::new("root/a.txt")).unwrap();
+        let visited: Vec<NodeId> = model.linear_visit().map(|(id, _)| *id).collect();
+
+        assert_eq!(visited, vec![root_id, dir_id, a_id, b_id]);
+        assert_eq!(model.node(Path::new("root/c.wav")), None);
+        assert_eq!(model.selection(), None);
+        assert!(!model.is_playable(a_id));
+        assert!(model.is_playable(b_id));
+    }
+
+    #[test]
+    fn test_rows_are_updated() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+        let rows = |model: &FileExplorerModel| -> Vec<(NodeId, usize)> {
+            model.linear_visit().copied().collect()
+        };
+
+        model.add(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "kicks".into(),
+                },
+                NewEntry::Directory {
+                    path_component: "snares".into(),
+                },
+            ],
+        );
+        let kicks_id = model.node(Path::new("root/kicks")).unwrap();
+        let snares_id = model.node(Path::new("root/snares")).unwrap();
+        model.add(
+            kicks_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "808".into(),
+                },
+                NewEntry::File {
+                    path_component: "kick.wav".into(),
+                },
+            ],
+        );
+        let tr_808_id = model.node(Path::new("root/kicks/808")).unwrap();
+        model.add(
+            tr_808_id,
+            vec![NewEntry::File {
+                path_component: "long.wav".into(),
+            }],
+        );
+        model.add(
+            snares_id,
+            vec![NewEntry::File {
+                path_component: "snare.wav".into(),
+            }],
+        );
+
+        assert_eq!(rows(&model).len(), 7);
+        assert_eq!(
+            rows(&model)[3],
+            (model.node(Path::new("root/kicks/808/long.wav")).unwrap(), 3)
+        );
+
+        model.set_status(kicks_id, ContainerStatus::Collapsed);
+
+        let collapsed = rows(&model);
+
+        model.update_linear_index();
+
+        assert_eq!(collapsed, rows(&model));
+        assert_eq!(collapsed.len(), 4);
+
+        model.set_status(kicks_id, ContainerStatus::Expanded);
+        model.remove(tr_808_id);
+
+        let removed = rows(&model);
+
+        model.update_linear_index();
+
+        assert_eq!(removed, rows(&model));
+        assert_eq!(model.node(Path::new("root/kicks/808")), None);
+        assert_eq!(model.node(Path::new("root/kicks/808/long.wav")), None);
+        assert_eq!(model.path(snares_id), PathBuf::from("root/snares"));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            ["Kick 1.wav", "Kick 10.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into(),
+        );
+
+        let kick_2_id = model.insert(
+            Path::new("root/Kick 2.wav"),
+            NewEntry::File {
+                path_component: "Kick 2.wav".into(),
+            },
+        );
+
+        assert!(kick_2_id.is_some());
+        assert_eq!(
+            model.insert(
+                Path::new("root/Kick 2.wav"),
+                NewEntry::File {
+                    path_component: "Kick 2.wav".into(),
+                },
+            ),
+            None
+        );
+        model.insert(
+            Path::new("root/Snares"),
+            NewEntry::Directory {
+                path_component: "Snares".into(),
+            },
+        );
+        // The directory is not loaded.
+        assert_eq!(
+            model.insert(
+                Path::new("root/Snares/snare.wav"),
+                NewEntry::File {
+                    path_component: "snare.wav".into(),
+                },
+            ),
+            None
+        );
+
+        assert_eq!(
+            child_names(&model),
+            ["Snares", "Kick 1.wav", "Kick 2.wav", "Kick 10.wav"]
+        );
+    }
+
+    #[test]
+    fn test_reuse_slots() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "kicks".into(),
+                },
+                NewEntry::File {
+                    path_component: "snare.wav".into(),
+                },
+            ],
+        );
+
+        let kicks_id = model.node(Path::new("root/kicks")).unwrap();
+        let snare_id = model.node(Path::new("root/snare.wav")).unwrap();
+
+        model.remove(kicks_id);
+
+        let hat_id = model
+            .insert(
+                Path::new("root/hat.wav"),
+                NewEntry::File {
+                    path_component: "hat.wav".into(),
+                },
+            )
+            .unwrap();
+
+        // The slot is reused, the former identifier does not designate the new node.
+        assert_eq!(model.nodes.len(), 3);
+        assert_eq!(hat_id.index, kicks_id.index);
+        assert_ne!(hat_id, kicks_id);
+        assert_eq!(model.path(kicks_id), PathBuf::new());
+        assert_eq!(model.path(hat_id), Path::new("root/hat.wav"));
+        assert_eq!(model.row(hat_id), Some(0));
+        assert_eq!(model.row(snare_id), Some(1));
+        assert_eq!(model.next(hat_id), Some(snare_id));
+        assert_eq!(model.previous(snare_id), Some(hat_id));
+        assert_eq!(model.row(kicks_id), None);
+    }
+
+    #[test]
+    fn test_rename_within_folder() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "Kicks".into(),
+                },
+                NewEntry::File {
+                    path_component: "hat.wav".into(),
+                },
+                NewEntry::File {
+                    path_component: "snare.wav".into(),
+                },
+            ],
+        );
+
+        let kicks_id = model.node(Path::new("root/Kicks")).unwrap();
+
+        model.add(
+            kicks_id,
+            vec![NewEntry::File {
+                path_component: "kick.wav".into(),
+            }],
+        );
+
+        let snare_id = model.node(Path::new("root/snare.wav")).unwrap();
+
+        model.set_selection(Some(snare_id));
+
+        assert!(model.rename(snare_id, Path::new("root/clap.wav")));
+        assert_eq!(model.selection(), Some(snare_id));
+        assert_eq!(model.node(Path::new("root/clap.wav")), Some(snare_id));
+        assert_eq!(model.node(Path::new("root/snare.wav")), None);
+        assert_eq!(
+            child_names(&model),
+            ["Kicks", "kick.wav", "clap.wav", "hat.wav"]
+        );
+
+        model.set_status(kicks_id, ContainerStatus::Collapsed);
+
+        assert!(model.rename(kicks_id, Path::new("root/Bass drums")));
+        assert!(matches!(
+            model.status(kicks_id),
+            Some(ContainerStatus::Collapsed)
+        ));
+        assert_eq!(
+            model.node(Path::new("root/Bass drums/kick.wav")),
+            model.first_child(kicks_id)
+        );
+        assert_eq!(child_names(&model), ["Bass drums", "clap.wav", "hat.wav"]);
+        // The destination already exists.
+        assert!(!model.rename(snare_id, Path::new("root/hat.wav")));
+    }
+
+    #[test]
+    fn test_move_between_folders() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+        let rows = |model: &FileExplorerModel| -> Vec<(NodeId, usize)> {
+            model.linear_visit().copied().collect()
+        };
+
+        model.add(
+            root_id,
+            ["drums", "loops", "one shots"]
+                .map(|name| NewEntry::Directory {
+                    path_component: name.into(),
+                })
+                .into(),
+        );
+
+        let drums_id = model.node(Path::new("root/drums")).unwrap();
+        let loops_id = model.node(Path::new("root/loops")).unwrap();
+
+        model.add(
+            loops_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "breaks".into(),
+                },
+                NewEntry::File {
+                    path_component: "loop.wav".into(),
+                },
+            ],
+        );
+
+        let breaks_id = model.node(Path::new("root/loops/breaks")).unwrap();
+
+        model.add(
+            breaks_id,
+            vec![NewEntry::File {
+                path_component: "amen.wav".into(),
+            }],
+        );
+        model.add(drums_id, Vec::new());
+        model.set_selection(Some(breaks_id));
+
+        assert!(model.rename(breaks_id, Path::new("root/drums/breaks")));
+
+        let moved = rows(&model);
+
+        model.update_linear_index();
+
+        assert_eq!(moved, rows(&model));
+        assert_eq!(model.selection(), Some(breaks_id));
+        assert_eq!(model.parent(breaks_id), Some(drums_id));
+        assert_eq!(model.node(Path::new("root/loops/breaks")), None);
+        assert_eq!(model.node(Path::new("root/loops/breaks/amen.wav")), None);
+        assert_eq!(
+            model.node(Path::new("root/drums/breaks/amen.wav")),
+            model.first_child(breaks_id)
+        );
+        assert_eq!(
+            model.path(model.first_child(breaks_id).unwrap()),
+            Path::new("root/drums/breaks/amen.wav")
+        );
+        assert_eq!(model.row(breaks_id), Some(1));
+        assert_eq!(
+            child_names(&model),
+            [
+                "drums",
+                "breaks",
+                "amen.wav",
+                "loops",
+                "loop.wav",
+                "one shots"
+            ]
+        );
+        // The content of "one shots" is not loaded, the caller removes the node instead.
+        assert!(!model.rename(breaks_id, Path::new("root/one shots/breaks")));
+        // A directory can't be moved into itself.
+        assert!(!model.rename(drums_id, Path::new("root/drums/breaks/drums")));
+        assert_eq!(model.parent(breaks_id), Some(drums_id));
+    }
+
+    #[test]
+    fn test_expanded_directories() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            ["drums", "loops"]
+                .map(|name| NewEntry::Directory {
+                    path_component: name.into(),
+                })
+                .into(),
+        );
+
+        let drums_id = model.node(Path::new("root/drums")).unwrap();
+
+        model.add(
+            drums_id,
+            vec![NewEntry::Directory {
+                path_component: "kicks".into(),
+            }],
+        );
+
+        assert_eq!(
+            model.expanded_directories(),
+            BTreeSet::from([PathBuf::from("root"), PathBuf::from("root/drums")])
+        );
+
+        assert!(model.take_expanded_changed());
+        assert!(!model.take_expanded_changed());
+
+        model.set_status(drums_id, ContainerStatus::Collapsed);
+
+        assert!(model.take_expanded_changed());
+        assert_eq!(
+            model.expanded_directories(),
+            BTreeSet::from([PathBuf::from("root")])
+        );
+
+        let kicks_id = model.node(Path::new("root/drums/kicks")).unwrap();
+
+        model.set_status(drums_id, ContainerStatus::Expanded);
+        model.add(kicks_id, Vec::new());
+        assert!(model.rename(kicks_id, Path::new("root/kicks")));
+        assert!(model.take_expanded_changed());
+        assert_eq!(
+            model.expanded_directories(),
+            BTreeSet::from([
+                PathBuf::from("root"),
+                PathBuf::from("root/drums"),
+                PathBuf::from("root/kicks")
+            ])
+        );
+
+        model.set_status(drums_id, ContainerStatus::Collapsed);
+        let _ = model.take_expanded_changed();
+        model.remove(kicks_id);
+
+        assert!(model.take_expanded_changed());
+        assert_eq!(
+            model.expanded_directories(),
+            BTreeSet::from([PathBuf::from("root")])
+        );
+
+        // Collapsing a collapsed directory changes nothing.
+        model.set_status(drums_id, ContainerStatus::Collapsed);
+
+        assert!(!model.take_expanded_changed());
+    }
+
+    #[test]
+    fn test_select_when_loaded() {
+        let root_path = PathBuf::from("/samples");
+        let mut explorer = FileExplorer::new(
+            svg::Handle::from_memory(Vec::new()),
+            Config::default().extensions,
+        );
+
+        let _ = explorer.set_root_path(&root_path);
+        explorer.select_when_loaded(root_path.join("b.wav"));
+
+        let root_id = explorer.model.as_ref().unwrap().root_id();
+        let _ = explorer.update(file_explorer::Message::ChildrenLoaded(
+            root_id,
+            ["a.wav", "b.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into(),
+        ));
+
+        let model = explorer.model.as_ref().unwrap();
+
+        assert_eq!(
+            model.selection().map(|id| model.path(id)),
+            Some(root_path.join("b.wav"))
+        );
+        assert_eq!(explorer.pending_selection, None);
+    }
+
+    #[test]
+    fn test_reveal() {
+        let root_path = PathBuf::from("/samples");
+        let mut explorer = FileExplorer::new(
+            svg::Handle::from_memory(Vec::new()),
+            Config::default().extensions,
+        );
+
+        let _ = explorer.set_root_path(&root_path);
+        let root_id = explorer.model.as_ref().unwrap().root_id();
+        let _ = explorer.update(file_explorer::Message::ChildrenLoaded(
+            root_id,
+            vec![NewEntry::Directory {
+                path_component: "kicks".into(),
+            }],
+        ));
+
+        // The directory is not loaded yet, the file is selected once it is.
+        let _ = explorer.reveal(root_path.join("kicks").join("kick.wav"));
+
+        assert_eq!(explorer.selection(), None);
+
+        let kicks_id = explorer
+            .model
+            .as_ref()
+            .unwrap()
+            .node(&root_path.join("kicks"))
+            .unwrap();
+        let _ = explorer.update(file_explorer::Message::ChildrenLoaded(
+            kicks_id,
+            vec![NewEntry::File {
+                path_component: "kick.wav".into(),
+            }],
+        ));
+
+        let model = explorer.model.as_ref().unwrap();
+
+        assert_eq!(
+            model.selection().map(|id| model.path(id)),
+            Some(root_path.join("kicks").join("kick.wav"))
+        );
+
+        // A path that is not in the tree is ignored.
+        let _ = explorer.reveal(root_path.join("kicks").join("missing.wav"));
+
+        assert_eq!(explorer.pending_selection, None);
+    }
+
+    #[test]
+    fn test_keyboard_navigation() {
+        let root_path = PathBuf::from("/samples");
+        let mut explorer = FileExplorer::new(
+            svg::Handle::from_memory(Vec::new()),
+            Config::default().extensions,
+        );
+
+        let _ = explorer.set_root_path(&root_path);
+        let root_id = explorer.model.as_ref().unwrap().root_id();
+        let _ = explorer.update(file_explorer::Message::ChildrenLoaded(
+            root_id,
+            vec![
+                NewEntry::Directory {
+                    path_component: "kicks".into(),
+                },
+                NewEntry::File {
+                    path_component: "snare.wav".into(),
+                },
+            ],
+        ));
+        let kicks_id = explorer
+            .model
+            .as_ref()
+            .unwrap()
+            .node(&root_path.join("kicks"))
+            .unwrap();
+        let _ = explorer.update(file_explorer::Message::ChildrenLoaded(
+            kicks_id,
+            ["kick 1.wav", "kick 2.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into(),
+        ));
+        let selected = |explorer: &FileExplorer| {
+            explorer
+                .selected_path()
+                .and_then(|path| path.strip_prefix(&root_path).ok().map(Path::to_path_buf))
+        };
+
+        let _ = explorer.update(file_explorer::Message::SelectLast);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("snare.wav")));
+
+        let _ = explorer.update(file_explorer::Message::SelectFirst);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("kicks")));
+
+        let _ = explorer.update(file_explorer::Message::SelectChild);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("kicks/kick 1.wav")));
+
+        let _ = explorer.update(file_explorer::Message::PageDown);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("snare.wav")));
+
+        let _ = explorer.update(file_explorer::Message::PageUp);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("kicks")));
+
+        // Left collapses the directory, then Right expands it.
+        let _ = explorer.update(file_explorer::Message::SelectParent);
+        assert_eq!(explorer.model.as_ref().unwrap().row_count(), 2);
+
+        let _ = explorer.update(file_explorer::Message::SelectChild);
+        assert_eq!(explorer.model.as_ref().unwrap().row_count(), 4);
+
+        let _ = explorer.update(file_explorer::Message::SelectLast);
+        let _ = explorer.update(file_explorer::Message::TypeAhead("K".into()));
+        let _ = explorer.update(file_explorer::Message::TypeAhead("i".into()));
+        assert_eq!(selected(&explorer), Some(PathBuf::from("kicks")));
+
+        let _ = explorer.update(file_explorer::Message::SelectNext);
+        let _ = explorer.update(file_explorer::Message::SelectParent);
+        assert_eq!(selected(&explorer), Some(PathBuf::from("kicks")));
+    }
+
+    #[test]
+    fn test_find_sibling() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            ["Hat.wav", "kit.wav", "Kick.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into(),
+        );
+
+        let kick_id = model.node(Path::new("root/Kick.wav")).unwrap();
+        let kit_id = model.node(Path::new("root/kit.wav")).unwrap();
+
+        assert_eq!(model.find_sibling("k"), Some(kick_id));
+        assert_eq!(model.find_sibling("kit"), Some(kit_id));
+        assert_eq!(model.find_sibling("snare"), None);
+    }
+
+    fn child_names(model: &FileExplorerModel) -> Vec<String> {
+        let mut model_names = Vec::new();
+
+        for (id, _) in model.linear_visit().skip(1) {
+            model_names.push(model.path_component(*id).unwrap().into_string().unwrap());
+        }
+
+        model_names
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.add(
+            root_id,
+            ["Kick 10.wav", "kick 2.wav", "Kick 1.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into_iter()
+                .chain([NewEntry::Directory {
+                    path_component: "Snares".into(),
+                }])
+                .collect(),
+        );
+        model.update_linear_index();
+
+        assert_eq!(
+            child_names(&model),
+            ["Snares", "Kick 1.wav", "kick 2.wav", "Kick 10.wav"]
+        );
+
+        model.set_sort(Sort {
+            key: SortKey::Name,
+            order: SortOrder::Descending,
+        });
+        model.update_linear_index();
+
+        assert_eq!(
+            child_names(&model),
+            ["Snares", "Kick 10.wav", "kick 2.wav", "Kick 1.wav"]
+        );
+    }
+
+    #[rstest]
+    #[case(SortOrder::Ascending, ["small.wav", "big.wav", "unknown.wav"])]
+    #[case(SortOrder::Descending, ["big.wav", "small.wav", "unknown.wav"])]
+    fn test_sort_by_size(#[case] order: SortOrder, #[case] expected: [&str; 3]) {
+        let mut model = FileExplorerModel::new("root".into());
+        let root_id = model.root_id();
+
+        model.set_sort(Sort {
+            key: SortKey::Size,
+            order,
+        });
+        model.add(
+            root_id,
+            ["big.wav", "small.wav", "unknown.wav"]
+                .map(|name| NewEntry::File {
+                    path_component: name.into(),
+                })
+                .into(),
+        );
+
+        let files = model.files_without_details(root_id, false);
+
+        assert_eq!(files.len(), 3);
+
+        let big_id = model.node(Path::new("root/big.wav")).unwrap();
+        let small_id = model.node(Path::new("root/small.wav")).unwrap();
+
+        model.set_details(vec![
+            (
+                big_id,
+                FileDetails {
+                    size: 1000,
+                    ..Default::default()
+                },
+            ),
+            (
+                small_id,
+                FileDetails {
+                    size: 10,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        model.update_linear_index();
+
+        assert_eq!(child_names(&model), expected);
+        assert_eq!(model.files_without_details(root_id, false).len(), 1);
+        // The sizes are known but not the audio properties.
+        assert_eq!(model.files_without_details(root_id, true).len(), 3);
+    }
 }
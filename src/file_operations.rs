@@ -0,0 +1,826 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use iced::{
+    widget::{button, column, row, text},
+    Element, Task,
+};
+use rfd::AsyncFileDialog;
+
+use crate::ui;
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum OperationError {
+    #[error("'{0}' already exists")]
+    AlreadyExists(PathBuf),
+    #[error("Invalid name '{0}'")]
+    InvalidName(String),
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+    #[error("Trash error: {0}")]
+    Trash(String),
+    #[cfg(not(any(
+        target_os = "windows",
+        all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        )
+    )))]
+    #[error("Restoring files from the trash is not supported on this platform")]
+    RestoreUnsupported,
+}
+
+impl OperationError {
+    fn io(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        move |source| {
+            if source.kind() == io::ErrorKind::AlreadyExists {
+                OperationError::AlreadyExists(path)
+            } else {
+                OperationError::Io {
+                    path,
+                    source: Arc::new(source),
+                }
+            }
+        }
+    }
+}
+
+/// An operation requested by the user.
+#[derive(Debug, Clone)]
+pub enum Request {
+    Rename {
+        path: PathBuf,
+        new_name: String,
+    },
+    Duplicate(PathBuf),
+    /// Move the file or directory into a directory chosen by the user.
+    Move(PathBuf),
+    /// Copy the file into a directory chosen by the user.
+    Export(PathBuf),
+    CreateFolder {
+        parent: PathBuf,
+    },
+    Trash(Vec<PathBuf>),
+}
+
+/// An operation that has been executed, it contains what is needed to undo it.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// A file has been duplicated or exported.
+    Copy {
+        to: PathBuf,
+    },
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CreateFolder(PathBuf),
+    Trash(Vec<PathBuf>),
+}
+
+impl Operation {
+    fn changes(&self) -> Changes {
+        match self {
+            Operation::Rename { from, to } | Operation::Move { from, to } => Changes {
+                renamed: vec![(from.clone(), to.clone())],
+                ..Changes::default()
+            },
+            Operation::Copy { to: path } | Operation::CreateFolder(path) => Changes {
+                added: vec![path.clone()],
+                ..Changes::default()
+            },
+            Operation::Trash(paths) => Changes {
+                removed: paths.clone(),
+                ..Changes::default()
+            },
+        }
+    }
+}
+
+/// The paths removed, added and renamed by an operation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    removed: Vec<PathBuf>,
+    added: Vec<PathBuf>,
+    /// The paths before and after being renamed or moved.
+    renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Changes {
+    fn reversed(self) -> Self {
+        Self {
+            removed: self.added,
+            added: self.removed,
+            renamed: self
+                .renamed
+                .into_iter()
+                .map(|(from, to)| (to, from))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Request(Request),
+    Confirm,
+    Cancel,
+    /// The operation is finished, `None` if the user cancelled it.
+    Done(Option<Result<Operation, OperationError>>),
+    Undo,
+    Undone(Result<Changes, OperationError>),
+}
+
+#[derive(Default)]
+pub struct FileOperations {
+    /// The operation to confirm before executing it.
+    confirmation: Option<Request>,
+    /// The error of the last operation, displayed until the user dismisses it.
+    error: Option<String>,
+    last_operation: Option<Operation>,
+}
+
+impl FileOperations {
+    pub fn update(&mut self, message: Message) -> Task<crate::Message> {
+        match message {
+            Message::Request(request) => {
+                if matches!(request, Request::Trash(_)) {
+                    self.confirmation = Some(request);
+                } else {
+                    return execute_task(request);
+                }
+            }
+            Message::Confirm => {
+                if self.error.take().is_some() {
+                    return Task::none();
+                }
+
+                if let Some(request) = self.confirmation.take() {
+                    return execute_task(request);
+                }
+            }
+            Message::Cancel => {
+                self.confirmation = None;
+                self.error = None;
+            }
+            Message::Done(Some(Ok(operation))) => {
+                let task = refresh_explorer(operation.changes());
+
+                self.last_operation = Some(operation);
+
+                return task;
+            }
+            Message::Done(Some(Err(error))) => {
+                log::error!("File operation failed: {}", error);
+                self.error = Some(format!("The operation failed: {error}"));
+            }
+            Message::Done(None) => {}
+            Message::Undo => {
+                if let Some(operation) = self.last_operation.take() {
+                    return Task::perform(undo(operation), |result| {
+                        crate::Message::FileOperation(Message::Undone(result))
+                    });
+                }
+            }
+            Message::Undone(Ok(changes)) => {
+                return refresh_explorer(changes);
+            }
+            Message::Undone(Err(error)) => {
+                log::error!("Failed to undo the last operation: {}", error);
+                self.error = Some(format!("Failed to undo the last operation: {error}"));
+            }
+        }
+
+        Task::none()
+    }
+
+    /// A modal dialog is displayed, to confirm an operation or to show an error.
+    pub fn has_dialog(&self) -> bool {
+        self.confirmation.is_some() || self.error.is_some()
+    }
+
+    /// Show a dialog over the content when an operation must be confirmed or has failed.
+    pub fn view<'a>(&'a self, content: Element<'a, crate::Message>) -> Element<'a, crate::Message> {
+        let dialog = if let Some(error) = self.error.as_ref() {
+            column![
+                text(error),
+                button("OK").on_press(crate::Message::FileOperation(Message::Confirm)),
+            ]
+        } else if let Some(Request::Trash(paths)) = self.confirmation.as_ref() {
+            let question = match paths.as_slice() {
+                [path] => format!(
+                    "Move '{}' to the trash?",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ),
+                paths => format!("Move {} items to the trash?", paths.len()),
+            };
+
+            column![
+                text(question),
+                row![
+                    button("Cancel").on_press(crate::Message::FileOperation(Message::Cancel)),
+                    button("Move to trash")
+                        .style(button::danger)
+                        .on_press(crate::Message::FileOperation(Message::Confirm)),
+                ]
+                .spacing(8),
+            ]
+        } else {
+            return content;
+        };
+
+        ui::dialog(
+            content,
+            dialog,
+            crate::Message::FileOperation(Message::Cancel),
+        )
+    }
+}
+
+fn execute_task(request: Request) -> Task<crate::Message> {
+    Task::perform(execute(request), |result| {
+        crate::Message::FileOperation(Message::Done(result))
+    })
+}
+
+/// The file watcher updates the explorer too, but only when the directory is watched.
+/// The first added path is selected.
+fn refresh_explorer(changes: Changes) -> Task<crate::Message> {
+    use crate::file_explorer::Message::{Added, Removed, Renamed, SelectWhenLoaded};
+
+    let removed = changes.removed.into_iter().map(Removed);
+    let select = changes
+        .added
+        .first()
+        .or(changes.renamed.first().map(|(_, to)| to))
+        .cloned()
+        .map(SelectWhenLoaded);
+    let added = changes.added.into_iter().map(Added);
+    let renamed = changes
+        .renamed
+        .into_iter()
+        .map(|(from, to)| Renamed { from, to });
+
+    Task::batch(
+        removed
+            .chain(select)
+            .chain(added)
+            .chain(renamed)
+            .map(|message| Task::done(crate::Message::FileExplorer(message))),
+    )
+}
+
+/// Returns `None` if the user cancelled the operation.
+async fn execute(request: Request) -> Option<Result<Operation, OperationError>> {
+    let result = match request {
+        Request::Rename { path, new_name } => rename(path, new_name).await,
+        Request::Duplicate(path) => duplicate(path).await,
+        Request::Move(path) => {
+            let destination = AsyncFileDialog::new()
+                .set_title("Move to")
+                .pick_folder()
+                .await?;
+
+            move_into(path, destination.path()).await
+        }
+        Request::Export(path) => {
+            let destination = AsyncFileDialog::new()
+                .set_title("Export to")
+                .pick_folder()
+                .await?;
+
+            export(path, destination.path()).await
+        }
+        Request::CreateFolder { parent } => create_folder(parent).await,
+        Request::Trash(paths) => trash(paths).await,
+    };
+
+    Some(result)
+}
+
+async fn rename(path: PathBuf, new_name: String) -> Result<Operation, OperationError> {
+    if new_name.is_empty()
+        || new_name == "."
+        || new_name == ".."
+        || new_name.contains(std::path::is_separator)
+    {
+        return Err(OperationError::InvalidName(new_name));
+    }
+
+    let to = path.with_file_name(&new_name);
+
+    move_path(&path, &to).await?;
+
+    Ok(Operation::Rename { from: path, to })
+}
+
+async fn duplicate(path: PathBuf) -> Result<Operation, OperationError> {
+    let to = copy_path(&path);
+
+    copy_new(&path, &to).await?;
+
+    Ok(Operation::Copy { to })
+}
+
+async fn export(path: PathBuf, directory: &Path) -> Result<Operation, OperationError> {
+    let Some(file_name) = path.file_name() else {
+        return Err(OperationError::InvalidName(path.display().to_string()));
+    };
+    let to = directory.join(file_name);
+
+    copy_new(&path, &to).await?;
+
+    Ok(Operation::Copy { to })
+}
+
+async fn move_into(path: PathBuf, directory: &Path) -> Result<Operation, OperationError> {
+    let Some(file_name) = path.file_name() else {
+        return Err(OperationError::InvalidName(path.display().to_string()));
+    };
+    let to = directory.join(file_name);
+
+    move_path(&path, &to).await?;
+
+    Ok(Operation::Move { from: path, to })
+}
+
+async fn create_folder(parent: PathBuf) -> Result<Operation, OperationError> {
+    let path = available_path(&parent, "New folder", None, |path| path.exists());
+
+    tokio::fs::create_dir(&path)
+        .await
+        .map_err(OperationError::io(&path))?;
+
+    Ok(Operation::CreateFolder(path))
+}
+
+async fn trash(paths: Vec<PathBuf>) -> Result<Operation, OperationError> {
+    let to_delete = paths.clone();
+
+    tokio::task::spawn_blocking(move || trash::delete_all(to_delete))
+        .await
+        .map_err(|error| OperationError::Trash(error.to_string()))?
+        .map_err(|error| OperationError::Trash(error.to_string()))?;
+
+    Ok(Operation::Trash(paths))
+}
+
+/// Undo the operation, returns the paths changed by undoing it.
+async fn undo(operation: Operation) -> Result<Changes, OperationError> {
+    let changes = operation.changes().reversed();
+
+    match operation {
+        Operation::Rename { from, to } | Operation::Move { from, to } => {
+            move_path(&to, &from).await?;
+        }
+        Operation::Copy { to } => {
+            tokio::fs::remove_file(&to)
+                .await
+                .map_err(OperationError::io(&to))?;
+        }
+        Operation::CreateFolder(path) => {
+            // Fails if something has been added to the folder since.
+            tokio::fs::remove_dir(&path)
+                .await
+                .map_err(OperationError::io(&path))?;
+        }
+        Operation::Trash(paths) => {
+            tokio::task::spawn_blocking(move || restore(paths))
+                .await
+                .map_err(|error| OperationError::Trash(error.to_string()))??;
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore(paths: Vec<PathBuf>) -> Result<(), OperationError> {
+    use trash::os_limited;
+
+    let items = os_limited::list().map_err(|error| OperationError::Trash(error.to_string()))?;
+    let mut to_restore = Vec::new();
+
+    // The same path may have been trashed several times, restore the most recent one.
+    for path in paths {
+        if let Some(item) = items
+            .iter()
+            .filter(|item| item.original_path() == path)
+            .max_by_key(|item| item.time_deleted)
+        {
+            to_restore.push(item.clone());
+        }
+    }
+
+    os_limited::restore_all(to_restore).map_err(|error| OperationError::Trash(error.to_string()))
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore(_paths: Vec<PathBuf>) -> Result<(), OperationError> {
+    Err(OperationError::RestoreUnsupported)
+}
+
+/// Open the system file manager in the directory containing the path.
+/// The path is selected when the file manager supports it.
+pub fn show_in_file_manager(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut argument = OsString::from("/select,");
+
+        argument.push(path);
+
+        let mut command = Command::new("explorer");
+
+        command.arg(argument);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+
+    let mut child = command.spawn()?;
+
+    // Reap the process when it exits.
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Rename, or copy and remove if the destination is on another file system.
+/// Never replaces an existing file, the destination is checked and created atomically.
+async fn move_path(from: &Path, to: &Path) -> Result<(), OperationError> {
+    let (from, to) = (from.to_path_buf(), to.to_path_buf());
+    let task_to = to.clone();
+
+    tokio::task::spawn_blocking(move || match rename_no_replace(&from, &to) {
+        Ok(()) => Ok(()),
+        // Moving a directory across file systems is not supported.
+        Err(error) if error.kind() == io::ErrorKind::CrossesDevices && !from.is_dir() => {
+            copy_new_blocking(&from, &to)?;
+            fs::remove_file(&from).map_err(OperationError::io(&from))
+        }
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            Err(OperationError::AlreadyExists(to))
+        }
+        Err(error) => Err(OperationError::io(&from)(error)),
+    })
+    .await
+    .map_err(|error| OperationError::io(task_to)(io::Error::other(error)))?
+}
+
+/// Copy a file to a new file, never replaces an existing file.
+async fn copy_new(from: &Path, to: &Path) -> Result<(), OperationError> {
+    let (from, to) = (from.to_path_buf(), to.to_path_buf());
+    let task_to = to.clone();
+
+    tokio::task::spawn_blocking(move || copy_new_blocking(&from, &to))
+        .await
+        .map_err(|error| OperationError::io(task_to)(io::Error::other(error)))?
+}
+
+fn copy_new_blocking(from: &Path, to: &Path) -> Result<(), OperationError> {
+    let mut source = File::open(from).map_err(OperationError::io(from))?;
+    let mut destination = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(to)
+        .map_err(OperationError::io(to))?;
+    let copied = io::copy(&mut source, &mut destination).and_then(|_| {
+        destination.set_permissions(source.metadata()?.permissions())?;
+        destination.sync_all()
+    });
+
+    // An incomplete copy is removed.
+    copied.map_err(|error| {
+        let _ = fs::remove_file(to);
+
+        OperationError::io(to)(error)
+    })
+}
+
+/// Rename without replacing an existing path, the check is atomic with the rename.
+#[cfg(target_os = "linux")]
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let from_c = CString::new(from.as_os_str().as_bytes())?;
+    let to_c = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: the paths are valid C strings for the duration of the call.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            from_c.as_ptr(),
+            libc::AT_FDCWD,
+            to_c.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+
+    if result == 0 {
+        return Ok(());
+    }
+
+    let error = io::Error::last_os_error();
+
+    match error.raw_os_error() {
+        // The kernel or the file system does not support the flag.
+        Some(libc::EINVAL | libc::ENOSYS) => link_and_remove(from, to),
+        _ => Err(error),
+    }
+}
+
+/// Rename without replacing an existing path, the check is atomic with the rename.
+#[cfg(target_os = "macos")]
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let from_c = CString::new(from.as_os_str().as_bytes())?;
+    let to_c = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: the paths are valid C strings for the duration of the call.
+    let result = unsafe { libc::renamex_np(from_c.as_ptr(), to_c.as_ptr(), libc::RENAME_EXCL) };
+
+    if result == 0 {
+        return Ok(());
+    }
+
+    let error = io::Error::last_os_error();
+
+    match error.raw_os_error() {
+        // The file system does not support the flag.
+        Some(libc::ENOTSUP) => link_and_remove(from, to),
+        _ => Err(error),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn rename_no_replace(from: &Path, to: &Path) -> io::Result<()> {
+    link_and_remove(from, to)
+}
+
+/// Link the file to its new path then remove the old path, linking fails if the destination
+/// exists. The directories and the file systems without hard links fall back to a rename after
+/// checking the destination, which is not atomic.
+fn link_and_remove(from: &Path, to: &Path) -> io::Result<()> {
+    if !fs::symlink_metadata(from)?.is_dir() {
+        match fs::hard_link(from, to) {
+            Ok(()) => return fs::remove_file(from),
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::AlreadyExists | io::ErrorKind::CrossesDevices
+                ) =>
+            {
+                return Err(error)
+            }
+            Err(_) => {}
+        }
+    }
+
+    if fs::exists(to)? {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+
+    fs::rename(from, to)
+}
+
+/// The path of the copy of a file, "Kick.wav" gives "Kick copy.wav", then "Kick copy 2.wav".
+fn copy_path(path: &Path) -> PathBuf {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+
+    available_path(
+        directory,
+        &format!("{stem} copy"),
+        extension.as_deref(),
+        |path| path.exists(),
+    )
+}
+
+/// The first path in the directory named "name", "name 2", "name 3"... that does not exist.
+fn available_path(
+    directory: &Path,
+    name: &str,
+    extension: Option<&str>,
+    exists: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let make_path = |name: String| match extension {
+        Some(extension) => directory.join(format!("{name}.{extension}")),
+        None => directory.join(name),
+    };
+    let mut path = make_path(name.to_string());
+    let mut index = 2;
+
+    while exists(&path) {
+        path = make_path(format!("{name} {index}"));
+        index += 1;
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use temp_dir_builder::TempDirectoryBuilder;
+
+    use super::{
+        available_path, copy_new_blocking, execute, link_and_remove, undo, FileOperations, Message,
+        Operation, OperationError, Request,
+    };
+
+    #[test]
+    fn test_available_path() {
+        let existing = [PathBuf::from("/a/Kick copy.wav")];
+        let exists = |path: &Path| existing.iter().any(|existing| existing == path);
+
+        assert_eq!(
+            available_path(Path::new("/a"), "Snare copy", Some("wav"), exists),
+            PathBuf::from("/a/Snare copy.wav")
+        );
+        assert_eq!(
+            available_path(Path::new("/a"), "Kick copy", Some("wav"), exists),
+            PathBuf::from("/a/Kick copy 2.wav")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_undo() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("kick.wav")
+            .add_empty_file("snare.wav")
+            .build()
+            .unwrap();
+        let kick = test_dir.path().join("kick.wav");
+        let renamed = test_dir.path().join("kick 2.wav");
+
+        let operation = execute(Request::Rename {
+            path: kick.clone(),
+            new_name: "kick 2.wav".into(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(!kick.exists());
+        assert!(renamed.exists());
+
+        undo(operation).await.unwrap();
+
+        assert!(kick.exists());
+        assert!(!renamed.exists());
+
+        let result = execute(Request::Rename {
+            path: kick.clone(),
+            new_name: "snare.wav".into(),
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(result, Err(OperationError::AlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_error_dialog() {
+        let mut operations = FileOperations::default();
+
+        let _ = operations.update(Message::Done(Some(Err(OperationError::AlreadyExists(
+            "kick.wav".into(),
+        )))));
+
+        assert!(operations.has_dialog());
+
+        let _ = operations.update(Message::Confirm);
+
+        assert!(!operations.has_dialog());
+    }
+
+    #[test]
+    fn test_copy_new() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("snare.wav")
+            .build()
+            .unwrap();
+        let kick = test_dir.path().join("kick.wav");
+        let copy = test_dir.path().join("kick copy.wav");
+        let snare = test_dir.path().join("snare.wav");
+
+        std::fs::write(&kick, "kick").unwrap();
+        copy_new_blocking(&kick, &copy).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "kick");
+        assert!(matches!(
+            copy_new_blocking(&kick, &snare),
+            Err(OperationError::AlreadyExists(path)) if path == snare
+        ));
+        // The existing file is not truncated.
+        assert_eq!(std::fs::read_to_string(&snare).unwrap(), "");
+    }
+
+    #[test]
+    fn test_link_and_remove() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("kick.wav")
+            .add_empty_file("snare.wav")
+            .add_directory("Kicks")
+            .build()
+            .unwrap();
+        let kick = test_dir.path().join("kick.wav");
+        let moved = test_dir.path().join("Kicks").join("kick.wav");
+
+        assert_eq!(
+            link_and_remove(&kick, &test_dir.path().join("snare.wav"))
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+
+        link_and_remove(&kick, &moved).unwrap();
+
+        assert!(!kick.exists());
+        assert!(moved.exists());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_and_undo() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("kick.wav")
+            .build()
+            .unwrap();
+
+        let operation = execute(Request::Duplicate(test_dir.path().join("kick.wav")))
+            .await
+            .unwrap()
+            .unwrap();
+        let copy = test_dir.path().join("kick copy.wav");
+
+        assert!(matches!(&operation, Operation::Copy { to } if to == &copy));
+        assert!(copy.exists());
+
+        undo(operation).await.unwrap();
+
+        assert!(!copy.exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_folder_and_undo() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+
+        let operation = execute(Request::CreateFolder {
+            parent: test_dir.path().to_path_buf(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let folder = test_dir.path().join("New folder");
+
+        assert!(folder.is_dir());
+
+        undo(operation).await.unwrap();
+
+        assert!(!folder.exists());
+    }
+}
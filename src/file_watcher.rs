@@ -1,123 +1,506 @@
 use std::{
-    path::{Path, PathBuf},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 use iced::{
-    futures::{channel::mpsc, Stream, StreamExt},
-    Subscription, Task,
+    alignment::Vertical,
+    futures::{channel::mpsc, SinkExt, Stream, StreamExt},
+    widget::{button, column, container, row, text},
+    Element, Length, Subscription, Task,
 };
 use log::{debug, trace};
-use notify::Watcher;
+use notify::{
+    event::{AccessKind, AccessMode, MetadataKind, ModifyKind, RenameMode},
+    RecursiveMode,
+};
+use tokio::time::Instant;
+
+use crate::{file_explorer, search};
+
+/// The events are sent once no event has been received during this delay...
+const DEBOUNCE: Duration = Duration::from_millis(100);
+/// ...or at the latest after this delay, during a continuous stream of events.
+const MAX_DELAY: Duration = Duration::from_secs(1);
+/// Above this number of changed paths, the explorer reloads its directories instead.
+const MAX_CHANGES: usize = 1000;
+/// The interval between two scans when the files are watched by polling.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-use crate::{file_explorer, file_watcher};
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum WatcherError {
+    #[error("Failed to create the file watcher: {0}")]
+    Create(Arc<notify::Error>),
+    #[error("Failed to watch '{path}': {source}")]
+    Watch {
+        path: PathBuf,
+        source: Arc<notify::Error>,
+    },
+    #[error("File watcher error: {0}")]
+    Notify(Arc<notify::Error>),
+}
 
 pub enum Command {
-    Initialize(Arc<tokio::runtime::Runtime>),
-    ResetRootPath(PathBuf),
+    /// Watch these directories, and stop watching the others.
+    Watch(BTreeMap<PathBuf, RecursiveMode>),
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    Initialize(mpsc::Sender<Command>),
-    Notify(notify::Event),
+    Initialize(mpsc::UnboundedSender<Command>),
+    /// The changes received during the debounce delay.
+    Changed(Changes),
+    /// The native watcher failed, the directories are watched by polling.
+    Polling,
+    Failed(WatcherError),
+    DismissError,
+}
+
+/// The changes of the files under the root, coalesced.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    /// The paths before and after being renamed or moved.
+    renamed: Vec<(PathBuf, PathBuf)>,
+    added: Vec<PathBuf>,
+    removed: Vec<PathBuf>,
+    /// The files whose content changed.
+    modified: Vec<PathBuf>,
+    /// Too many changes, or changes missed by the watcher, the directories must be reloaded.
+    rescan: bool,
 }
 
+/// Watch the directories displayed by the explorer.
+/// The directories are not watched recursively, a recursive watch needs one inotify watch per
+/// subdirectory on Linux and fails on large libraries. Only the root is watched recursively
+/// during a search, whose results can be anywhere under it, and the directories are polled if
+/// there are too many.
 pub struct FileWatcher {
-    command_sender: Option<mpsc::Sender<Command>>,
-    runtime: Arc<tokio::runtime::Runtime>,
+    command_sender: Option<mpsc::UnboundedSender<Command>>,
+    /// The directories to watch, sent again once the subscription is initialized.
+    directories: BTreeMap<PathBuf, RecursiveMode>,
+    polling: bool,
+    error: Option<WatcherError>,
 }
 
 impl FileWatcher {
     pub fn new() -> Self {
         Self {
             command_sender: None,
-            runtime: Arc::new(tokio::runtime::Runtime::new().unwrap()),
+            directories: BTreeMap::new(),
+            polling: false,
+            error: None,
         }
     }
 
-    pub fn watch(&mut self, path: impl AsRef<Path>) {
-        if let Some(sender) = self.command_sender.as_mut() {
-            sender
-                .try_send(Command::ResetRootPath(path.as_ref().to_path_buf()))
-                .unwrap()
+    /// Watch these directories, and the recursive one with its subdirectories, and stop
+    /// watching the others.
+    pub fn watch(&mut self, directories: BTreeSet<PathBuf>, recursive: Option<PathBuf>) {
+        // The directories under the recursive one are already watched with it.
+        let mut directories: BTreeMap<PathBuf, RecursiveMode> = directories
+            .into_iter()
+            .filter(|directory| {
+                !recursive
+                    .as_ref()
+                    .is_some_and(|recursive| directory.starts_with(recursive))
+            })
+            .map(|directory| (directory, RecursiveMode::NonRecursive))
+            .collect();
+
+        directories.extend(recursive.map(|directory| (directory, RecursiveMode::Recursive)));
+
+        if self.directories != directories {
+            self.directories = directories;
+            self.send_directories();
+        }
+    }
+
+    fn send_directories(&self) {
+        if let Some(sender) = self.command_sender.as_ref() {
+            if let Err(error) = sender.unbounded_send(Command::Watch(self.directories.clone())) {
+                log::error!(
+                    "Failed to send the directories to the file watcher: {}",
+                    error
+                );
+            }
         }
     }
 
     pub fn update(&mut self, message: Message) -> Task<crate::Message> {
         match message {
-            Message::Initialize(mut sender) => {
-                sender
-                    .try_send(Command::Initialize(self.runtime.clone()))
-                    .unwrap();
-
+            Message::Initialize(sender) => {
                 self.command_sender = Some(sender);
+                self.send_directories();
             }
-            Message::Notify(event) => {
-                trace!("{:?}", event);
-                match event.kind {
-                    notify::EventKind::Create(_) => {
-                        return Task::batch(event.paths.iter().map(|path| {
-                            Task::done(crate::Message::FileExplorer(file_explorer::Message::Added(
-                                path.clone(),
-                            )))
-                        }))
-                    }
-                    notify::EventKind::Remove(_) => {
-                        return Task::batch(event.paths.iter().map(|path| {
-                            Task::done(crate::Message::FileExplorer(
-                                file_explorer::Message::Removed(path.clone()),
-                            ))
-                        }))
-                    }
-                    notify::EventKind::Modify(notify::event::ModifyKind::Name(
-                        notify::event::RenameMode::Any,
-                    )) => {
-                        return Task::batch(event.paths.iter().map(|path| match path.exists() {
-                            true => Task::done(crate::Message::FileExplorer(
-                                file_explorer::Message::Added(path.clone()),
-                            )),
-                            false => Task::done(crate::Message::FileExplorer(
-                                file_explorer::Message::Removed(path.clone()),
-                            )),
-                        }))
-                    }
-                    notify::EventKind::Modify(notify::event::ModifyKind::Name(
-                        notify::event::RenameMode::From,
-                    )) => {
-                        return Task::batch(event.paths.iter().map(|path| {
-                            Task::done(crate::Message::FileExplorer(
-                                file_explorer::Message::Removed(path.clone()),
-                            ))
-                        }))
-                    }
-                    notify::EventKind::Modify(notify::event::ModifyKind::Name(
-                        notify::event::RenameMode::To,
-                    )) => {
-                        return Task::batch(event.paths.iter().map(|path| {
-                            Task::done(crate::Message::FileExplorer(file_explorer::Message::Added(
-                                path.clone(),
-                            )))
-                        }))
-                    }
-                    _ => (),
+            Message::Changed(changes) => {
+                trace!("{:?}", changes);
+
+                // A file can be rewritten in place, or replaced by a new file.
+                let written: Vec<PathBuf> = changes
+                    .modified
+                    .iter()
+                    .chain(&changes.added)
+                    .chain(changes.renamed.iter().map(|(_, to)| to))
+                    .cloned()
+                    .collect();
+                let files_written = if written.is_empty() {
+                    Task::none()
+                } else {
+                    Task::done(crate::Message::FilesWritten(written))
+                };
+
+                if changes.rescan {
+                    return Task::batch([
+                        Task::done(crate::Message::FileExplorer(file_explorer::Message::Rescan)),
+                        Task::done(crate::Message::Search(search::Message::Rescan)),
+                        files_written,
+                    ]);
                 }
+
+                let renamed = changes.renamed.into_iter().flat_map(|(from, to)| {
+                    [
+                        crate::Message::FileExplorer(file_explorer::Message::Renamed {
+                            from: from.clone(),
+                            to: to.clone(),
+                        }),
+                        crate::Message::Search(search::Message::Renamed { from, to }),
+                    ]
+                });
+                let removed = changes.removed.into_iter().flat_map(|path| {
+                    [
+                        crate::Message::FileExplorer(file_explorer::Message::Removed(path.clone())),
+                        crate::Message::Search(search::Message::Removed(path)),
+                    ]
+                });
+                let added = changes.added.into_iter().flat_map(|path| {
+                    [
+                        crate::Message::FileExplorer(file_explorer::Message::Added(path.clone())),
+                        crate::Message::Search(search::Message::Added(path)),
+                    ]
+                });
+                let modified = changes.modified.into_iter().map(|path| {
+                    crate::Message::FileExplorer(file_explorer::Message::Modified(path))
+                });
+
+                return Task::batch(
+                    renamed
+                        .chain(removed)
+                        .chain(added)
+                        .chain(modified)
+                        .map(Task::done)
+                        .chain([files_written]),
+                );
+            }
+            Message::Polling => {
+                self.polling = true;
+            }
+            Message::Failed(error) => {
+                log::error!("{}", error);
+                self.error = Some(error);
+            }
+            Message::DismissError => {
+                self.error = None;
             }
         }
+
         Task::none()
     }
 
+    /// A warning shown above the explorer after an error of the watcher.
+    pub fn view(&self) -> Option<Element<crate::Message>> {
+        let error = self.error.as_ref()?;
+        let consequence = if self.polling {
+            format!(
+                "Checking for changes every {} seconds.",
+                POLL_INTERVAL.as_secs()
+            )
+        } else {
+            "The explorer is not updated when files change.".to_string()
+        };
+
+        Some(
+            container(
+                row![
+                    column![
+                        text(error.to_string()).style(text::danger),
+                        text(consequence)
+                    ]
+                    .width(Length::Fill),
+                    button("Dismiss").on_press(crate::Message::FileWatcher(Message::DismissError)),
+                ]
+                .spacing(8)
+                .align_y(Vertical::Center),
+            )
+            .padding(8)
+            .width(Length::Fill)
+            .into(),
+        )
+    }
+
     pub fn subscription(&self) -> Subscription<crate::Message> {
         Subscription::run(run_watcher)
     }
 }
 
-fn run_watcher() -> impl Stream<Item = crate::Message> {
-    use iced::futures::SinkExt;
+/// Accumulate the events of the watcher until they are sent to the UI.
+#[derive(Default)]
+struct Coalescer {
+    /// The paths created or removed, in the order of the events.
+    touched: Vec<PathBuf>,
+    touched_set: HashSet<PathBuf>,
+    renamed: Vec<(PathBuf, PathBuf)>,
+    modified: Vec<PathBuf>,
+    modified_set: HashSet<PathBuf>,
+    /// The tracker and the source of a rename waiting for its destination.
+    rename_from: Option<(usize, PathBuf)>,
+    rescan: bool,
+}
+
+impl Coalescer {
+    fn push(&mut self, mut event: notify::Event) {
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+
+        match event.kind {
+            notify::EventKind::Create(_)
+            | notify::EventKind::Remove(_)
+            | notify::EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => {
+                event.paths.into_iter().for_each(|path| self.touch(path));
+            }
+            notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                if let Some((_, path)) = self.rename_from.take() {
+                    self.touch(path);
+                }
+
+                // Wait for the destination to keep the state of the renamed node.
+                match event.tracker() {
+                    Some(tracker) if event.paths.len() == 1 => {
+                        self.rename_from = event.paths.pop().map(|path| (tracker, path));
+                    }
+                    _ => event.paths.into_iter().for_each(|path| self.touch(path)),
+                }
+            }
+            notify::EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                let tracker = event.tracker();
+                let single = event.paths.len() == 1;
+
+                match self
+                    .rename_from
+                    .take_if(|(from_tracker, _)| single && Some(*from_tracker) == tracker)
+                {
+                    Some((_, from)) => self.renamed.extend(event.paths.pop().map(|to| (from, to))),
+                    None => event.paths.into_iter().for_each(|path| self.touch(path)),
+                }
+            }
+            // Some platforms send this event after `From` and `To`.
+            notify::EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    let rename = (from.clone(), to.clone());
+
+                    self.rename_from.take_if(|(_, path)| path == from);
+
+                    if !self.renamed.contains(&rename) {
+                        self.renamed.push(rename);
+                    }
+                }
+            }
+            notify::EventKind::Modify(
+                ModifyKind::Any
+                | ModifyKind::Data(_)
+                | ModifyKind::Metadata(MetadataKind::WriteTime),
+            )
+            | notify::EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                for path in event.paths {
+                    if self.modified_set.insert(path.clone()) {
+                        self.modified.push(path);
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        if self.touched.len() + self.renamed.len() > MAX_CHANGES {
+            self.rescan = true;
+        }
+    }
+
+    fn touch(&mut self, path: PathBuf) {
+        // The paths are not needed anymore once the directories must be reloaded.
+        if !self.rescan && self.touched_set.insert(path.clone()) {
+            self.touched.push(path);
+        }
+    }
+
+    /// The changes since the previous call, a rename without destination is a removal.
+    fn finish(&mut self) -> Changes {
+        let mut coalescer = std::mem::take(self);
+
+        if let Some((_, path)) = coalescer.rename_from.take() {
+            coalescer.touch(path);
+        }
+
+        // The files created in the meantime are not modified, they are added.
+        let modified: Vec<PathBuf> = coalescer
+            .modified
+            .into_iter()
+            .filter(|path| !coalescer.touched_set.contains(path) && path.exists())
+            .collect();
+
+        if coalescer.rescan {
+            return Changes {
+                modified,
+                rescan: true,
+                ..Changes::default()
+            };
+        }
+
+        // Only the last state of a path matters, and the content of a directory created or
+        // removed in the meantime is listed, or removed, with the directory.
+        let (added, removed) = coalescer
+            .touched
+            .into_iter()
+            .filter(|path| {
+                !path
+                    .ancestors()
+                    .skip(1)
+                    .any(|ancestor| coalescer.touched_set.contains(ancestor))
+            })
+            .partition(|path| path.exists());
+
+        Changes {
+            renamed: coalescer.renamed,
+            added,
+            removed,
+            modified,
+            rescan: false,
+        }
+    }
+}
+
+/// The watcher of the subscription and the directories it watches.
+struct Watches {
+    watcher: Option<Box<dyn notify::Watcher + Send>>,
+    directories: BTreeMap<PathBuf, RecursiveMode>,
+    /// Once the native watcher failed, the directories are watched by polling.
+    polling: bool,
+    events: mpsc::UnboundedSender<notify::Result<notify::Event>>,
+}
+
+impl Watches {
+    fn new(events: mpsc::UnboundedSender<notify::Result<notify::Event>>) -> Self {
+        Self {
+            watcher: None,
+            directories: BTreeMap::new(),
+            polling: false,
+            events,
+        }
+    }
+
+    /// Watch these directories, and stop watching the others.
+    /// The directories are watched by polling if the native watcher fails.
+    fn set_directories(
+        &mut self,
+        directories: BTreeMap<PathBuf, RecursiveMode>,
+    ) -> Vec<WatcherError> {
+        if self.watcher.is_none() {
+            match start_watcher(self.events.clone(), self.polling) {
+                Ok(watcher) => self.watcher = Some(watcher),
+                Err(error) => return self.fall_back(vec![error], directories),
+            }
+        }
+
+        let Some(watcher) = self.watcher.as_mut() else {
+            return Vec::new();
+        };
+
+        // A directory whose mode changed is watched again.
+        for (directory, mode) in &self.directories {
+            if directories.get(directory) != Some(mode) {
+                // The watch of a removed directory is removed with it.
+                if let Err(error) = watcher.unwatch(directory) {
+                    debug!("Failed to unwatch {}: {}", directory.display(), error);
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut watch_limit_reached = false;
+
+        for (directory, mode) in &directories {
+            if self.directories.get(directory) == Some(mode) {
+                continue;
+            }
+
+            if let Err(error) = watcher.watch(directory, *mode) {
+                watch_limit_reached |= matches!(error.kind, notify::ErrorKind::MaxFilesWatch);
+                errors.push(WatcherError::Watch {
+                    path: directory.clone(),
+                    source: Arc::new(error),
+                });
+            }
+        }
+
+        if watch_limit_reached {
+            return self.fall_back(errors, directories);
+        }
+
+        self.directories = directories;
+
+        errors
+    }
+
+    /// Replace the native watcher by polling, unless it is already polling.
+    fn fall_back(
+        &mut self,
+        mut errors: Vec<WatcherError>,
+        directories: BTreeMap<PathBuf, RecursiveMode>,
+    ) -> Vec<WatcherError> {
+        if self.polling {
+            self.directories = directories;
+
+            return errors;
+        }
+
+        self.polling = true;
+        self.watcher = None;
+        self.directories.clear();
+        errors.extend(self.set_directories(directories));
+
+        errors
+    }
+}
+
+fn start_watcher(
+    events: mpsc::UnboundedSender<notify::Result<notify::Event>>,
+    polling: bool,
+) -> Result<Box<dyn notify::Watcher + Send>, WatcherError> {
+    // Sending fails only when the subscription is dropped.
+    let event_handler = move |event| {
+        let _ = events.unbounded_send(event);
+    };
 
+    if polling {
+        let config = notify::Config::default().with_poll_interval(POLL_INTERVAL);
+
+        Ok(Box::new(
+            notify::PollWatcher::new(event_handler, config)
+                .map_err(|error| WatcherError::Create(Arc::new(error)))?,
+        ))
+    } else {
+        Ok(Box::new(
+            notify::RecommendedWatcher::new(event_handler, notify::Config::default())
+                .map_err(|error| WatcherError::Create(Arc::new(error)))?,
+        ))
+    }
+}
+
+fn run_watcher() -> impl Stream<Item = crate::Message> {
     iced::stream::channel(4, async move |mut output| {
         debug!("Start file watcher subscription");
-        let (command_sender, mut command_receiver) = mpsc::channel::<Command>(8);
+        let (command_sender, mut command_receiver) = mpsc::unbounded::<Command>();
+        let (event_sender, mut event_receiver) = mpsc::unbounded();
 
         output
             .send(crate::Message::FileWatcher(Message::Initialize(
@@ -126,48 +509,272 @@ fn run_watcher() -> impl Stream<Item = crate::Message> {
             .await
             .unwrap();
 
-        let config = notify::Config::default();
-        let mut watcher = None;
-        let mut root_path: Option<PathBuf> = None;
-
-        while let Some(command) = command_receiver.next().await {
-            match command {
-                Command::Initialize(runtime) => {
-                    let mut output_handler = output.clone();
-                    let event_handler = move |event| {
-                        runtime.block_on(async {
-                            match event {
-                                Ok(event) => output_handler
-                                    .send(crate::Message::FileWatcher(
-                                        file_watcher::Message::Notify(event),
-                                    ))
-                                    .await
-                                    .unwrap(),
-                                Err(_) => todo!(),
-                            }
-                        });
-                    };
-
-                    watcher = match notify::RecommendedWatcher::new(event_handler, config) {
-                        Ok(watcher) => Some(watcher),
-                        Err(error) => {
-                            log::error!("Failed to create file watcher: {}", error);
-                            None
+        let mut watches = Watches::new(event_sender);
+        let mut coalescer = Coalescer::default();
+        // The time of the first and of the last event not sent yet.
+        let mut pending: Option<(Instant, Instant)> = None;
+
+        loop {
+            let deadline = pending.map(|(first, last)| (last + DEBOUNCE).min(first + MAX_DELAY));
+
+            tokio::select! {
+                command = command_receiver.next() => match command {
+                    Some(Command::Watch(directories)) => {
+                        let polling = watches.polling;
+
+                        for error in watches.set_directories(directories) {
+                            let _ = output
+                                .send(crate::Message::FileWatcher(Message::Failed(error)))
+                                .await;
                         }
-                    };
-                }
-                Command::ResetRootPath(path_buf) => {
-                    if let Some(watcher) = watcher.as_mut() {
-                        if let Some(root_path) = root_path.as_ref() {
-                            watcher.unwatch(root_path).unwrap();
+
+                        if watches.polling && !polling {
+                            let _ = output
+                                .send(crate::Message::FileWatcher(Message::Polling))
+                                .await;
                         }
-                        watcher
-                            .watch(&path_buf, notify::RecursiveMode::Recursive)
-                            .unwrap();
-                        root_path = Some(path_buf);
                     }
+                    None => break,
+                },
+                Some(event) = event_receiver.next() => match event {
+                    Ok(event) => {
+                        let now = Instant::now();
+
+                        coalescer.push(event);
+                        pending = Some((pending.map_or(now, |(first, _)| first), now));
+                    }
+                    Err(error) => {
+                        let _ = output
+                            .send(crate::Message::FileWatcher(Message::Failed(
+                                WatcherError::Notify(Arc::new(error)),
+                            )))
+                            .await;
+                    }
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)),
+                    if deadline.is_some() =>
+                {
+                    pending = None;
+
+                    let _ = output
+                        .send(crate::Message::FileWatcher(Message::Changed(
+                            coalescer.finish(),
+                        )))
+                        .await;
                 }
             }
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        time::Duration,
+    };
+
+    use iced::futures::{channel::mpsc, StreamExt};
+    use notify::{
+        event::{
+            AccessKind, AccessMode, CreateKind, DataChange, Flag, ModifyKind, RemoveKind,
+            RenameMode,
+        },
+        Event, EventKind, RecursiveMode,
+    };
+    use temp_dir_builder::TempDirectoryBuilder;
+
+    use super::{Changes, Coalescer, FileWatcher, Watches, MAX_CHANGES};
+
+    #[test]
+    fn test_rename_is_paired() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("clap.wav")
+            .build()
+            .unwrap();
+        let from = test_dir.path().join("snare.wav");
+        let to = test_dir.path().join("clap.wav");
+        let mut coalescer = Coalescer::default();
+
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                .add_path(from.clone())
+                .set_tracker(1),
+        );
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::To)))
+                .add_path(to.clone())
+                .set_tracker(1),
+        );
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                .add_path(from.clone())
+                .add_path(to.clone())
+                .set_tracker(1),
+        );
+
+        assert_eq!(
+            coalescer.finish(),
+            Changes {
+                renamed: vec![(from, to)],
+                ..Changes::default()
+            }
+        );
+        assert_eq!(coalescer.finish(), Changes::default());
+    }
+
+    #[test]
+    fn test_rename_without_destination_is_removed() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let from = test_dir.path().join("snare.wav");
+        let mut coalescer = Coalescer::default();
+
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                .add_path(from.clone())
+                .set_tracker(1),
+        );
+
+        assert_eq!(
+            coalescer.finish(),
+            Changes {
+                removed: vec![from],
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_changes_are_coalesced() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("kick.wav")
+            .build()
+            .unwrap();
+        let pack = test_dir.path().join("pack");
+        let kick = test_dir.path().join("kick.wav");
+        let temporary = test_dir.path().join("kick.wav.tmp");
+        let mut coalescer = Coalescer::default();
+
+        std::fs::create_dir(&pack).unwrap();
+
+        coalescer.push(Event::new(EventKind::Create(CreateKind::Folder)).add_path(pack.clone()));
+        coalescer
+            .push(Event::new(EventKind::Create(CreateKind::File)).add_path(pack.join("snare.wav")));
+        coalescer.push(Event::new(EventKind::Create(CreateKind::File)).add_path(temporary.clone()));
+        coalescer.push(Event::new(EventKind::Create(CreateKind::File)).add_path(kick.clone()));
+        coalescer.push(Event::new(EventKind::Remove(RemoveKind::File)).add_path(temporary.clone()));
+
+        assert_eq!(
+            coalescer.finish(),
+            Changes {
+                added: vec![pack, kick],
+                removed: vec![temporary],
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_modified_files() {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("bounce.wav")
+            .add_empty_file("new.wav")
+            .build()
+            .unwrap();
+        let bounce = test_dir.path().join("bounce.wav");
+        let new = test_dir.path().join("new.wav");
+        let mut coalescer = Coalescer::default();
+
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+                .add_path(bounce.clone()),
+        );
+        coalescer.push(
+            Event::new(EventKind::Access(AccessKind::Close(AccessMode::Write)))
+                .add_path(bounce.clone()),
+        );
+        coalescer.push(Event::new(EventKind::Create(CreateKind::File)).add_path(new.clone()));
+        coalescer.push(
+            Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+                .add_path(new.clone()),
+        );
+
+        assert_eq!(
+            coalescer.finish(),
+            Changes {
+                added: vec![new],
+                modified: vec![bounce],
+                ..Changes::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_event_storm_is_a_rescan() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let mut coalescer = Coalescer::default();
+
+        for index in 0..=MAX_CHANGES {
+            coalescer.push(
+                Event::new(EventKind::Create(CreateKind::File))
+                    .add_path(test_dir.path().join(format!("sample {index}.wav"))),
+            );
+        }
+
+        assert_eq!(
+            coalescer.finish(),
+            Changes {
+                rescan: true,
+                ..Changes::default()
+            }
+        );
+
+        coalescer.push(Event::new(EventKind::Other).set_flag(Flag::Rescan));
+
+        assert!(coalescer.finish().rescan);
+    }
+
+    /// During a search the root is watched with its subdirectories, a file created in a
+    /// directory neither expanded nor containing results is reported.
+    #[tokio::test]
+    async fn test_recursive_watch() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let root = test_dir.path().canonicalize().unwrap();
+        let kicks = root.join("drums").join("kicks");
+        let (events, mut receiver) = mpsc::unbounded();
+        let mut file_watcher = FileWatcher::new();
+        let mut watches = Watches::new(events);
+
+        std::fs::create_dir_all(&kicks).unwrap();
+        file_watcher.watch(
+            BTreeSet::from([root.clone(), root.join("drums")]),
+            Some(root.clone()),
+        );
+
+        assert_eq!(
+            file_watcher.directories,
+            BTreeMap::from([(root.clone(), RecursiveMode::Recursive)])
+        );
+        assert!(watches
+            .set_directories(file_watcher.directories.clone())
+            .is_empty());
+
+        let created = kicks.join("kick.wav");
+
+        std::fs::write(&created, b"").unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = receiver.next().await {
+                let event = event.unwrap();
+
+                if matches!(event.kind, EventKind::Create(_)) && event.paths.contains(&created) {
+                    return;
+                }
+            }
+
+            panic!("The watcher stopped");
+        })
+        .await
+        .unwrap();
+    }
+}
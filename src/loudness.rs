@@ -0,0 +1,438 @@
+//! Loudness and true peak as defined by ITU-R BS.1770 and EBU R128.
+
+use std::{collections::VecDeque, f64::consts::PI};
+
+/// The loudness is computed on blocks of 100 ms.
+const BLOCKS_PER_SECOND: usize = 10;
+/// 400 ms.
+const MOMENTARY_BLOCKS: usize = 4;
+/// 3 s.
+const SHORT_TERM_BLOCKS: usize = 30;
+/// Blocks quieter than this loudness are ignored, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks quieter than the loudness of the louder blocks minus this gate are ignored by the
+/// integrated loudness, in LU.
+const INTEGRATED_RELATIVE_GATE: f64 = 10.0;
+/// The same for the loudness range.
+const RANGE_RELATIVE_GATE: f64 = 20.0;
+const OVERSAMPLING: usize = 4;
+/// The samples used to interpolate a sample, half before and half after.
+const INTERPOLATION_TAPS: usize = 12;
+/// The samples between a sample and the values interpolated from it by the `TruePeakMeter`.
+pub const TRUE_PEAK_DELAY: usize = INTERPOLATION_TAPS / 2;
+
+/// The loudness of a whole file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileLoudness {
+    /// In LUFS, `None` for a silent file.
+    pub integrated: Option<f32>,
+    /// In LU, `None` for a file shorter than 3 s.
+    pub range: Option<f32>,
+}
+
+/// A second order filter.
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+
+        output
+    }
+}
+
+/// The weighting of BS.1770: a high shelf modelling the head, then a high pass filter.
+/// The coefficients of the standard are given for 48 kHz, they are computed for the sample rate.
+fn k_weighting(sample_rate: usize) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let gain = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (PI * 1_681.974_450_955_533 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let q = 0.500_327_037_323_877_3;
+    let k = (PI * 38.135_470_876_024_44 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// The weight of a channel, the surround channels of a 5.1 file are louder and the LFE is ignored.
+fn channel_weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (5 | 6, 4 | 5) | (5, 3) => 1.41,
+        _ => 1.0,
+    }
+}
+
+fn loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(powers: &[f64]) -> f64 {
+    powers.iter().sum::<f64>() / powers.len() as f64
+}
+
+/// The powers louder than the absolute gate.
+fn above_absolute_gate(powers: &[f64]) -> Vec<f64> {
+    powers
+        .iter()
+        .copied()
+        .filter(|power| loudness(*power) > ABSOLUTE_GATE)
+        .collect()
+}
+
+/// Measures the loudness of interleaved samples.
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    /// The channel of the next sample.
+    channel: usize,
+    block_size: usize,
+    /// The frames of the current block.
+    block_frames: usize,
+    /// The weighted sum of the squares of the current block.
+    block_sum: f64,
+    /// The mean square of the last blocks, enough for the short-term loudness.
+    blocks: VecDeque<f64>,
+    /// The mean square of each momentary window, one per block.
+    momentary_powers: Vec<f64>,
+    /// The mean square of each short-term window, one per block.
+    short_term_powers: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(channels: usize, sample_rate: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate); channels],
+            weights: (0..channels)
+                .map(|channel| channel_weight(channel, channels))
+                .collect(),
+            channel: 0,
+            block_size: (sample_rate / BLOCKS_PER_SECOND).max(1),
+            block_frames: 0,
+            block_sum: 0.0,
+            blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+            momentary_powers: Vec::new(),
+            short_term_powers: Vec::new(),
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.push_sample(*sample);
+        }
+    }
+
+    /// Add the next sample, the channels are interleaved.
+    pub fn push_sample(&mut self, sample: f32) {
+        let [shelf, high_pass] = &mut self.filters[self.channel];
+        let weighted = high_pass.process(shelf.process(sample as f64));
+
+        self.block_sum += self.weights[self.channel] * weighted * weighted;
+        self.channel += 1;
+
+        if self.channel < self.filters.len() {
+            return;
+        }
+
+        self.channel = 0;
+        self.block_frames += 1;
+
+        if self.block_frames == self.block_size {
+            self.end_block();
+        }
+    }
+
+    fn end_block(&mut self) {
+        if self.blocks.len() == SHORT_TERM_BLOCKS {
+            self.blocks.pop_front();
+        }
+
+        self.blocks
+            .push_back(self.block_sum / self.block_size as f64);
+        self.block_sum = 0.0;
+        self.block_frames = 0;
+
+        if let Some(power) = self.window_power(MOMENTARY_BLOCKS) {
+            self.momentary_powers.push(power);
+        }
+        if let Some(power) = self.window_power(SHORT_TERM_BLOCKS) {
+            self.short_term_powers.push(power);
+        }
+    }
+
+    /// The mean square of the last blocks, `None` if there are not enough blocks yet.
+    fn window_power(&self, blocks: usize) -> Option<f64> {
+        (self.blocks.len() >= blocks)
+            .then(|| self.blocks.iter().rev().take(blocks).sum::<f64>() / blocks as f64)
+    }
+
+    /// The loudness of the last 400 ms, in LUFS.
+    pub fn momentary(&self) -> Option<f32> {
+        self.window_power(MOMENTARY_BLOCKS)
+            .map(|power| loudness(power) as f32)
+    }
+
+    /// The loudness of the last 3 s, in LUFS.
+    pub fn short_term(&self) -> Option<f32> {
+        self.window_power(SHORT_TERM_BLOCKS)
+            .map(|power| loudness(power) as f32)
+    }
+
+    /// The gated loudness since the start, in LUFS.
+    pub fn integrated(&self) -> Option<f32> {
+        let powers = above_absolute_gate(&self.momentary_powers);
+
+        if powers.is_empty() {
+            return None;
+        }
+
+        let threshold = loudness(mean(&powers)) - INTEGRATED_RELATIVE_GATE;
+        let powers: Vec<f64> = powers
+            .into_iter()
+            .filter(|power| loudness(*power) > threshold)
+            .collect();
+
+        (!powers.is_empty()).then(|| loudness(mean(&powers)) as f32)
+    }
+
+    /// The difference between the loud and the quiet parts, in LU.
+    pub fn range(&self) -> Option<f32> {
+        let powers = above_absolute_gate(&self.short_term_powers);
+
+        if powers.len() < 2 {
+            return None;
+        }
+
+        let threshold = loudness(mean(&powers)) - RANGE_RELATIVE_GATE;
+        let mut levels: Vec<f64> = powers
+            .into_iter()
+            .map(loudness)
+            .filter(|level| *level > threshold)
+            .collect();
+
+        if levels.is_empty() {
+            return None;
+        }
+
+        levels.sort_by(f64::total_cmp);
+
+        let percentile = |ratio: f64| levels[((levels.len() - 1) as f64 * ratio).round() as usize];
+
+        Some((percentile(0.95) - percentile(0.10)) as f32)
+    }
+
+    pub fn file_loudness(&self) -> FileLoudness {
+        FileLoudness {
+            integrated: self.integrated(),
+            range: self.range(),
+        }
+    }
+}
+
+/// Measures the peaks between the samples by oversampling them.
+pub struct TruePeakMeter {
+    /// The interpolation filter of each oversampled position.
+    phases: [[f32; INTERPOLATION_TAPS]; OVERSAMPLING],
+    /// The last samples of each channel, the most recent first.
+    history: Vec<[f32; INTERPOLATION_TAPS]>,
+    channel: usize,
+}
+
+impl TruePeakMeter {
+    pub fn new(channels: usize) -> Self {
+        let center = (INTERPOLATION_TAPS / 2) as f64;
+        // A windowed sinc, each phase interpolates at a fraction of a sample.
+        let phases = std::array::from_fn(|phase| {
+            std::array::from_fn(|tap| {
+                let position = tap as f64 - center + phase as f64 / OVERSAMPLING as f64;
+                let sinc = if position == 0.0 {
+                    1.0
+                } else {
+                    (PI * position).sin() / (PI * position)
+                };
+                let window = 0.5 * (1.0 + (PI * position / center).cos());
+
+                (sinc * window) as f32
+            })
+        });
+
+        Self {
+            phases,
+            history: vec![[0.0; INTERPOLATION_TAPS]; channels],
+            channel: 0,
+        }
+    }
+
+    /// The highest absolute value of each channel, between the samples too.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        let mut peaks = vec![0f32; self.history.len()];
+
+        for sample in samples {
+            let channel = self.channel;
+
+            peaks[channel] = peaks[channel].max(self.push_sample(*sample));
+        }
+
+        peaks
+    }
+
+    /// Add the next sample, the channels are interleaved. Returns the highest absolute value
+    /// between the samples of its channel `TRUE_PEAK_DELAY` and `TRUE_PEAK_DELAY - 1` samples
+    /// before it.
+    pub fn push_sample(&mut self, sample: f32) -> f32 {
+        let channel = self.channel;
+
+        self.channel = (channel + 1) % self.history.len();
+
+        let history = &mut self.history[channel];
+
+        history.rotate_right(1);
+        history[0] = sample;
+
+        self.phases
+            .iter()
+            .map(|phase| {
+                phase
+                    .iter()
+                    .zip(history.iter())
+                    .map(|(coefficient, sample)| coefficient * sample)
+                    .sum::<f32>()
+                    .abs()
+            })
+            .fold(0f32, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{LoudnessMeter, TruePeakMeter};
+
+    const SAMPLE_RATE: usize = 48000;
+
+    fn sine(frequency: f32, amplitude: f32, phase: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                amplitude
+                    * (i as f32 / SAMPLE_RATE as f32 * frequency * 2.0 * std::f32::consts::PI
+                        + phase)
+                        .sin()
+            })
+            .collect()
+    }
+
+    fn db(value: f32) -> f32 {
+        20.0 * value.log10()
+    }
+
+    #[test]
+    fn test_full_scale_sine() {
+        // The reference of BS.1770, a 997 Hz sine at 0 dBFS on one channel is at -3.01 LUFS.
+        let mut meter = LoudnessMeter::new(1, SAMPLE_RATE);
+
+        meter.push(&sine(997.0, 1.0, 0.0, 5.0));
+
+        assert!((meter.momentary().unwrap() + 3.01).abs() < 0.05);
+        assert!((meter.short_term().unwrap() + 3.01).abs() < 0.05);
+        assert!((meter.integrated().unwrap() + 3.01).abs() < 0.05);
+        assert!(meter.range().unwrap() < 0.1);
+    }
+
+    #[test]
+    fn test_stereo_is_louder() {
+        let signal = sine(997.0, 0.1, 0.0, 1.0);
+        let interleaved: Vec<f32> = signal
+            .iter()
+            .flat_map(|sample| [*sample, *sample])
+            .collect();
+        let mut meter = LoudnessMeter::new(2, SAMPLE_RATE);
+
+        meter.push(&interleaved);
+
+        // -20 dBFS on both channels.
+        assert!((meter.integrated().unwrap() + 20.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_silence_is_gated() {
+        let mut meter = LoudnessMeter::new(1, SAMPLE_RATE);
+
+        meter.push(&sine(997.0, 1.0, 0.0, 2.0));
+        meter.push(&vec![0.0; SAMPLE_RATE * 10]);
+
+        // Only the windows overlapping the end of the sine are quieter.
+        assert!((meter.integrated().unwrap() + 3.01).abs() < 0.5);
+        assert!(meter.momentary().unwrap() < -70.0);
+    }
+
+    #[test]
+    fn test_short_file() {
+        let mut meter = LoudnessMeter::new(1, SAMPLE_RATE);
+
+        meter.push(&sine(997.0, 1.0, 0.0, 0.3));
+
+        assert_eq!(meter.momentary(), None);
+        assert_eq!(meter.file_loudness().integrated, None);
+        assert_eq!(meter.file_loudness().range, None);
+    }
+
+    #[test]
+    fn test_loudness_range() {
+        let mut meter = LoudnessMeter::new(1, SAMPLE_RATE);
+
+        // 10 dB between the two parts.
+        meter.push(&sine(997.0, 0.1, 0.0, 20.0));
+        meter.push(&sine(997.0, 0.1 * 10f32.powf(-10.0 / 20.0), 0.0, 20.0));
+
+        assert!((meter.range().unwrap() - 10.0).abs() < 0.5);
+    }
+
+    #[rstest]
+    // The samples are at 45° of the peaks, they are 3 dB below the peaks.
+    #[case(SAMPLE_RATE as f32 / 4.0, std::f32::consts::FRAC_PI_4)]
+    #[case(1000.0, 0.0)]
+    fn test_true_peak(#[case] frequency: f32, #[case] phase: f32) {
+        let signal = sine(frequency, 1.0, phase, 0.1);
+        let sample_peak = signal
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        let mut meter = TruePeakMeter::new(1);
+        let true_peak = meter.push(&signal)[0];
+
+        assert!(db(true_peak).abs() < 0.5);
+        assert!(true_peak >= sample_peak - 0.001);
+    }
+}
@@ -1,21 +1,29 @@
 use std::{
     ffi::OsStr,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
 use audio::Audio;
-use file_explorer::{FileExplorer, NewEntry};
+use config::{Action, Config};
+use file_explorer::{FileExplorer, ListingOptions, NewEntry};
+use file_operations::FileOperations;
 use file_watcher::FileWatcher;
 use iced::{
+    alignment::Vertical,
+    event,
     keyboard::{self, Key, Modifiers},
-    widget::{column, pane_grid, svg, PaneGrid},
-    window, Element, Font, Length, Subscription, Task, Theme,
+    widget::{button, column, container, pane_grid, row, svg, text, PaneGrid},
+    window, Element, Event, Font, Length, Subscription, Task, Theme,
 };
 use log::debug;
 use rfd::AsyncFileDialog;
 use scope::Scope;
 use search::Search;
+use spectrogram::Spectrogram;
 use spectrum::Spectrum;
+use tags::Tags;
 use tuner::Tuner;
 use vectorscope::Vectorscope;
 use visualization::Visualization;
@@ -23,18 +31,32 @@ use vu_meter::VuMeter;
 use waveform::Waveform;
 
 mod audio;
+mod clipboard;
+mod clipping;
+mod config;
+mod drag_out;
 mod fft_processor;
+mod file_details;
 mod file_explorer;
+mod file_operations;
 mod file_watcher;
+mod loudness;
+mod opus;
 mod scope;
 mod search;
+mod spectrogram;
 mod spectrum;
+mod tags;
 mod tuner;
 mod ui;
 mod vectorscope;
 mod visualization;
 mod vu_meter;
 mod waveform;
+mod wavpack;
+
+/// The displayed file is reloaded once it has not been written during this delay.
+const RELOAD_DELAY: Duration = Duration::from_millis(500);
 
 #[derive(thiserror::Error, Debug)]
 enum AppError {
@@ -63,21 +85,42 @@ fn main() -> Result<(), AppError> {
 #[derive(Debug, Clone)]
 enum Message {
     OpenDirectory(Option<PathBuf>),
+    /// A file or a directory is dropped onto the window.
+    FileDropped(PathBuf),
     FileExplorer(file_explorer::Message),
+    FileOperation(file_operations::Message),
     Search(search::Message),
+    Tags(tags::Message),
     Waveform(waveform::Message),
     Audio(audio::Message),
     VuMeter(vu_meter::Message),
     Vectorscope(vectorscope::Message),
     Scope(scope::Message),
     Spectrum(spectrum::Message),
+    Spectrogram(spectrogram::Message),
     FileWatcher(file_watcher::Message),
     Visualization(visualization::Message),
     Tuner(tuner::Message),
     PaneResized(pane_grid::ResizeEvent),
+    KeyPressed(Key, Modifiers),
+    ReloadConfig,
+    ConfigLoaded(Result<Config, config::ConfigError>),
+    DismissConfigError,
     /// Send this message to show the waveform of a file and play it using Task::done.
     /// Send SelectFile(None) to clear the waveform and stop playing audio.
     SelectFile(Option<PathBuf>),
+    CopyPath(PathBuf),
+    /// Copy the file to the clipboard, to paste it into a file manager.
+    CopyFile(PathBuf),
+    /// Open the directory containing the path in the system file manager.
+    ShowInFileManager(PathBuf),
+    /// Leave the search results and select the path in the explorer.
+    RevealInExplorer(PathBuf),
+    /// Files created or modified, the displayed file is reloaded if it is one of them.
+    FilesWritten(Vec<PathBuf>),
+    /// Reload the displayed file if it has not been written since the `FilesWritten` message
+    /// with this generation.
+    ReloadFile(usize),
 }
 
 enum View {
@@ -92,14 +135,17 @@ enum PaneState {
     Vectorscope,
     Scope,
     Spectrum,
+    Spectrogram,
     Tuner,
 }
 
 struct SEx {
     audio: Audio,
     explorer: FileExplorer,
+    file_operations: FileOperations,
     watcher: FileWatcher,
     search: Search,
+    tags: Tags,
     view: View,
     panes: pane_grid::State<PaneState>,
     waveform: Waveform,
@@ -108,8 +154,13 @@ struct SEx {
     vectorscope: Vectorscope,
     scope: Scope,
     spectrum: Spectrum,
-    theme: Theme,
+    spectrogram: Spectrogram,
+    config: Config,
+    /// The error of the last configuration loaded, the previous configuration is kept.
+    config_error: Option<config::ConfigError>,
     tuner: Tuner,
+    /// Incremented each time the displayed file is written, to debounce its reload.
+    reload_generation: usize,
 }
 
 impl SEx {
@@ -167,6 +218,16 @@ impl SEx {
 
         panes.resize(spectrum_split, 0.6);
 
+        let (_, spectrogram_split) = panes
+            .split(
+                pane_grid::Axis::Horizontal,
+                waveform_pane,
+                PaneState::Spectrogram,
+            )
+            .unwrap();
+
+        panes.resize(spectrogram_split, 0.5);
+
         let (_, tuner_split) = panes
             .split(pane_grid::Axis::Vertical, scope_pane, PaneState::Tuner)
             .unwrap();
@@ -175,12 +236,16 @@ impl SEx {
 
         panes.resize(tuner_split, 0.8);
 
+        let config = Config::default();
+
         (
             Self {
                 audio: Audio::new(),
-                explorer: FileExplorer::new(directory_icon.clone()),
+                explorer: FileExplorer::new(directory_icon.clone(), config.extensions.clone()),
+                file_operations: FileOperations::default(),
                 watcher: FileWatcher::new(),
-                search: Search::new(directory_icon.clone()),
+                search: Search::new(directory_icon.clone(), config.extensions.clone()),
+                tags: Tags::default(),
                 view: View::Explorer,
                 panes,
                 waveform: Waveform::default(),
@@ -188,11 +253,20 @@ impl SEx {
                 visualization: Visualization::new(),
                 vectorscope: Vectorscope::new(),
                 scope: Scope::new(),
-                spectrum: Spectrum::new(),
-                theme: Theme::CatppuccinFrappe,
+                spectrum: Spectrum::new(config.fft_settings(), config.spectrum_range),
+                spectrogram: Spectrogram::new(config.fft_settings()),
+                config,
+                config_error: None,
                 tuner: Tuner::new(),
+                reload_generation: 0,
             },
-            Task::perform(select_existing_directory(), Message::OpenDirectory),
+            Task::batch([
+                Task::done(Message::ReloadConfig),
+                Task::perform(tags::load(), |result| {
+                    Message::Tags(tags::Message::Loaded(result))
+                }),
+                Task::perform(select_existing_directory(), Message::OpenDirectory),
+            ]),
         )
     }
 
@@ -203,22 +277,49 @@ impl SEx {
                     assert!(path.is_dir());
                     debug!("Open directory {}", path.display());
                     self.search.set_root_path(path.clone());
-                    self.watcher.watch(&path);
-                    return self.explorer.set_root_path(&path);
+                    let task = self.explorer.set_root_path(&path);
+
+                    self.update_watched_directories();
+                    return task;
                 }
                 None => return window::get_latest().and_then(window::close),
             },
+            Message::FileDropped(path) => return self.open_dropped(path),
             Message::FileExplorer(message) => {
-                return self.explorer.update(message);
+                // The stars and tags follow the files renamed or moved.
+                let tags = match &message {
+                    file_explorer::Message::Renamed { from, to } => self.tags.rename(from, to),
+                    _ => Task::none(),
+                };
+                let task = self.explorer.update(message);
+
+                if self.explorer.take_expanded_changed() {
+                    self.update_watched_directories();
+                }
+                return Task::batch([task, tags]);
+            }
+            Message::FileOperation(message) => {
+                return self.file_operations.update(message);
+            }
+            Message::Tags(message) => {
+                return self.tags.update(message);
             }
             Message::Search(message) => {
-                return self.search.update(message, &mut self.view);
+                let task = self.search.update(message, &mut self.view);
+
+                self.update_watched_directories();
+                return task;
             }
             Message::PaneResized(pane_grid::ResizeEvent { split, ratio }) => {
                 self.panes.resize(split, ratio);
                 return self.waveform.update_bounds();
             }
             Message::Waveform(message) => {
+                if let waveform::Message::PlayPosition(position) = &message {
+                    self.spectrogram
+                        .update(spectrogram::Message::PlayPosition(*position));
+                }
+
                 return self.waveform.update(message);
             }
             Message::Audio(message) => {
@@ -236,13 +337,18 @@ impl SEx {
             Message::Spectrum(message) => {
                 self.spectrum.update(message);
             }
+            Message::Spectrogram(message) => {
+                self.spectrogram.update(message);
+            }
             Message::Tuner(message) => {
                 self.tuner.update(message);
             }
             Message::SelectFile(Some(path)) => {
-                if path.is_file() && display_file(&path) {
+                if path.is_file() && is_supported(&path, &self.config.extensions) {
                     self.audio.play(&path);
                     self.waveform.show(&path);
+                    self.spectrogram.show(&path);
+                    self.update_watched_directories();
                     return Task::done(Message::Visualization(
                         visualization::Message::SampleSelectionChanged,
                     ));
@@ -253,6 +359,62 @@ impl SEx {
             Message::SelectFile(None) => {
                 self.audio.stop();
                 self.waveform.clear();
+                self.spectrogram.clear();
+                self.update_watched_directories();
+            }
+            Message::FilesWritten(paths) => {
+                if self
+                    .waveform
+                    .path()
+                    .is_some_and(|path| paths.iter().any(|written| written == path))
+                {
+                    self.reload_generation += 1;
+
+                    let generation = self.reload_generation;
+
+                    return Task::perform(tokio::time::sleep(RELOAD_DELAY), move |_| {
+                        Message::ReloadFile(generation)
+                    });
+                }
+            }
+            Message::ReloadFile(generation) => {
+                if generation != self.reload_generation {
+                    return Task::none();
+                }
+
+                if let Some(path) = self.waveform.path().map(Path::to_path_buf) {
+                    debug!("Reload {}", path.display());
+                    // The player keeps its position, the analysis of the previous content
+                    // is discarded.
+                    self.audio.reload();
+                    self.waveform.show(&path);
+                    self.spectrogram.show(&path);
+                    return Task::done(Message::Visualization(
+                        visualization::Message::SampleSelectionChanged,
+                    ));
+                }
+            }
+            Message::CopyPath(path) => {
+                return iced::clipboard::write(path.display().to_string());
+            }
+            Message::CopyFile(path) => {
+                return clipboard::copy_files(vec![path]);
+            }
+            Message::ShowInFileManager(path) => {
+                if let Err(error) = file_operations::show_in_file_manager(&path) {
+                    log::error!("Failed to open the file manager: {}", error);
+                }
+            }
+            Message::RevealInExplorer(path) => {
+                let reveal = self.explorer.reveal(path);
+
+                self.update_watched_directories();
+                return Task::batch([
+                    Task::done(Message::Search(search::Message::SearchTextChanged(
+                        String::new(),
+                    ))),
+                    reveal,
+                ]);
             }
             Message::Visualization(message) => {
                 return self.visualization.update(message);
@@ -260,19 +422,88 @@ impl SEx {
             Message::FileWatcher(message) => {
                 return self.watcher.update(message);
             }
+            Message::KeyPressed(key, modifiers) => {
+                let text = self.typed_text(&key, modifiers);
+
+                // While a name is typed, a key bound to an action such as the space is part of
+                // the name.
+                if let Some(text) = text.as_ref().filter(|_| self.explorer.is_typing_ahead()) {
+                    return type_ahead(text);
+                }
+
+                if let Some(action) = self.config.key_bindings.action(&key, modifiers) {
+                    return self.on_action(action);
+                }
+
+                return text.map_or_else(Task::none, |text| type_ahead(&text));
+            }
+            Message::ReloadConfig => {
+                return Task::perform(config::load(), Message::ConfigLoaded);
+            }
+            Message::ConfigLoaded(Ok(config)) => {
+                debug!("Configuration loaded");
+                self.config_error = None;
+                return self.apply_config(config);
+            }
+            Message::ConfigLoaded(Err(error)) => {
+                log::error!("{}", error);
+                self.config_error = Some(error);
+            }
+            Message::DismissConfigError => {
+                self.config_error = None;
+            }
         }
 
         Task::none()
     }
 
+    /// The explorer is updated by the watcher only for the expanded directories, the other
+    /// directories are reloaded when they are expanded.
+    /// The directories of the search results and of the displayed file are watched too, to
+    /// update the results and to reload the file when it is rewritten.
+    fn update_watched_directories(&mut self) {
+        let mut directories = self.explorer.expanded_directories();
+
+        directories.extend(
+            self.waveform
+                .path()
+                .and_then(Path::parent)
+                .map(Path::to_path_buf),
+        );
+        // The results of a search can be anywhere under the root.
+        self.watcher.watch(
+            directories,
+            self.search.watched_root().map(Path::to_path_buf),
+        );
+    }
+
+    fn apply_config(&mut self, config: Config) -> Task<Message> {
+        log::set_max_level(config.log_level);
+        self.spectrum.set_fft_settings(config.fft_settings());
+        self.spectrum.set_range_db(config.spectrum_range);
+        self.spectrogram.set_fft_settings(config.fft_settings());
+        self.search.set_extensions(config.extensions.clone());
+
+        let task = self.explorer.set_extensions(config.extensions.clone());
+
+        self.config = config;
+
+        task
+    }
+
     fn view(&self) -> Element<Message> {
         let pane_grid = PaneGrid::new(&self.panes, |_id, pane, _is_maximized| match pane {
             PaneState::Explorer => match self.view {
-                View::Explorer => column![self.search.view_input(), self.explorer.view(),]
+                View::Explorer => column![self.search.view_input()]
+                    .push_maybe(self.view_config_error())
+                    .push_maybe(self.watcher.view())
+                    .push(self.explorer.view(&self.tags))
                     .width(Length::Fill)
                     .height(Length::Fill)
                     .into(),
-                View::Search => column![self.search.view_input(), self.search.view_results(),]
+                View::Search => column![self.search.view_input()]
+                    .push_maybe(self.view_config_error())
+                    .push(self.search.view_results(&self.tags))
                     .width(Length::Fill)
                     .height(Length::Fill)
                     .into(),
@@ -282,73 +513,293 @@ impl SEx {
             PaneState::Vectorscope => self.vectorscope.view().into(),
             PaneState::Scope => self.scope.view().into(),
             PaneState::Spectrum => self.spectrum.view().into(),
+            PaneState::Spectrogram => self.spectrogram.view(),
             PaneState::Tuner => self.tuner.view().into(),
         });
 
-        pane_grid
+        self.file_operations.view(
+            self.tags.view(
+                pane_grid
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .on_resize(8, Message::PaneResized)
+                    .into(),
+            ),
+        )
+    }
+
+    /// A warning shown above the explorer and the search results after an invalid
+    /// configuration file.
+    fn view_config_error(&self) -> Option<Element<Message>> {
+        let error = self.config_error.as_ref()?;
+
+        Some(
+            container(
+                row![
+                    column![
+                        text(error.to_string()).style(text::danger),
+                        text("The previous configuration is used.")
+                    ]
+                    .width(Length::Fill),
+                    button("Dismiss").on_press(Message::DismissConfigError),
+                ]
+                .spacing(8)
+                .align_y(Vertical::Center),
+            )
+            .padding(8)
             .width(Length::Fill)
-            .height(Length::Fill)
-            .on_resize(8, Message::PaneResized)
-            .into()
+            .into(),
+        )
     }
 
     fn theme(&self) -> Theme {
-        self.theme.clone()
+        self.config.theme.clone()
     }
 
     fn subscription(&self) -> Subscription<Message> {
         Subscription::batch([
-            keyboard::on_key_press(match self.view {
-                View::Explorer => Self::on_key_press_explorer,
-                View::Search => Self::on_key_press_search,
+            keyboard::on_key_press(|key, modifiers| Some(Message::KeyPressed(key, modifiers))),
+            event::listen_with(|event, _status, _id| match event {
+                Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+                _ => None,
             }),
             self.search.subscription(),
             self.waveform.subscription(),
+            self.spectrogram.subscription(),
+            self.vu_meter.subscription(),
             self.audio.subscription(),
             self.watcher.subscription(),
         ])
     }
 
-    fn on_key_press_explorer(key: Key, _modifiers: Modifiers) -> Option<crate::Message> {
-        match key {
-            keyboard::Key::Named(keyboard::key::Named::ArrowDown) => {
-                Some(Message::FileExplorer(file_explorer::Message::SelectNext))
+    /// A dropped directory becomes the root of the explorer.
+    /// For a dropped file, its directory becomes the root and the file is selected.
+    fn open_dropped(&mut self, path: PathBuf) -> Task<Message> {
+        let directory = if path.is_dir() {
+            path.clone()
+        } else if let Some(parent) = path.parent().filter(|_| path.is_file()) {
+            parent.to_path_buf()
+        } else {
+            log::warn!("Ignore dropped path {}", path.display());
+            return Task::none();
+        };
+
+        // Leave the search results to show the explorer.
+        let clear_search = Task::done(Message::Search(search::Message::SearchTextChanged(
+            String::new(),
+        )));
+        let open = self.update(Message::OpenDirectory(Some(directory)));
+
+        if path.is_file() {
+            self.explorer.select_when_loaded(path);
+        }
+
+        Task::batch([clear_search, open])
+    }
+
+    /// The text of a key typed without shortcut modifier, to jump to a file of the explorer.
+    /// A space can only continue a name.
+    fn typed_text(&self, key: &Key, modifiers: Modifiers) -> Option<String> {
+        let text = match key {
+            Key::Character(text) => text.to_string(),
+            Key::Named(keyboard::key::Named::Space) if self.explorer.is_typing_ahead() => {
+                " ".to_string()
             }
-            keyboard::Key::Named(keyboard::key::Named::ArrowUp) => Some(Message::FileExplorer(
+            _ => return None,
+        };
+
+        if modifiers.control()
+            || modifiers.alt()
+            || modifiers.logo()
+            || self.file_operations.has_dialog()
+            || self.tags.is_editing()
+            || !matches!(self.view, View::Explorer)
+        {
+            return None;
+        }
+
+        Some(text)
+    }
+
+    fn on_action(&mut self, action: Action) -> Task<Message> {
+        // The dialogs are modal.
+        if self.file_operations.has_dialog() {
+            return match action {
+                Action::ExpandCollapse => {
+                    Task::done(Message::FileOperation(file_operations::Message::Confirm))
+                }
+                Action::Cancel => {
+                    Task::done(Message::FileOperation(file_operations::Message::Cancel))
+                }
+                _ => Task::none(),
+            };
+        }
+
+        if self.tags.is_editing() {
+            return match action {
+                Action::ExpandCollapse => Task::done(Message::Tags(tags::Message::EditSubmitted)),
+                Action::Cancel => Task::done(Message::Tags(tags::Message::Cancel)),
+                _ => Task::none(),
+            };
+        }
+
+        match (action, &self.view) {
+            (Action::SelectNext, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::SelectNext))
+            }
+            (Action::SelectPrevious, View::Explorer) => Task::done(Message::FileExplorer(
                 file_explorer::Message::SelectPrevious,
             )),
-            keyboard::Key::Named(keyboard::key::Named::Enter) => Some(Message::FileExplorer(
+            (Action::ExpandCollapse, View::Explorer) => Task::done(Message::FileExplorer(
                 file_explorer::Message::ExpandCollapseCurrent,
             )),
-            _ => None,
-        }
-    }
+            (Action::SelectNext, View::Search) => {
+                Task::done(Message::Search(search::Message::SelectNext))
+            }
+            (Action::SelectPrevious, View::Search) => {
+                Task::done(Message::Search(search::Message::SelectPrevious))
+            }
+            (Action::SelectParent, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::SelectParent))
+            }
+            (Action::SelectChild, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::SelectChild))
+            }
+            (Action::SelectFirst, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::SelectFirst))
+            }
+            (Action::SelectLast, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::SelectLast))
+            }
+            (Action::PageUp, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::PageUp))
+            }
+            (Action::PageDown, View::Explorer) => {
+                Task::done(Message::FileExplorer(file_explorer::Message::PageDown))
+            }
+            // The transport works the same whatever the view.
+            (Action::TogglePlayback, _) => {
+                Task::done(Message::Audio(audio::Message::TogglePlayback))
+            }
+            (Action::Replay, _) => Task::done(Message::Audio(audio::Message::Replay)),
+            (Action::SeekBackward, _) => Task::done(Message::Audio(audio::Message::SeekBackward)),
+            (Action::SeekForward, _) => Task::done(Message::Audio(audio::Message::SeekForward)),
+            (Action::ToggleLoop, _) => Task::done(Message::Audio(audio::Message::ToggleLoop)),
+            (
+                Action::ExpandCollapse
+                | Action::SelectParent
+                | Action::SelectChild
+                | Action::SelectFirst
+                | Action::SelectLast
+                | Action::PageUp
+                | Action::PageDown,
+                View::Search,
+            ) => Task::none(),
+            (Action::ReloadConfig, _) => Task::done(Message::ReloadConfig),
+            (Action::Rename, View::Explorer) => match self.explorer.selection() {
+                Some(id) => Task::done(Message::FileExplorer(file_explorer::Message::StartRename(
+                    id,
+                ))),
+                None => Task::none(),
+            },
+            (Action::Duplicate, View::Explorer) => self
+                .explorer
+                .selected_path()
+                .filter(|path| path.is_file())
+                .map(file_operations::Request::Duplicate)
+                .map_or_else(Task::none, request_file_operation),
+            (Action::MoveTo, View::Explorer) => self
+                .explorer
+                .selected_path()
+                .map(file_operations::Request::Move)
+                .map_or_else(Task::none, request_file_operation),
+            (Action::NewFolder, View::Explorer) => {
+                // In the selected directory, or next to the selected file.
+                let parent = match self.explorer.selected_path() {
+                    Some(path) if path.is_dir() => Some(path),
+                    Some(path) => path.parent().map(Path::to_path_buf),
+                    None => self.explorer.root_path(),
+                };
 
-    fn on_key_press_search(key: Key, _modifiers: Modifiers) -> Option<crate::Message> {
-        match key {
-            keyboard::Key::Named(keyboard::key::Named::ArrowDown) => {
-                Some(Message::Search(search::Message::SelectNext))
+                parent
+                    .map(|parent| file_operations::Request::CreateFolder { parent })
+                    .map_or_else(Task::none, request_file_operation)
             }
-            keyboard::Key::Named(keyboard::key::Named::ArrowUp) => {
-                Some(Message::Search(search::Message::SelectPrevious))
+            (Action::Trash, View::Explorer) => self
+                .explorer
+                .selected_path()
+                .map(|path| file_operations::Request::Trash(vec![path]))
+                .map_or_else(Task::none, request_file_operation),
+            (Action::Undo, _) => Task::done(Message::FileOperation(file_operations::Message::Undo)),
+            (Action::Cancel, View::Explorer) if self.explorer.is_renaming() => {
+                Task::done(Message::FileExplorer(file_explorer::Message::CancelRename))
             }
-            _ => None,
+            // Stop playing and clear the waveform.
+            (Action::Cancel, _) => Task::done(Message::SelectFile(None)),
+            (Action::CopyPath, View::Explorer) => self
+                .explorer
+                .selected_path()
+                .map_or_else(Task::none, |path| Task::done(Message::CopyPath(path))),
+            (Action::CopyPath, View::Search) => self
+                .search
+                .selected_path()
+                .map_or_else(Task::none, |path| Task::done(Message::CopyPath(path))),
+            (Action::CopyFile, View::Explorer) => self
+                .explorer
+                .selected_path()
+                .map_or_else(Task::none, |path| Task::done(Message::CopyFile(path))),
+            (Action::CopyFile, View::Search) => self
+                .search
+                .selected_path()
+                .map_or_else(Task::none, |path| Task::done(Message::CopyFile(path))),
+            (
+                Action::Rename
+                | Action::Duplicate
+                | Action::MoveTo
+                | Action::NewFolder
+                | Action::Trash,
+                View::Search,
+            ) => Task::none(),
         }
     }
 }
 
-fn display_file(path: impl AsRef<Path>) -> bool {
+fn type_ahead(text: &str) -> Task<Message> {
+    Task::done(Message::FileExplorer(file_explorer::Message::TypeAhead(
+        text.to_string(),
+    )))
+}
+
+fn request_file_operation(request: file_operations::Request) -> Task<Message> {
+    Task::done(Message::FileOperation(file_operations::Message::Request(
+        request,
+    )))
+}
+
+fn display_file(path: impl AsRef<Path>, extensions: &[String]) -> bool {
     let path = path.as_ref();
 
-    if path
+    !is_hidden(path) && is_supported(path, extensions)
+}
+
+/// Returns true if the name of the file or directory starts with a dot.
+fn is_hidden(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
         .file_name()
         .and_then(OsStr::to_str)
         .is_some_and(|name| name.starts_with('.'))
-    {
-        return false;
-    }
+}
 
-    matches!(path.extension().and_then(OsStr::to_str), Some("wav") | Some("flac") | Some("ogg") | Some("mp3"))
+/// Returns true if the extension of the file is one of the extensions that can be played.
+fn is_supported(path: impl AsRef<Path>, extensions: &[String]) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|expected| expected.eq_ignore_ascii_case(extension))
+        })
 }
 
 async fn select_existing_directory() -> Option<PathBuf> {
@@ -358,34 +809,78 @@ async fn select_existing_directory() -> Option<PathBuf> {
         .map(|fh| fh.path().to_path_buf())
 }
 
-async fn load_directory_entries(directory_path: PathBuf) -> Vec<NewEntry> {
+async fn load_directory_entries(
+    directory_path: PathBuf,
+    extensions: Arc<Vec<String>>,
+    options: ListingOptions,
+) -> Vec<NewEntry> {
     let mut results = Vec::new();
 
     if let Ok(mut dir_entries) = tokio::fs::read_dir(directory_path).await {
         while let Ok(Some(entry)) = dir_entries.next_entry().await {
             if let Ok(metadata) = entry.metadata().await {
-                if metadata.is_dir() {
-                    results.push(NewEntry::Directory {
-                        path_component: entry.file_name(),
-                    });
-                } else if metadata.is_file() {
-                    let path: PathBuf = entry.path();
-
-                    if display_file(&path) {
-                        results.push(NewEntry::File {
-                            path_component: entry.file_name(),
-                        });
-                    }
+                if metadata.is_dir() || metadata.is_file() {
+                    results.extend(new_entry(
+                        &entry.path(),
+                        metadata.is_dir(),
+                        &extensions,
+                        options,
+                    ));
                 }
             }
         }
     }
 
-    results.sort();
+    // Directories first, then the files sorted by name whether they are supported or not.
+    results.sort_by(|left, right| {
+        right
+            .is_directory()
+            .cmp(&left.is_directory())
+            .then_with(|| {
+                file_explorer::compare_names(left.path_component(), right.path_component())
+            })
+    });
 
     results
 }
 
+/// The entry of a single file or directory, `None` if it does not exist or it is not listed.
+async fn load_entry(
+    path: PathBuf,
+    extensions: Arc<Vec<String>>,
+    options: ListingOptions,
+) -> Option<NewEntry> {
+    let metadata = tokio::fs::metadata(&path).await.ok()?;
+
+    if !metadata.is_dir() && !metadata.is_file() {
+        return None;
+    }
+
+    new_entry(&path, metadata.is_dir(), &extensions, options)
+}
+
+/// The entry listed for the path, `None` if the options filter it out.
+fn new_entry(
+    path: &Path,
+    is_directory: bool,
+    extensions: &[String],
+    options: ListingOptions,
+) -> Option<NewEntry> {
+    let path_component = path.file_name()?.to_os_string();
+
+    if !options.show_hidden && is_hidden(path) {
+        None
+    } else if is_directory {
+        Some(NewEntry::Directory { path_component })
+    } else if is_supported(path, extensions) {
+        Some(NewEntry::File { path_component })
+    } else if options.show_all_files {
+        Some(NewEntry::UnsupportedFile { path_component })
+    } else {
+        None
+    }
+}
+
 fn setup_logger() -> Result<(), AppError> {
     fern::Dispatch::new()
         .format(|out, message, record| {
@@ -398,7 +893,8 @@ fn setup_logger() -> Result<(), AppError> {
             ))
         })
         .level(log::LevelFilter::Off)
-        .level_for("sex", log::LevelFilter::Debug)
+        // The effective level is set with log::set_max_level() when the configuration is loaded.
+        .level_for("sex", log::LevelFilter::Trace)
         .chain(std::io::stdout())
         .chain(
             std::fs::OpenOptions::new()
@@ -409,18 +905,37 @@ fn setup_logger() -> Result<(), AppError> {
         )
         .apply()?;
 
+    log::set_max_level(Config::default().log_level);
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use std::ffi::OsString;
+    use std::{
+        ffi::OsString,
+        fs,
+        path::{Path, PathBuf},
+    };
 
-    use iced::Settings;
-    use iced_test::Simulator;
+    use iced::{
+        futures::channel::mpsc,
+        keyboard::{key::Named, Key, Modifiers},
+        Settings,
+    };
+    use iced_test::{selector::text, Simulator};
+    use rstest::rstest;
     use temp_dir_builder::TempDirectoryBuilder;
 
-    use crate::{load_directory_entries, Message, SEx};
+    use crate::{
+        config::{Config, ConfigError},
+        display_file, file_explorer,
+        file_explorer::ListingOptions,
+        load_directory_entries,
+        spectrogram::{self, SpectrogramCommand},
+        waveform::{self, WaveformCommand},
+        Message, SEx,
+    };
 
     pub(crate) fn simulator(app: &SEx) -> Simulator<Message> {
         Simulator::with_settings(
@@ -433,12 +948,57 @@ mod tests {
         )
     }
 
+    /// The path of a snapshot hash, given to `matches_hash`.
+    /// A missing hash fails the test instead of being written. After a change of the rendering,
+    /// run the tests with `SEX_UPDATE_SNAPSHOTS=1` to write the hashes again, then commit them.
+    pub(crate) fn snapshot_path(name: &str) -> PathBuf {
+        let directory = Path::new("snapshots");
+        let prefix = format!("{name}-");
+        let hashes: Vec<PathBuf> = fs::read_dir(directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".sha256"))
+            })
+            .collect();
+
+        if std::env::var_os("SEX_UPDATE_SNAPSHOTS").is_some() {
+            for hash in hashes {
+                fs::remove_file(hash).unwrap();
+            }
+        } else {
+            assert!(
+                !hashes.is_empty(),
+                "No hash for the snapshot '{name}', run the tests with SEX_UPDATE_SNAPSHOTS=1 to write it"
+            );
+        }
+
+        directory.join(name)
+    }
+
     pub(crate) fn generate_sine(size: usize) -> impl Iterator<Item = f32> {
         (0..size)
             .map(move |i| i as f32 / (size as f32) * 2.0 * std::f32::consts::PI)
             .map(f32::sin)
     }
 
+    #[rstest]
+    #[case("kick.wav", true)]
+    #[case("KICK.WAV", true)]
+    #[case("Kick.Aiff", true)]
+    #[case("kick.mid", false)]
+    #[case("kick", false)]
+    #[case(".kick.wav", false)]
+    fn test_display_file(#[case] path: &str, #[case] expected: bool) {
+        let extensions = ["wav", "aiff"].map(String::from);
+
+        assert_eq!(display_file(path, &extensions), expected);
+    }
+
     #[tokio::test]
     async fn test_load_directory_entries() {
         let test_dir = TempDirectoryBuilder::default()
@@ -447,10 +1007,159 @@ mod tests {
             .build()
             .unwrap();
 
-        let entries = load_directory_entries(test_dir.path().to_path_buf()).await;
+        let entries = load_directory_entries(
+            test_dir.path().to_path_buf(),
+            Config::default().extensions,
+            ListingOptions::default(),
+        )
+        .await;
 
         assert_eq!(entries.len(), 2);
         assert_eq!(entries[0].path_component(), &OsString::from("dir"));
         assert_eq!(entries[1].path_component(), &OsString::from("file.wav"));
     }
+
+    #[rstest]
+    #[case(false, false, &["dir", "b.wav"])]
+    #[case(true, false, &["dir", "a.mid", "b.wav", "c.txt"])]
+    #[case(false, true, &[".hidden_dir", "dir", ".hidden.wav", "b.wav"])]
+    #[case(true, true, &[".hidden_dir", "dir", ".hidden.txt", ".hidden.wav", "a.mid", "b.wav", "c.txt"])]
+    #[tokio::test]
+    async fn test_load_directory_entries_with_options(
+        #[case] show_all_files: bool,
+        #[case] show_hidden: bool,
+        #[case] expected: &[&str],
+    ) {
+        let test_dir = TempDirectoryBuilder::default()
+            .add_empty_file("a.mid")
+            .add_empty_file("b.wav")
+            .add_empty_file("c.txt")
+            .add_empty_file(".hidden.wav")
+            .add_empty_file(".hidden.txt")
+            .add_directory("dir")
+            .add_directory(".hidden_dir")
+            .build()
+            .unwrap();
+        let options = ListingOptions {
+            show_all_files,
+            show_hidden,
+        };
+
+        let entries = load_directory_entries(
+            test_dir.path().to_path_buf(),
+            Config::default().extensions,
+            options,
+        )
+        .await;
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|entry| entry.path_component().to_str().unwrap())
+            .collect();
+
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn test_typed_text() {
+        let (mut app, _task) = SEx::new();
+        let space = Key::Named(Named::Space);
+
+        assert_eq!(
+            app.typed_text(&Key::Character("k".into()), Modifiers::SHIFT),
+            Some("k".to_string())
+        );
+        assert_eq!(
+            app.typed_text(&Key::Character("l".into()), Modifiers::CTRL),
+            None
+        );
+        assert_eq!(app.typed_text(&space, Modifiers::empty()), None);
+
+        // The space continues the name being typed instead of toggling the playback.
+        let _ = app.update(Message::FileExplorer(file_explorer::Message::TypeAhead(
+            "k".into(),
+        )));
+
+        assert_eq!(
+            app.typed_text(&space, Modifiers::empty()),
+            Some(" ".to_string())
+        );
+    }
+
+    /// An invalid configuration is shown until it is dismissed or a valid one is loaded.
+    #[test]
+    fn test_config_error() {
+        let (mut app, _task) = SEx::new();
+        let error = ConfigError::UnknownLogLevel("loud".into());
+
+        let _ = app.update(Message::ConfigLoaded(Err(error.clone())));
+
+        simulator(&app).find(text(error.to_string())).unwrap();
+
+        let _ = app.update(Message::DismissConfigError);
+
+        assert!(simulator(&app).find(text(error.to_string())).is_err());
+
+        let _ = app.update(Message::ConfigLoaded(Err(error)));
+        let _ = app.update(Message::ConfigLoaded(Ok(Config::default())));
+
+        assert!(app.config_error.is_none());
+    }
+
+    /// Each write of the displayed file delays its reload, which shows its new content.
+    #[test]
+    fn test_reload_written_file() {
+        let (mut app, _task) = SEx::new();
+        let (waveform_sender, mut waveform_commands) = mpsc::channel(8);
+        let (spectrogram_sender, mut spectrogram_commands) = mpsc::channel(8);
+        let path = PathBuf::from("/samples/kick.wav");
+        let other_path = PathBuf::from("/samples/snare.wav");
+
+        let _ = app.update(Message::Waveform(waveform::Message::Initialized(
+            waveform_sender,
+        )));
+        let _ = app.update(Message::Spectrogram(spectrogram::Message::Initialized(
+            spectrogram_sender,
+        )));
+        app.waveform.show(&path);
+        app.spectrogram.show(&path);
+
+        while waveform_commands.try_next().is_ok() {}
+        while spectrogram_commands.try_next().is_ok() {}
+
+        let _ = app.update(Message::FilesWritten(vec![path.clone()]));
+        let _ = app.update(Message::FilesWritten(vec![
+            other_path.clone(),
+            path.clone(),
+        ]));
+        // Another file written does not delay the reload.
+        let _ = app.update(Message::FilesWritten(vec![other_path]));
+
+        assert_eq!(app.reload_generation, 2);
+
+        // The reload of a previous write is ignored.
+        let _ = app.update(Message::ReloadFile(1));
+
+        assert!(waveform_commands.try_next().is_err());
+        assert!(spectrogram_commands.try_next().is_err());
+
+        let _ = app.update(Message::ReloadFile(2));
+
+        assert!(matches!(
+            waveform_commands.try_next(),
+            Ok(Some(WaveformCommand::StopLoading))
+        ));
+        assert!(matches!(
+            waveform_commands.try_next(),
+            Ok(Some(WaveformCommand::LoadFile { path: loaded, .. })) if loaded == path
+        ));
+        assert!(matches!(
+            spectrogram_commands.try_next(),
+            Ok(Some(SpectrogramCommand::Stop))
+        ));
+        assert!(matches!(
+            spectrogram_commands.try_next(),
+            Ok(Some(SpectrogramCommand::Compute { path: computed, .. })) if computed == path
+        ));
+        assert_eq!(app.waveform.path(), Some(path.as_path()));
+    }
 }
@@ -0,0 +1,342 @@
+//! Decoding of the Ogg Opus files, which the decoding library used by rodio does not implement.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use audiopus::{
+    coder::{Decoder, GenericCtl},
+    packet::Packet,
+    Channels, MutSignals, SampleRate,
+};
+use ogg::{OggReadError, PacketReader};
+use rodio::{source::SeekError, Source};
+
+/// Opus is always decoded at 48 kHz.
+const SAMPLE_RATE: u32 = 48_000;
+/// The longest Opus packet, 120 ms at 48 kHz.
+const MAX_FRAME_SIZE: usize = 5760;
+/// The frames decoded before a seek position so the decoder converges, 80 ms as recommended by
+/// RFC 7845.
+const SEEK_PRE_ROLL: u64 = 3840;
+/// The end of the file searched for the last page, which gives the duration.
+const TAIL_SIZE: u64 = 64 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpusError {
+    #[error("Not an Opus stream")]
+    NotOpus,
+    #[error("Unsupported channel mapping {mapping} with {channels} channels")]
+    UnsupportedChannels { channels: u8, mapping: u8 },
+    #[error("Ogg error: {0}")]
+    Ogg(#[from] OggReadError),
+    #[error("Opus error: {0}")]
+    Opus(#[from] audiopus::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Decodes an Ogg Opus stream, with one or two channels.
+pub struct OpusDecoder<R: Read + Seek> {
+    reader: PacketReader<R>,
+    decoder: Decoder,
+    serial: u32,
+    channels: u16,
+    /// The frames at the start of the stream which are not part of the audio.
+    pre_skip: u64,
+    /// The linear gain from the header.
+    gain: f32,
+    /// The frames of the audio, `None` if the last page was not found.
+    frames_count: Option<u64>,
+    /// The frame of the next sample kept, from the start of the audio.
+    frame: u64,
+    /// The frames still to decode and drop, the pre-skip or the frames before a seek position.
+    skipped: u64,
+    /// The interleaved samples of the last packet decoded.
+    buffer: Vec<f32>,
+    /// The next sample of `buffer`.
+    index: usize,
+}
+
+impl<R: Read + Seek> OpusDecoder<R> {
+    pub fn new(data: R) -> Result<Self, OpusError> {
+        let mut reader = PacketReader::new(data);
+        let header = reader.read_packet()?.ok_or(OpusError::NotOpus)?;
+        let serial = header.stream_serial();
+        let header = header.data;
+
+        if header.len() < 19 || !header.starts_with(b"OpusHead") {
+            return Err(OpusError::NotOpus);
+        }
+
+        let channels = header[9];
+        let pre_skip = u16::from_le_bytes([header[10], header[11]]) as u64;
+        // In dB, Q7.8 fixed point.
+        let gain = i16::from_le_bytes([header[16], header[17]]) as f32 / 256.0;
+        let mapping = header[18];
+        let opus_channels = match (mapping, channels) {
+            (0, 1) => Channels::Mono,
+            (0, 2) => Channels::Stereo,
+            _ => return Err(OpusError::UnsupportedChannels { channels, mapping }),
+        };
+        let mut data = reader.into_inner();
+        let frames_count =
+            last_granule(&mut data, serial)?.map(|granule| granule.saturating_sub(pre_skip));
+        let mut decoder = Self {
+            reader: PacketReader::new(data),
+            decoder: Decoder::new(SampleRate::Hz48000, opus_channels)?,
+            serial,
+            channels: channels as u16,
+            pre_skip,
+            gain: 10f32.powf(gain / 20.0),
+            frames_count,
+            frame: 0,
+            skipped: 0,
+            buffer: Vec::with_capacity(MAX_FRAME_SIZE * channels as usize),
+            index: 0,
+        };
+
+        decoder.rewind()?;
+
+        Ok(decoder)
+    }
+
+    /// Go back to the first audio packet.
+    fn rewind(&mut self) -> Result<(), OpusError> {
+        self.reader.seek_bytes(SeekFrom::Start(0))?;
+
+        // The identification and the comment headers.
+        for _ in 0..2 {
+            self.next_packet()?.ok_or(OpusError::NotOpus)?;
+        }
+
+        self.frame = 0;
+        self.skipped = self.pre_skip;
+
+        Ok(())
+    }
+
+    fn next_packet(&mut self) -> Result<Option<ogg::Packet>, OpusError> {
+        while let Some(packet) = self.reader.read_packet()? {
+            if packet.stream_serial() == self.serial {
+                return Ok(Some(packet));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Decode a packet at the end of the buffer, returns the number of frames decoded.
+    fn decode(&mut self, data: &[u8]) -> Result<usize, OpusError> {
+        let channels = self.channels as usize;
+        let start = self.buffer.len();
+
+        self.buffer.resize(start + MAX_FRAME_SIZE * channels, 0.0);
+
+        let frames = Packet::try_from(data).and_then(|packet| {
+            let output = MutSignals::try_from(&mut self.buffer[start..])?;
+
+            self.decoder.decode_float(Some(packet), output, false)
+        });
+
+        self.buffer
+            .truncate(start + frames.as_ref().map_or(0, |frames| frames * channels));
+
+        Ok(frames?)
+    }
+
+    /// Drop the frames to skip and the frames after the end of the audio, then apply the gain.
+    fn trim(&mut self) {
+        let channels = self.channels as usize;
+        let frames = self.buffer.len() / channels;
+        let skipped = self.skipped.min(frames as u64) as usize;
+        let remaining = self.frames_count.map_or(usize::MAX, |count| {
+            count.saturating_sub(self.frame) as usize
+        });
+        let kept = (frames - skipped).min(remaining);
+
+        self.skipped -= skipped as u64;
+        self.frame += kept as u64;
+        self.buffer.truncate((skipped + kept) * channels);
+        self.buffer.drain(..skipped * channels);
+
+        if self.gain != 1.0 {
+            for sample in self.buffer.iter_mut() {
+                *sample *= self.gain;
+            }
+        }
+    }
+
+    /// Decode the next packet in the buffer, false at the end of the audio.
+    fn decode_next(&mut self) -> Result<bool, OpusError> {
+        self.buffer.clear();
+        self.index = 0;
+
+        if self.frames_count.is_some_and(|count| self.frame >= count) {
+            return Ok(false);
+        }
+
+        let Some(packet) = self.next_packet()? else {
+            return Ok(false);
+        };
+
+        self.decode(&packet.data)?;
+        self.trim();
+
+        Ok(true)
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<(), OpusError> {
+        let mut target = (position.as_secs_f64() * SAMPLE_RATE as f64) as u64;
+
+        if let Some(count) = self.frames_count {
+            target = target.min(count);
+        }
+
+        let goal = target + self.pre_skip;
+
+        self.decoder.reset_state()?;
+        self.buffer.clear();
+        self.index = 0;
+
+        // Close to the start there may be no page to seek to, decoding from the start is as fast.
+        if goal < 2 * SEEK_PRE_ROLL
+            || !self
+                .reader
+                .seek_absgp(Some(self.serial), goal - SEEK_PRE_ROLL)?
+        {
+            self.rewind()?;
+            self.skipped = goal;
+            self.frame = target;
+
+            return Ok(());
+        }
+
+        // The granule position is only known at the end of a page, the packets up to the end of
+        // the first page are decoded to know where they start.
+        let mut frames = 0;
+        let start = loop {
+            let Some(packet) = self.next_packet()? else {
+                self.buffer.clear();
+                self.frame = self.frames_count.unwrap_or(target);
+
+                return Ok(());
+            };
+
+            frames += self.decode(&packet.data)? as u64;
+
+            if packet.last_in_page() {
+                break packet.absgp_page().saturating_sub(frames);
+            }
+        };
+
+        self.skipped = goal.saturating_sub(start);
+        self.frame = start.max(goal) - self.pre_skip;
+        self.trim();
+
+        Ok(())
+    }
+}
+
+/// The granule position of the last page of the stream, its frames including the pre-skip.
+fn last_granule(data: &mut (impl Read + Seek), serial: u32) -> io::Result<Option<u64>> {
+    let length = data.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+
+    data.seek(SeekFrom::Start(length.saturating_sub(TAIL_SIZE)))?;
+    data.by_ref().take(TAIL_SIZE).read_to_end(&mut tail)?;
+
+    let granule = (0..tail.len().saturating_sub(18)).rev().find_map(|start| {
+        let page = &tail[start..];
+        let granule = u64::from_le_bytes(page[6..14].try_into().unwrap());
+        let page_serial = u32::from_le_bytes(page[14..18].try_into().unwrap());
+
+        // A granule position of -1 means no packet ends in the page.
+        (page.starts_with(b"OggS") && page_serial == serial && granule != u64::MAX)
+            .then_some(granule)
+    });
+
+    Ok(granule)
+}
+
+impl<R: Read + Seek> Iterator for OpusDecoder<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(sample) = self.buffer.get(self.index) {
+                self.index += 1;
+
+                return Some(*sample);
+            }
+
+            match self.decode_next() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                // A corrupted packet is skipped.
+                Err(OpusError::Opus(error)) => {
+                    log::error!("Failed to decode an Opus packet: {}", error)
+                }
+                Err(error) => {
+                    log::error!("Failed to read an Opus stream: {}", error);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Source for OpusDecoder<R> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.frames_count
+            .map(|count| Duration::from_secs_f64(count as f64 / SAMPLE_RATE as f64))
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.seek(position).map_err(|error| {
+            log::error!("Failed to seek in an Opus stream: {}", error);
+
+            SeekError::NotSupported {
+                underlying_source: std::any::type_name::<Self>(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::last_granule;
+
+    fn page(granule: u64, serial: u32) -> Vec<u8> {
+        let mut page = b"OggS\0\0".to_vec();
+
+        page.extend(granule.to_le_bytes());
+        page.extend(serial.to_le_bytes());
+        page.extend([0; 32]);
+        page
+    }
+
+    #[test]
+    fn test_last_granule() {
+        let data = [page(960, 1), page(1920, 2), page(u64::MAX, 1), page(0, 2)].concat();
+
+        assert_eq!(last_granule(&mut Cursor::new(&data), 1).unwrap(), Some(960));
+        assert_eq!(last_granule(&mut Cursor::new(&data), 2).unwrap(), Some(0));
+        assert_eq!(last_granule(&mut Cursor::new(&data), 3).unwrap(), None);
+    }
+}
@@ -92,7 +92,7 @@ mod tests {
     use std::sync::Arc;
 
     use crate::{
-        tests::{generate_sine, simulator},
+        tests::{generate_sine, simulator, snapshot_path},
         SEx,
     };
     use iced_test::Error;
@@ -107,7 +107,7 @@ mod tests {
         let mut ui = simulator(&app);
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_scope")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_scope"))?);
 
         Ok(())
     }
@@ -4,13 +4,22 @@ use iced::{
         stream::Stream,
         FutureExt, SinkExt, StreamExt,
     },
-    widget::{scrollable, svg, text_input, Column},
+    widget::{scrollable, scrollable::Viewport, svg, text_input},
     Element, Length, Subscription, Task,
 };
 use log::{debug, trace};
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::{display_file, ui, View};
+use crate::{
+    display_file, file_operations,
+    tags::{self, Tags},
+    ui, View,
+};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -23,6 +32,20 @@ pub enum Message {
     Selected(Option<usize>),
     SelectPrevious,
     SelectNext,
+    Scrolled(Viewport),
+    /// A file or a directory has been created, it is added to the results if it matches.
+    /// The content of a directory is searched.
+    Added(PathBuf),
+    /// The paths matching the search in a directory created.
+    FoundAdded(Vec<PathBuf>),
+    /// The results in the removed path are removed.
+    Removed(PathBuf),
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Too many changes to update the results one by one, search again.
+    Rescan,
 }
 
 pub struct Search {
@@ -33,21 +56,49 @@ pub struct Search {
     search_options: SearchOptions,
     selected: Option<usize>,
     directory_icon: svg::Handle,
+    /// The visible part of the results, known once they are displayed.
+    viewport: Option<Viewport>,
+    /// The results added by the file watcher during the search, not to add them twice.
+    added: HashSet<PathBuf>,
 }
 
 impl Search {
-    pub fn new(directory_icon: svg::Handle) -> Self {
+    pub fn new(directory_icon: svg::Handle, extensions: Arc<Vec<String>>) -> Self {
         Self {
             input: String::new(),
             command_sender: None,
             root_path: PathBuf::new(),
             results: Vec::new(),
-            search_options: SearchOptions::default(),
+            search_options: SearchOptions {
+                extensions,
+                ..Default::default()
+            },
             selected: None,
             directory_icon,
+            viewport: None,
+            added: HashSet::new(),
         }
     }
 
+    /// Change the extensions of the files searched.
+    /// It is applied to the next search.
+    pub fn set_extensions(&mut self, extensions: Arc<Vec<String>>) {
+        self.search_options.extensions = extensions;
+    }
+
+    /// The path of the selected result.
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.selected
+            .and_then(|selected| self.results.get(selected))
+            .map(|(path, _)| path.clone())
+    }
+
+    /// The directory to watch with its subdirectories to keep the results up to date, the root
+    /// while searching.
+    pub fn watched_root(&self) -> Option<&Path> {
+        (!self.input.is_empty()).then_some(self.root_path.as_path())
+    }
+
     pub fn set_root_path(&mut self, path: PathBuf) {
         self.root_path = path;
     }
@@ -59,24 +110,30 @@ impl Search {
             .into()
     }
 
-    pub fn view_results(&self) -> Element<crate::Message> {
-        let mut main_column = Column::new();
-
-        for (index, (path, icon)) in self.results.iter().enumerate() {
+    pub fn view_results<'a>(&'a self, tags: &'a Tags) -> Element<'a, crate::Message> {
+        let main_column = ui::virtual_list(self.results.len(), self.viewport.as_ref(), |index| {
+            let (path, icon) = &self.results[index];
             let selected = self
                 .selected
                 .is_some_and(|selected_index| selected_index == index);
-            let entry = ui::file_entry(
-                path.display(),
-                crate::Message::Search(Message::Selected(Some(index))),
-                icon.clone(),
-                selected,
-            );
-
-            main_column = main_column.push(entry);
-        }
 
-        scrollable(main_column.width(Length::Fill)).into()
+            let is_starred = tags.is_starred(path);
+
+            ui::context_menu(
+                ui::file_entry(
+                    tags.label(path, path.display()),
+                    crate::Message::Search(Message::Selected(Some(index))),
+                    icon.clone(),
+                    selected,
+                    false,
+                ),
+                move || context_menu_items(index, path, is_starred),
+            )
+        });
+
+        scrollable(main_column.width(Length::Fill))
+            .on_scroll(|viewport| crate::Message::Search(Message::Scrolled(viewport)))
+            .into()
     }
 
     pub fn update(&mut self, message: Message, view: &mut View) -> Task<crate::Message> {
@@ -87,7 +144,7 @@ impl Search {
             }
             Message::SearchTextChanged(text) => {
                 self.input = text.clone();
-                self.results.clear();
+                self.clear_results();
 
                 let command_sender = self.command_sender.as_mut().expect("not initialized");
                 if text.is_empty() {
@@ -106,26 +163,22 @@ impl Search {
                 };
             }
             Message::FoundResults(results) => {
-                self.results.extend(results.into_iter().map(|path| {
-                    let icon = if path.is_dir() {
-                        Some(self.directory_icon.clone())
-                    } else {
-                        None
-                    };
-
-                    (path, icon)
-                }));
+                for path in results {
+                    if !self.added.contains(&path) {
+                        self.push_result(path);
+                    }
+                }
             }
             Message::SearchStarted => {
                 debug!("Search started");
-                self.results.clear();
+                self.clear_results();
                 *view = View::Search;
             }
             Message::SearchFinished => {
                 debug!("Search finished");
             }
             Message::ClearResults => {
-                self.results.clear();
+                self.clear_results();
             }
             Message::Selected(selected) => {
                 self.selected = selected;
@@ -153,16 +206,154 @@ impl Search {
                     }
                 }
             }
+            Message::Scrolled(viewport) => {
+                self.viewport = Some(viewport);
+            }
+            Message::Added(path) => {
+                // The watcher only reports the directory, not what it contains.
+                let task = if path.is_dir() {
+                    self.search_added_directory(path.clone())
+                } else {
+                    Task::none()
+                };
+
+                self.add_result(path);
+
+                return task;
+            }
+            Message::FoundAdded(paths) => {
+                for path in paths {
+                    self.add_result(path);
+                }
+            }
+            Message::Removed(path) => {
+                self.retain_results(|result| !result.starts_with(&path));
+            }
+            Message::Renamed { from, to } => {
+                let mut renamed = false;
+
+                for (result, _) in self.results.iter_mut() {
+                    if let Ok(relative) = result.strip_prefix(&from) {
+                        *result = if relative.as_os_str().is_empty() {
+                            to.clone()
+                        } else {
+                            to.join(relative)
+                        };
+                        renamed = true;
+                    }
+                }
+
+                if renamed {
+                    let accepted = self.accept(&to);
+
+                    self.retain_results(|result| accepted || result != to);
+                } else {
+                    return self.update(Message::Added(to), view);
+                }
+            }
+            Message::Rescan => {
+                if !self.input.is_empty() {
+                    return Task::done(crate::Message::Search(Message::SearchTextChanged(
+                        self.input.clone(),
+                    )));
+                }
+            }
         }
 
         Task::none()
     }
 
+    /// True if the path is under the root and it matches the current search.
+    fn accept(&self, path: &Path) -> bool {
+        !self.input.is_empty()
+            && path.starts_with(&self.root_path)
+            && accept_path(path, &self.input, &self.search_options)
+    }
+
+    /// Search the content of a directory created under the root.
+    fn search_added_directory(&self, directory: PathBuf) -> Task<crate::Message> {
+        if !self
+            .watched_root()
+            .is_some_and(|root| directory.starts_with(root))
+        {
+            return Task::none();
+        }
+
+        Task::perform(
+            search_directory(directory, self.input.clone(), self.search_options.clone()),
+            |results| crate::Message::Search(Message::FoundAdded(results)),
+        )
+    }
+
+    /// Add a path created after the start of the search, if it matches and it was not found yet.
+    fn add_result(&mut self, path: PathBuf) {
+        if self.accept(&path) && !self.results.iter().any(|(result, _)| *result == path) {
+            self.added.insert(path.clone());
+            self.push_result(path);
+        }
+    }
+
+    fn push_result(&mut self, path: PathBuf) {
+        let icon = if path.is_dir() {
+            Some(self.directory_icon.clone())
+        } else {
+            None
+        };
+
+        self.results.push((path, icon));
+    }
+
+    /// Keep the results for which `keep` returns true, the selection follows its result.
+    fn retain_results(&mut self, keep: impl Fn(&Path) -> bool) {
+        let selected_path = self.selected_path();
+
+        self.results.retain(|(path, _)| keep(path));
+        self.selected = selected_path
+            .and_then(|selected| self.results.iter().position(|(path, _)| *path == selected));
+    }
+
+    fn clear_results(&mut self) {
+        self.results.clear();
+        self.added.clear();
+    }
+
     pub fn subscription(&self) -> Subscription<crate::Message> {
         Subscription::run(search_new).map(crate::Message::Search)
     }
 }
 
+fn context_menu_items(
+    index: usize,
+    path: &Path,
+    is_starred: bool,
+) -> Vec<Element<'_, crate::Message>> {
+    let mut items = vec![
+        ui::menu_item(
+            "Play",
+            crate::Message::Search(Message::Selected(Some(index))),
+        ),
+        ui::menu_item(
+            "Reveal in explorer",
+            crate::Message::RevealInExplorer(path.to_path_buf()),
+        ),
+        ui::menu_item("Copy path", crate::Message::CopyPath(path.to_path_buf())),
+        ui::menu_item("Copy file", crate::Message::CopyFile(path.to_path_buf())),
+        ui::menu_item(
+            "Show in file manager",
+            crate::Message::ShowInFileManager(path.to_path_buf()),
+        ),
+        ui::menu_item(
+            "Export…",
+            crate::Message::FileOperation(file_operations::Message::Request(
+                file_operations::Request::Export(path.to_path_buf()),
+            )),
+        ),
+    ];
+
+    items.extend(tags::menu_items(path, is_starred));
+    items
+}
+
 pub enum SearchCommand {
     Search(String, PathBuf, SearchOptions),
     Clear,
@@ -171,10 +362,11 @@ pub enum SearchCommand {
 #[derive(Default, Clone)]
 pub struct SearchOptions {
     case_sensitive: bool,
+    extensions: Arc<Vec<String>>,
 }
 
-fn accept_entry(entry: &tokio::fs::DirEntry, searched: &str, options: &SearchOptions) -> bool {
-    if let Some(filename) = entry.file_name().to_str() {
+fn accept_path(path: &Path, searched: &str, options: &SearchOptions) -> bool {
+    if let Some(filename) = path.file_name().and_then(OsStr::to_str) {
         let accept = if options.case_sensitive {
             filename.contains(searched)
         } else {
@@ -182,7 +374,7 @@ fn accept_entry(entry: &tokio::fs::DirEntry, searched: &str, options: &SearchOpt
                 || filename.to_lowercase().contains(&searched.to_lowercase())
         };
 
-        return accept && display_file(entry.path());
+        return accept && display_file(path, &options.extensions);
     }
 
     false
@@ -205,7 +397,7 @@ async fn search_filesystem(
                         if metadata.is_dir() {
                             stack.push(path.clone());
                         }
-                        if accept_entry(&entry, searched, options) {
+                        if accept_path(&path, searched, options) {
                             results.push(path);
                         }
                     }
@@ -217,6 +409,22 @@ async fn search_filesystem(
     results
 }
 
+/// The paths matching the search in the directory and its subdirectories.
+async fn search_directory(
+    directory: PathBuf,
+    searched: String,
+    options: SearchOptions,
+) -> Vec<PathBuf> {
+    let mut stack = vec![directory];
+    let mut results = Vec::new();
+
+    while !stack.is_empty() {
+        results.extend(search_filesystem(&mut stack, &searched, &options).await);
+    }
+
+    results
+}
+
 enum SearchState {
     Idle,
     Search(String, Vec<PathBuf>, SearchOptions),
@@ -271,3 +479,140 @@ fn search_new() -> impl Stream<Item = Message> {
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use iced::widget::svg;
+    use temp_dir_builder::TempDirectoryBuilder;
+
+    use crate::{config::Config, View};
+
+    use super::{search_directory, Message, Search};
+
+    fn result_paths(search: &Search) -> Vec<PathBuf> {
+        search
+            .results
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_results_follow_file_events() {
+        let mut search = Search::new(
+            svg::Handle::from_memory(Vec::new()),
+            Config::default().extensions,
+        );
+        let mut view = View::Search;
+
+        search.set_root_path(PathBuf::from("/samples"));
+        search.input = "kick".to_string();
+
+        let _ = search.update(
+            Message::FoundResults(vec![
+                PathBuf::from("/samples/a/kick.wav"),
+                PathBuf::from("/samples/b/kick 2.wav"),
+            ]),
+            &mut view,
+        );
+        let _ = search.update(Message::Selected(Some(1)), &mut view);
+        let _ = search.update(Message::Removed(PathBuf::from("/samples/a")), &mut view);
+
+        assert_eq!(
+            result_paths(&search),
+            [PathBuf::from("/samples/b/kick 2.wav")]
+        );
+        assert_eq!(search.selected, Some(0));
+
+        for path in [
+            "/samples/c/Kick 3.wav",
+            "/samples/c/snare.wav",
+            "/elsewhere/kick.wav",
+        ] {
+            let _ = search.update(Message::Added(PathBuf::from(path)), &mut view);
+        }
+        // Found by the search after being added by the watcher.
+        let _ = search.update(
+            Message::FoundResults(vec![PathBuf::from("/samples/c/Kick 3.wav")]),
+            &mut view,
+        );
+
+        assert_eq!(
+            result_paths(&search),
+            [
+                PathBuf::from("/samples/b/kick 2.wav"),
+                PathBuf::from("/samples/c/Kick 3.wav")
+            ]
+        );
+
+        let _ = search.update(
+            Message::Renamed {
+                from: PathBuf::from("/samples/b"),
+                to: PathBuf::from("/samples/drums"),
+            },
+            &mut view,
+        );
+
+        assert_eq!(
+            search.selected_path(),
+            Some(PathBuf::from("/samples/drums/kick 2.wav"))
+        );
+        assert_eq!(search.watched_root(), Some(Path::new("/samples")));
+
+        let _ = search.update(
+            Message::Renamed {
+                from: PathBuf::from("/samples/drums/kick 2.wav"),
+                to: PathBuf::from("/samples/drums/snare.wav"),
+            },
+            &mut view,
+        );
+
+        assert_eq!(
+            result_paths(&search),
+            [PathBuf::from("/samples/c/Kick 3.wav")]
+        );
+        assert_eq!(search.selected, None);
+    }
+
+    /// The watcher reports a directory created, the paths it contains are searched.
+    #[tokio::test]
+    async fn test_added_directory_is_searched() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let added = test_dir.path().join("new");
+        let kicks = added.join("kicks");
+        let mut search = Search::new(
+            svg::Handle::from_memory(Vec::new()),
+            Config::default().extensions,
+        );
+        let mut view = View::Search;
+
+        std::fs::create_dir_all(&kicks).unwrap();
+        std::fs::write(kicks.join("kick.wav"), b"").unwrap();
+        std::fs::write(added.join("snare.wav"), b"").unwrap();
+        search.set_root_path(test_dir.path().to_path_buf());
+        search.input = "kick".to_string();
+
+        let _ = search.update(Message::Added(added.clone()), &mut view);
+        let found = search_directory(
+            added.clone(),
+            search.input.clone(),
+            search.search_options.clone(),
+        )
+        .await;
+        let _ = search.update(Message::FoundAdded(found), &mut view);
+        // Found again by the search started before.
+        let _ = search.update(
+            Message::FoundResults(vec![kicks.join("kick.wav")]),
+            &mut view,
+        );
+
+        assert_eq!(result_paths(&search), [kicks.join("kick.wav")]);
+
+        // Without a search, the root is not watched with its subdirectories.
+        search.input.clear();
+
+        assert!(search.watched_root().is_none());
+    }
+}
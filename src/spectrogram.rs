@@ -0,0 +1,588 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use iced::{
+    futures::{channel::mpsc, FutureExt, SinkExt, Stream, StreamExt},
+    mouse,
+    widget::{
+        canvas::{self, Frame},
+        container, image, pick_list, Stack,
+    },
+    ContentFit, Element, Length, Point, Renderer, Size, Subscription, Theme,
+};
+use log::debug;
+
+use crate::{
+    audio,
+    fft_processor::{FftProcessor, FftSettings},
+    ui,
+};
+
+const MIN_FREQ: f32 = 20.0;
+const MAX_FREQ: f32 = 22000.0;
+/// Levels below the maximum minus this range are drawn with the first color of the colormap.
+const DYNAMIC_RANGE_DB: f32 = 90.0;
+/// The rows of the image, from `MIN_FREQ` at the bottom to the maximum frequency at the top, on a
+/// logarithmic scale.
+const ROWS: usize = 256;
+/// The frames of a long file are merged to keep the image small.
+const MAX_COLUMNS: usize = 2048;
+
+pub enum SpectrogramCommand {
+    Compute {
+        path: PathBuf,
+        settings: FftSettings,
+        /// Same as `WaveformCommand::LoadFile`, the result of a previous file is ignored.
+        generation: usize,
+    },
+    Stop,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Initialized(mpsc::Sender<SpectrogramCommand>),
+    Computed {
+        spectrogram: Arc<SpectrogramData>,
+        generation: usize,
+    },
+    PlayPosition(f32),
+    ColormapSelected(Colormap),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    #[default]
+    Inferno,
+    Viridis,
+    Grayscale,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 3] = [Colormap::Inferno, Colormap::Viridis, Colormap::Grayscale];
+
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Inferno => &[
+                [0, 0, 4],
+                [87, 16, 110],
+                [188, 55, 84],
+                [249, 142, 9],
+                [252, 255, 164],
+            ],
+            Colormap::Viridis => &[
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+            Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+        }
+    }
+
+    /// The color of a level between 0 and 1, interpolated between the stops of the colormap.
+    fn color(self, level: f32) -> [u8; 3] {
+        let stops = self.stops();
+        let position = level.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let ratio = position - index as f32;
+
+        std::array::from_fn(|component| {
+            let from = stops[index][component] as f32;
+            let to = stops[index + 1][component] as f32;
+
+            (from + (to - from) * ratio).round() as u8
+        })
+    }
+}
+
+impl fmt::Display for Colormap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Colormap::Inferno => "Inferno",
+            Colormap::Viridis => "Viridis",
+            Colormap::Grayscale => "Grayscale",
+        })
+    }
+}
+
+/// The levels of the whole file, normalized between 0 and 1.
+#[derive(Debug)]
+pub struct SpectrogramData {
+    columns: usize,
+    /// `ROWS` levels per column, from the lowest frequency.
+    levels: Vec<f32>,
+    max_frequency: f32,
+}
+
+impl SpectrogramData {
+    fn level(&self, column: usize, row: usize) -> f32 {
+        self.levels[column * ROWS + row]
+    }
+
+    fn image(&self, colormap: Colormap) -> image::Handle {
+        let mut pixels = Vec::with_capacity(self.columns * ROWS * 4);
+
+        // The first row of the image is the highest frequency.
+        for row in (0..ROWS).rev() {
+            for column in 0..self.columns {
+                let [red, green, blue] = colormap.color(self.level(column, row));
+
+                pixels.extend([red, green, blue, 255]);
+            }
+        }
+
+        image::Handle::from_rgba(self.columns as u32, ROWS as u32, pixels)
+    }
+}
+
+pub struct Spectrogram {
+    settings: FftSettings,
+    command_sender: Option<mpsc::Sender<SpectrogramCommand>>,
+    current_generation: usize,
+    path: Option<PathBuf>,
+    spectrogram: Option<Arc<SpectrogramData>>,
+    image: Option<image::Handle>,
+    colormap: Colormap,
+    play_position: f32,
+}
+
+impl Spectrogram {
+    pub fn new(settings: FftSettings) -> Self {
+        Self {
+            settings,
+            command_sender: None,
+            current_generation: 0,
+            path: None,
+            spectrogram: None,
+            image: None,
+            colormap: Colormap::default(),
+            play_position: 0.0,
+        }
+    }
+
+    /// The spectrogram of the displayed file is computed again with the new settings.
+    pub fn set_fft_settings(&mut self, settings: FftSettings) {
+        if settings != self.settings {
+            self.settings = settings;
+
+            if let Some(path) = self.path.clone() {
+                self.show(path);
+            }
+        }
+    }
+
+    /// Compute the spectrogram of the file in the background.
+    pub fn show(&mut self, path: impl AsRef<Path>) {
+        self.clear();
+        self.path = Some(path.as_ref().to_path_buf());
+
+        if let Some(sender) = self.command_sender.as_mut() {
+            sender
+                .try_send(SpectrogramCommand::Compute {
+                    path: path.as_ref().to_path_buf(),
+                    settings: self.settings,
+                    generation: self.current_generation,
+                })
+                .unwrap();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.path = None;
+        self.spectrogram = None;
+        self.image = None;
+        self.play_position = 0.0;
+
+        if let Some(sender) = self.command_sender.as_mut() {
+            self.current_generation += 1;
+
+            sender.try_send(SpectrogramCommand::Stop).unwrap();
+        }
+    }
+
+    pub fn update(&mut self, message: Message) {
+        match message {
+            Message::Initialized(command_sender) => {
+                debug!("Spectrogram initialized");
+                self.command_sender = Some(command_sender);
+            }
+            Message::Computed {
+                spectrogram,
+                generation,
+            } => {
+                if generation == self.current_generation {
+                    self.image = Some(spectrogram.image(self.colormap));
+                    self.spectrogram = Some(spectrogram);
+                }
+            }
+            Message::PlayPosition(position) => {
+                self.play_position = position;
+            }
+            Message::ColormapSelected(colormap) => {
+                self.colormap = colormap;
+                self.image = self
+                    .spectrogram
+                    .as_ref()
+                    .map(|spectrogram| spectrogram.image(colormap));
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<crate::Message> {
+        let mut layers = Stack::new().width(Length::Fill).height(Length::Fill);
+
+        if let Some(handle) = self.image.as_ref() {
+            layers = layers.push(
+                image(handle.clone())
+                    .content_fit(ContentFit::Fill)
+                    .width(Length::Fill)
+                    .height(Length::Fill),
+            );
+        }
+
+        layers
+            .push(canvas(self).width(Length::Fill).height(Length::Fill))
+            .push(
+                container(
+                    pick_list(Colormap::ALL, Some(self.colormap), |colormap| {
+                        crate::Message::Spectrogram(Message::ColormapSelected(colormap))
+                    })
+                    .text_size(12),
+                )
+                .align_right(Length::Fill)
+                .padding(4),
+            )
+            .into()
+    }
+
+    pub fn subscription(&self) -> Subscription<crate::Message> {
+        Subscription::run(spectrogram_computing).map(crate::Message::Spectrogram)
+    }
+}
+
+fn spectrogram_computing() -> impl Stream<Item = Message> {
+    iced::stream::channel(8, async move |mut output| {
+        let (command_sender, mut command_receiver) = mpsc::channel::<SpectrogramCommand>(8);
+
+        output
+            .send(Message::Initialized(command_sender))
+            .await
+            .unwrap();
+
+        while let Some(command) = command_receiver.next().await {
+            let SpectrogramCommand::Compute {
+                path,
+                settings,
+                generation,
+            } = command
+            else {
+                continue;
+            };
+
+            let spectrogram = compute(&path, settings, || {
+                matches!(
+                    command_receiver.next().now_or_never().flatten(),
+                    Some(SpectrogramCommand::Stop)
+                )
+            });
+
+            if let Some(spectrogram) = spectrogram {
+                output
+                    .send(Message::Computed {
+                        spectrogram: Arc::new(spectrogram),
+                        generation,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+    })
+}
+
+/// The spectrogram of the file downmixed to mono, `None` if it cannot be decoded or if `stopped`
+/// returns true.
+fn compute(
+    path: &Path,
+    settings: FftSettings,
+    mut stopped: impl FnMut() -> bool,
+) -> Option<SpectrogramData> {
+    let mut decoder = match audio::open_decoder(path) {
+        Ok(decoder) => decoder,
+        Err(error) => {
+            log::error!("Failed to decode file '{}': {}", path.display(), error);
+            return None;
+        }
+    };
+    let sample_rate = decoder.sample_rate() as usize;
+
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let fft_size = settings.size;
+    let mut analyzer = Analyzer::new(settings, sample_rate);
+    let mut buffer = Vec::with_capacity(fft_size);
+    let mut channel = 0;
+    let mut accumulator = 0f32;
+
+    while let Some(sample) = decoder.next() {
+        accumulator += sample;
+        channel += 1;
+
+        if channel == decoder.channels() {
+            buffer.push(accumulator / decoder.channels() as f32);
+            accumulator = 0f32;
+            channel -= decoder.channels();
+
+            if buffer.len() == fft_size {
+                if stopped() {
+                    return None;
+                }
+
+                analyzer.push(&buffer);
+                buffer.clear();
+            }
+        }
+    }
+
+    // The end of the file is padded with silence to fill the last frame.
+    if !buffer.is_empty() {
+        buffer.resize(fft_size, 0f32);
+        analyzer.push(&buffer);
+    }
+
+    let spectrogram = analyzer.finish();
+
+    // An empty file has no image.
+    (spectrogram.columns > 0).then_some(spectrogram)
+}
+
+/// Computes the levels of each frame on the rows of the spectrogram.
+struct Analyzer {
+    processor: FftProcessor,
+    /// The range of bins of each row.
+    rows: Vec<std::ops::Range<usize>>,
+    max_frequency: f32,
+    magnitudes: Vec<f32>,
+    frames: Vec<f32>,
+}
+
+impl Analyzer {
+    fn new(settings: FftSettings, sample_rate: usize) -> Self {
+        let fft_size = settings.size;
+        let half_fft_size = fft_size / 2;
+        let bin_resolution = sample_rate as f32 / fft_size as f32;
+        let max_frequency = MAX_FREQ.min(sample_rate as f32 / 2.0);
+        let rows = (0..ROWS)
+            .map(|row| {
+                let low = row_frequency(row as f32, max_frequency) / bin_resolution;
+                let high = row_frequency((row + 1) as f32, max_frequency) / bin_resolution;
+                let start = (low.floor() as usize).min(half_fft_size - 1);
+                let end = (high.ceil() as usize).clamp(start + 1, half_fft_size);
+
+                start..end
+            })
+            .collect();
+
+        Self {
+            processor: FftProcessor::new(settings),
+            rows,
+            max_frequency,
+            magnitudes: Vec::with_capacity(half_fft_size),
+            frames: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, buffer: &[f32]) {
+        let half_fft_size = self.processor.fft_size() / 2;
+        // Same reference as the spectrum, a full scale sine is at 0 dB.
+        let magnitude_zero_db = self.processor.magnitude_zero_db();
+        let mut input = buffer;
+
+        while let Some(results) = self.processor.process(input) {
+            self.magnitudes.clear();
+            self.magnitudes.extend(
+                results
+                    .take(half_fft_size)
+                    .map(|result| (result.re * result.re + result.im * result.im).sqrt()),
+            );
+
+            for bins in self.rows.iter() {
+                let magnitude = self.magnitudes[bins.clone()]
+                    .iter()
+                    .copied()
+                    .fold(0f32, f32::max);
+                let amplitude = magnitude / magnitude_zero_db;
+                let db = 20.0 * amplitude.max(f32::EPSILON).log10();
+
+                self.frames
+                    .push(((db + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0));
+            }
+
+            input = &[];
+        }
+    }
+
+    /// Merge the frames in at most `MAX_COLUMNS` columns, keeping the highest levels.
+    fn finish(self) -> SpectrogramData {
+        let frame_count = self.frames.len() / ROWS;
+        let columns = frame_count.min(MAX_COLUMNS);
+        let mut levels = vec![0f32; columns * ROWS];
+
+        for (column, column_levels) in levels.chunks_mut(ROWS).enumerate() {
+            let first_frame = column * frame_count / columns;
+            let last_frame = (column + 1) * frame_count / columns;
+
+            for frame in self.frames[first_frame * ROWS..last_frame * ROWS].chunks(ROWS) {
+                for (level, frame_level) in column_levels.iter_mut().zip(frame) {
+                    *level = level.max(*frame_level);
+                }
+            }
+        }
+
+        SpectrogramData {
+            columns,
+            levels,
+            max_frequency: self.max_frequency,
+        }
+    }
+}
+
+/// The frequency at the bottom of the row, the rows are spread on a logarithmic scale.
+fn row_frequency(row: f32, max_frequency: f32) -> f32 {
+    MIN_FREQ * (max_frequency / MIN_FREQ).powf(row / ROWS as f32)
+}
+
+impl canvas::Program<crate::Message> for Spectrogram {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: iced::Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let mut frame = Frame::new(renderer, bounds.size());
+
+        if let Some(spectrogram) = self.spectrogram.as_ref() {
+            let color = theme.extended_palette().secondary.base.color;
+
+            // Frequency axis
+            for frequency in ui::FREQUENCY_GRID {
+                if frequency >= spectrogram.max_frequency {
+                    continue;
+                }
+
+                let y = frame.height()
+                    * (1.0
+                        - ui::frequency_position(frequency, MIN_FREQ, spectrogram.max_frequency));
+
+                frame.fill_rectangle(Point::new(0.0, y), Size::new(6.0, 1.0), color);
+                frame.fill_text(canvas::Text {
+                    content: ui::format_frequency(frequency),
+                    position: Point::new(8.0, y - 6.0),
+                    color,
+                    size: 10f32.into(),
+                    ..Default::default()
+                });
+            }
+
+            // Play position
+            frame.fill_rectangle(
+                Point::new(self.play_position * frame.width(), 0.0),
+                Size::new(1.0, frame.height()),
+                color,
+            );
+        }
+
+        let path = canvas::Path::line(Point::ORIGIN, Point::new(frame.width(), 0.0));
+
+        frame.stroke(&path, ui::separation_line_stroke(theme));
+
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analyzer, Colormap, MAX_COLUMNS, MIN_FREQ, ROWS};
+    use crate::{
+        fft_processor::{FftSettings, WindowFunction},
+        ui,
+    };
+
+    const SAMPLE_RATE: usize = 44100;
+    const FFT_SIZE: usize = 1024;
+
+    fn settings(size: usize) -> FftSettings {
+        FftSettings {
+            size,
+            window: WindowFunction::Hann,
+            overlap: 0,
+        }
+    }
+
+    fn sine(frequency: f32, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|i| (i as f32 / SAMPLE_RATE as f32 * frequency * 2.0 * std::f32::consts::PI).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_is_on_its_row() {
+        let mut analyzer = Analyzer::new(settings(FFT_SIZE), SAMPLE_RATE);
+
+        for buffer in sine(1000.0, FFT_SIZE * 8).chunks(FFT_SIZE) {
+            analyzer.push(buffer);
+        }
+
+        let spectrogram = analyzer.finish();
+        let expected_row = (ui::frequency_position(1000.0, MIN_FREQ, spectrogram.max_frequency)
+            * ROWS as f32) as usize;
+
+        assert_eq!(spectrogram.columns, 8);
+
+        for column in 0..spectrogram.columns {
+            let loudest_row = (0..ROWS)
+                .max_by(|left, right| {
+                    spectrogram
+                        .level(column, *left)
+                        .total_cmp(&spectrogram.level(column, *right))
+                })
+                .unwrap();
+
+            assert!(loudest_row.abs_diff(expected_row) <= 3);
+            assert!(spectrogram.level(column, loudest_row) > 0.9);
+            assert!(spectrogram.level(column, ROWS - 1) < 0.5);
+        }
+    }
+
+    #[test]
+    fn test_long_file_frames_are_merged() {
+        let mut analyzer = Analyzer::new(settings(512), SAMPLE_RATE);
+        let silence = [0f32; 512];
+
+        for _ in 0..MAX_COLUMNS * 2 + 1 {
+            analyzer.push(&silence);
+        }
+
+        let spectrogram = analyzer.finish();
+
+        assert_eq!(spectrogram.columns, MAX_COLUMNS);
+        assert_eq!(spectrogram.levels.len(), MAX_COLUMNS * ROWS);
+    }
+
+    #[test]
+    fn test_colormap() {
+        assert_eq!(Colormap::Grayscale.color(0.0), [0, 0, 0]);
+        assert_eq!(Colormap::Grayscale.color(0.5), [128, 128, 128]);
+        assert_eq!(Colormap::Grayscale.color(2.0), [255, 255, 255]);
+        assert_eq!(Colormap::Inferno.color(1.0), [252, 255, 164]);
+    }
+}
@@ -1,87 +1,330 @@
-use std::sync::Arc;
+use std::{fmt, ops::Range, sync::Arc};
 
 use iced::widget::canvas;
 use iced::{
     mouse,
-    widget::canvas::{Frame, Path},
-    Element, Length, Point, Renderer, Size, Theme,
+    widget::{
+        canvas::{Frame, Path, Stroke},
+        container, pick_list, stack,
+    },
+    Color, Element, Length, Point, Renderer, Size, Theme,
 };
 
-use crate::{fft_processor::FftProcessor, ui};
+use crate::{
+    fft_processor::{FftProcessor, FftSettings},
+    ui,
+};
 
-/// FFT size, bigger FFT causes slower updates.
-/// 2048 gives good results, there are enough bins, and it's not too slow.
-/// The priority here is the visual result.
-const FFT_SIZE: usize = 2048;
-/// 1023.75037 is the value I get for the bin of frequency 9996.094 (which is the maximum frequency
-/// displayed) if I play a generated sine at 9996.094Hz at 0dB.
-/// So I'm rounding to 1024 to be sure its big enough.
-const MAGNITUDE_ZERO_DB: f32 = 1024.0;
 const MIN_FREQ: f32 = 20.0;
 const MAX_FREQ: f32 = 22000.0;
+/// The levels are computed on points spread on a logarithmic frequency axis.
+const DISPLAY_POINTS: usize = 256;
+/// Each point is the level of the band of 1/SMOOTHING octave around its frequency.
+const SMOOTHING: f32 = 6.0;
+/// Time constant of a rising level, in seconds.
+const ATTACK: f32 = 0.01;
+/// Time constant of a falling level, in seconds.
+const RELEASE: f32 = 0.3;
+/// A peak is held during this time, in seconds, before falling.
+const PEAK_HOLD: f32 = 1.0;
+const PEAK_FALL_DB_PER_SECOND: f32 = 20.0;
+/// The level of silence.
+const MIN_DB: f32 = -200.0;
+
+/// The signals analyzed, each one is drawn as a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// The downmix of all the channels.
+    #[default]
+    Mono,
+    /// The first two channels.
+    LeftRight,
+    /// The sum and the difference of the first two channels.
+    MidSide,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::Mono, Mode::LeftRight, Mode::MidSide];
+
+    fn trace_names(self) -> &'static [&'static str] {
+        match self {
+            Mode::Mono => &[""],
+            Mode::LeftRight => &["L", "R"],
+            Mode::MidSide => &["M", "S"],
+        }
+    }
+
+    /// The signal of each trace. A mono file is played on both the left and the right channel.
+    fn signals(self, channels: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let (Some(left), Some(right)) = (channels.first(), channels.get(1).or(channels.first()))
+        else {
+            return Vec::new();
+        };
+
+        match self {
+            Mode::Mono => vec![(0..left.len())
+                .map(|frame| {
+                    channels.iter().map(|channel| channel[frame]).sum::<f32>()
+                        / channels.len() as f32
+                })
+                .collect()],
+            Mode::LeftRight => vec![left.clone(), right.clone()],
+            Mode::MidSide => vec![
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| (left + right) / 2.0)
+                    .collect(),
+                left.iter()
+                    .zip(right)
+                    .map(|(left, right)| (left - right) / 2.0)
+                    .collect(),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Mono => "Mono",
+            Mode::LeftRight => "Left / Right",
+            Mode::MidSide => "Mid / Side",
+        })
+    }
+}
+
+/// The levels of one signal.
+struct Trace {
+    processor: FftProcessor,
+    /// The level of each point in dB, empty when nothing is played.
+    levels: Vec<f32>,
+    peaks: Vec<f32>,
+    /// The time since each peak was reached, in seconds.
+    peak_ages: Vec<f32>,
+}
+
+impl Trace {
+    fn new(settings: FftSettings) -> Self {
+        Self {
+            processor: FftProcessor::new(settings),
+            levels: Vec::new(),
+            peaks: Vec::new(),
+            peak_ages: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.levels.clear();
+        self.peaks.clear();
+        self.peak_ages.clear();
+    }
+
+    fn process(&mut self, samples: &[f32], points: &[Range<usize>], sample_rate: usize) {
+        // With a FFT of 2048 and a Hann window, 1023.75037 is the value I get for the bin of
+        // frequency 9996.094 if I play a generated sine at 9996.094Hz at 0dB. So I'm rounding to
+        // half the FFT size to be sure its big enough, the other windows are scaled by their gain.
+        let magnitude_zero_db = self.processor.magnitude_zero_db();
+        let noise_bandwidth = self.processor.noise_bandwidth();
+        let half_fft_size = self.processor.fft_size() / 2;
+        // The time between two blocks.
+        let elapsed = self.processor.settings().hop_size() as f32 / sample_rate as f32;
+        let mut powers = Vec::with_capacity(half_fft_size);
+        let mut input = samples;
+
+        if self.levels.is_empty() {
+            self.levels.resize(DISPLAY_POINTS, MIN_DB);
+            self.peaks.resize(DISPLAY_POINTS, MIN_DB);
+            self.peak_ages.resize(DISPLAY_POINTS, 0.0);
+        }
+
+        // With overlap there are several blocks per buffer.
+        while let Some(results) = self.processor.process(input) {
+            input = &[];
+            powers.clear();
+            powers.extend(results.take(half_fft_size).map(|result| {
+                let amplitude = result.norm() / magnitude_zero_db;
+
+                amplitude * amplitude
+            }));
+
+            for (index, bins) in points.iter().enumerate() {
+                let band = &powers[bins.clone()];
+                // The power of the band, a sine keeps its level whatever the width of the band.
+                let power = (band.iter().sum::<f32>() / noise_bandwidth)
+                    .max(band.iter().copied().fold(0.0, f32::max));
+                let db = (10.0 * power.log10()).max(MIN_DB);
+
+                self.levels[index] = ballistics(self.levels[index], db, elapsed);
+
+                if self.levels[index] >= self.peaks[index] {
+                    self.peaks[index] = self.levels[index];
+                    self.peak_ages[index] = 0.0;
+                } else {
+                    self.peak_ages[index] += elapsed;
+
+                    if self.peak_ages[index] > PEAK_HOLD {
+                        self.peaks[index] = (self.peaks[index] - PEAK_FALL_DB_PER_SECOND * elapsed)
+                            .max(self.levels[index]);
+                    }
+                }
+            }
+        }
+    }
+}
 
 pub struct Spectrum {
-    processor: FftProcessor<2048>,
+    settings: FftSettings,
+    mode: Mode,
+    /// One trace per signal of the mode.
+    traces: Vec<Trace>,
     sample_rate: usize,
-    display_buffer: Vec<f32>,
+    /// The bins of the band of each display point.
+    points: Vec<Range<usize>>,
+    max_frequency: f32,
+    /// The levels displayed, in dB below 0 dB.
+    range_db: f32,
 }
 
 impl Spectrum {
-    pub fn new() -> Self {
+    /// The FFT size is configurable, bigger FFT causes slower updates.
+    /// 2048 gives good results, there are enough bins, and it's not too slow.
+    /// The priority here is the visual result.
+    pub fn new(settings: FftSettings, range_db: f32) -> Self {
+        let mode = Mode::default();
+
         Self {
-            processor: FftProcessor::new(),
+            settings,
+            mode,
+            traces: new_traces(mode, settings),
             sample_rate: 0,
-            display_buffer: Vec::with_capacity(FFT_SIZE),
+            points: Vec::new(),
+            max_frequency: MAX_FREQ,
+            range_db,
         }
     }
 
+    pub fn set_fft_settings(&mut self, settings: FftSettings) {
+        if settings != self.settings {
+            self.settings = settings;
+            self.traces = new_traces(self.mode, settings);
+            self.update_points();
+        }
+    }
+
+    pub fn set_range_db(&mut self, range_db: f32) {
+        self.range_db = range_db;
+    }
+
     pub fn update(&mut self, message: Message) {
         match message {
-            Message::Buffer(buffer) => {
-                if buffer.is_empty() {
-                    self.display_buffer.clear();
+            Message::Buffer(channels) => {
+                let signals = self.mode.signals(&channels);
+
+                if signals.iter().all(Vec::is_empty) {
+                    self.traces.iter_mut().for_each(Trace::clear);
+                    return;
+                }
+
+                if self.points.is_empty() {
                     return;
                 }
 
-                self.process_buffer(buffer);
+                for (trace, signal) in self.traces.iter_mut().zip(signals) {
+                    trace.process(&signal, &self.points, self.sample_rate);
+                }
             }
             Message::SampleRateChanged(sample_rate) => {
                 self.sample_rate = sample_rate;
-                self.processor.reset();
+                self.traces = new_traces(self.mode, self.settings);
+                self.update_points();
+            }
+            Message::ModeSelected(mode) => {
+                self.mode = mode;
+                self.traces = new_traces(mode, self.settings);
             }
         }
     }
 
-    fn process_buffer(&mut self, buffer: Arc<Vec<f32>>) {
-        let bin_resolution = self.sample_rate as f32 / self.processor.fft_size() as f32;
+    /// The bins of each point depend on the sample rate and on the FFT size.
+    fn update_points(&mut self) {
+        self.points.clear();
 
-        if let Some(results) = self.processor.process(&buffer) {
-            self.display_buffer.clear();
+        if self.sample_rate == 0 {
+            return;
+        }
 
-            for (index, result) in results.take(FFT_SIZE / 2).enumerate() {
-                let frequency = bin_resolution * index as f32;
+        let fft_size = self.settings.size;
+        let half_fft_size = fft_size / 2;
+        let bin_resolution = self.sample_rate as f32 / fft_size as f32;
+        let half_band = 2f32.powf(1.0 / (2.0 * SMOOTHING));
 
-                if (MIN_FREQ..=MAX_FREQ).contains(&frequency) {
-                    let magnitude = (result.re * result.re + result.im * result.im).sqrt();
-                    let amplitude = magnitude / MAGNITUDE_ZERO_DB;
-                    let db = 20.0 * (amplitude.max(f32::EPSILON)).log10();
-                    let normalized = ((db + 60.0f32) / 60.0f32).clamp(0.0f32, 1.0f32);
+        self.max_frequency = MAX_FREQ.min(self.sample_rate as f32 / 2.0);
+        self.points.extend((0..DISPLAY_POINTS).map(|point| {
+            let frequency = point_frequency(point, self.max_frequency);
+            let first = ((frequency / half_band) / bin_resolution).ceil() as usize;
+            let last = ((frequency * half_band) / bin_resolution).floor() as usize;
 
-                    self.display_buffer.push(normalized);
-                }
+            if first <= last {
+                first.max(1)..(last + 1).min(half_fft_size)
+            } else {
+                // The band is narrower than a bin, the nearest bin is used.
+                let nearest =
+                    ((frequency / bin_resolution).round() as usize).clamp(1, half_fft_size - 1);
+
+                nearest..nearest + 1
             }
-        }
+        }));
+    }
+
+    /// The height of a level, 0 at the bottom of the range and 1 at 0 dB.
+    fn normalize(&self, db: f32) -> f32 {
+        ((db + self.range_db) / self.range_db).clamp(0.0, 1.0)
     }
 
     pub fn view(&self) -> Element<crate::Message> {
-        canvas(self).width(Length::Fill).height(Length::Fill).into()
+        stack![
+            canvas(self).width(Length::Fill).height(Length::Fill),
+            container(
+                pick_list(Mode::ALL, Some(self.mode), |mode| {
+                    crate::Message::Spectrum(Message::ModeSelected(mode))
+                })
+                .text_size(12),
+            )
+            .align_right(Length::Fill)
+            .padding(4),
+        ]
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
     }
 }
 
+fn new_traces(mode: Mode, settings: FftSettings) -> Vec<Trace> {
+    mode.trace_names()
+        .iter()
+        .map(|_| Trace::new(settings))
+        .collect()
+}
+
+/// The frequency of a display point, on a logarithmic scale.
+fn point_frequency(point: usize, max_frequency: f32) -> f32 {
+    MIN_FREQ * (max_frequency / MIN_FREQ).powf(point as f32 / (DISPLAY_POINTS - 1) as f32)
+}
+
+/// Move the level towards the new level, quickly when it rises and slowly when it falls.
+fn ballistics(level: f32, new_level: f32, elapsed: f32) -> f32 {
+    let time_constant = if new_level > level { ATTACK } else { RELEASE };
+    let coefficient = 1.0 - (-elapsed / time_constant).exp();
+
+    level + (new_level - level) * coefficient
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
-    Buffer(Arc<Vec<f32>>),
+    /// The samples of each channel.
+    Buffer(Arc<Vec<Vec<f32>>>),
     SampleRateChanged(usize),
+    ModeSelected(Mode),
 }
 
 impl canvas::Program<crate::Message> for Spectrum {
@@ -96,19 +339,115 @@ impl canvas::Program<crate::Message> for Spectrum {
         _cursor: mouse::Cursor,
     ) -> Vec<canvas::Geometry<Renderer>> {
         let mut frame = Frame::new(renderer, bounds.size());
-        let bin_count = self.display_buffer.len() / 2;
-        let bin_width = frame.width() / bin_count as f32;
+        let width = frame.width();
+        let height = frame.height();
+        let palette = theme.extended_palette();
+        let grid_color = palette.background.strong.color;
+        let label_color = palette.secondary.base.color;
+        let label = |content: String, position: Point, color: Color| canvas::Text {
+            content,
+            position,
+            color,
+            size: 10f32.into(),
+            ..Default::default()
+        };
+
+        // Frequency grid
+        for frequency in ui::FREQUENCY_GRID {
+            if frequency >= self.max_frequency {
+                continue;
+            }
+
+            let x = width * ui::frequency_position(frequency, MIN_FREQ, self.max_frequency);
+
+            frame.fill_rectangle(Point::new(x, 0.0), Size::new(1.0, height), grid_color);
+            frame.fill_text(label(
+                ui::format_frequency(frequency),
+                Point::new(x + 2.0, height - 12.0),
+                label_color,
+            ));
+        }
+
+        // Level grid
+        let step = if self.range_db <= 60.0 { 10 } else { 20 };
 
-        for (bin_index, amplitude) in self.display_buffer.iter().enumerate() {
-            let bin_height = amplitude * frame.height();
-            let bin_left = bin_index as f32 * bin_width;
-            let bin_top = frame.height() - bin_height;
+        for db in (step..self.range_db as usize).step_by(step) {
+            let y = height * (1.0 - self.normalize(-(db as f32)));
 
-            frame.fill_rectangle(
-                Point::new(bin_left, bin_top),
-                Size::new(bin_width, bin_height),
-                ui::main_color(theme),
-            );
+            frame.fill_rectangle(Point::new(0.0, y), Size::new(width, 1.0), grid_color);
+            frame.fill_text(label(
+                format!("-{}", db),
+                Point::new(2.0, y + 1.0),
+                label_color,
+            ));
+        }
+
+        let x = |index: usize| index as f32 * width / (DISPLAY_POINTS - 1) as f32;
+        let y = |db: f32| height * (1.0 - self.normalize(db));
+        let line = |levels: &[f32]| {
+            Path::new(|p| {
+                for (index, level) in levels.iter().enumerate() {
+                    let point = Point::new(x(index), y(*level));
+
+                    if index == 0 {
+                        p.move_to(point);
+                    } else {
+                        p.line_to(point);
+                    }
+                }
+            })
+        };
+
+        match self.traces.as_slice() {
+            // A single trace is filled.
+            [trace] => {
+                if !trace.levels.is_empty() {
+                    let area = Path::new(|p| {
+                        p.move_to(Point::new(0.0, height));
+
+                        for (index, level) in trace.levels.iter().enumerate() {
+                            p.line_to(Point::new(x(index), y(*level)));
+                        }
+
+                        p.line_to(Point::new(width, height));
+                        p.close();
+                    });
+
+                    frame.fill(&area, ui::main_color(theme));
+                    frame.stroke(
+                        &line(&trace.peaks),
+                        Stroke::default().with_color(label_color),
+                    );
+                }
+            }
+            // Overlaid traces are lines, with their name in the same color.
+            traces => {
+                let colors = [palette.primary.base.color, palette.success.base.color];
+
+                for (index, (trace, name)) in traces.iter().zip(self.mode.trace_names()).enumerate()
+                {
+                    let color = colors[index % colors.len()];
+
+                    frame.fill_text(label(
+                        name.to_string(),
+                        Point::new(30.0 + 12.0 * index as f32, 4.0),
+                        color,
+                    ));
+
+                    if trace.levels.is_empty() {
+                        continue;
+                    }
+
+                    frame.stroke(
+                        &line(&trace.levels),
+                        Stroke::default().with_color(color).with_width(1.5),
+                    );
+                    frame.stroke(
+                        &line(&trace.peaks),
+                        Stroke::default().with_color(color.scale_alpha(0.5)),
+                    );
+                }
+            }
         }
 
         let path = Path::line(Point::ORIGIN, Point::new(frame.width(), 0.0));
@@ -119,3 +458,141 @@ impl canvas::Program<crate::Message> for Spectrum {
         vec![frame.into_geometry()]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{point_frequency, Message, Mode, Spectrum, DISPLAY_POINTS, MIN_DB, PEAK_HOLD};
+    use crate::fft_processor::{FftSettings, WindowFunction};
+
+    const SAMPLE_RATE: usize = 44100;
+    const FFT_SIZE: usize = 2048;
+
+    fn spectrum() -> Spectrum {
+        let mut spectrum = Spectrum::new(
+            FftSettings {
+                size: FFT_SIZE,
+                window: WindowFunction::Hann,
+                overlap: 0,
+            },
+            60.0,
+        );
+
+        spectrum.update(Message::SampleRateChanged(SAMPLE_RATE));
+        spectrum
+    }
+
+    fn sine(frequency: f32, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|i| (i as f32 / SAMPLE_RATE as f32 * frequency * 2.0 * std::f32::consts::PI).sin())
+            .collect()
+    }
+
+    fn buffer(channels: Vec<Vec<f32>>) -> Message {
+        Message::Buffer(Arc::new(channels))
+    }
+
+    fn loudest_point(levels: &[f32]) -> usize {
+        (0..levels.len())
+            .max_by(|left, right| levels[*left].total_cmp(&levels[*right]))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sine_on_logarithmic_axis() {
+        let mut spectrum = spectrum();
+
+        // Above half the maximum frequency, it was not displayed with a linear axis.
+        for _ in 0..20 {
+            spectrum.update(buffer(vec![sine(15000.0, FFT_SIZE)]));
+        }
+
+        let levels = &spectrum.traces[0].levels;
+        let loudest = loudest_point(levels);
+
+        assert_eq!(levels.len(), DISPLAY_POINTS);
+        assert!((point_frequency(loudest, spectrum.max_frequency) / 15000.0 - 1.0).abs() < 0.05);
+        assert!(levels[loudest] > -12.0);
+        assert!(levels[0] < -60.0);
+    }
+
+    #[test]
+    fn test_release_and_peak_hold() {
+        let mut spectrum = spectrum();
+
+        for _ in 0..20 {
+            spectrum.update(buffer(vec![sine(1000.0, FFT_SIZE)]));
+        }
+
+        let loudest = loudest_point(&spectrum.traces[0].levels);
+        let level = spectrum.traces[0].levels[loudest];
+
+        // A block of silence is shorter than the release and the peak hold.
+        spectrum.update(buffer(vec![vec![0.0; FFT_SIZE]]));
+
+        let trace = &spectrum.traces[0];
+
+        assert!(trace.levels[loudest] < level);
+        assert!(trace.levels[loudest] > MIN_DB);
+        assert_eq!(trace.peaks[loudest], level);
+
+        let blocks = (PEAK_HOLD * SAMPLE_RATE as f32 / FFT_SIZE as f32) as usize + 2;
+
+        for _ in 0..blocks {
+            spectrum.update(buffer(vec![vec![0.0; FFT_SIZE]]));
+        }
+
+        let trace = &spectrum.traces[0];
+
+        assert!(trace.peaks[loudest] < level);
+        assert!(trace.peaks[loudest] >= trace.levels[loudest]);
+    }
+
+    #[test]
+    fn test_empty_buffer_clears() {
+        let mut spectrum = spectrum();
+
+        spectrum.update(buffer(vec![sine(1000.0, FFT_SIZE)]));
+        spectrum.update(buffer(Vec::new()));
+
+        assert!(spectrum.traces[0].levels.is_empty());
+    }
+
+    #[test]
+    fn test_left_right() {
+        let mut spectrum = spectrum();
+
+        spectrum.update(Message::ModeSelected(Mode::LeftRight));
+
+        for _ in 0..20 {
+            spectrum.update(buffer(vec![sine(1000.0, FFT_SIZE), vec![0.0; FFT_SIZE]]));
+        }
+
+        let left = &spectrum.traces[0].levels;
+        let right = &spectrum.traces[1].levels;
+
+        assert!(left[loudest_point(left)] > -12.0);
+        assert!(right.iter().all(|level| *level < -100.0));
+    }
+
+    #[test]
+    fn test_mid_side() {
+        let mut spectrum = spectrum();
+
+        spectrum.update(Message::ModeSelected(Mode::MidSide));
+
+        // The same signal on both channels has no side.
+        for _ in 0..20 {
+            let signal = sine(1000.0, FFT_SIZE);
+
+            spectrum.update(buffer(vec![signal.clone(), signal]));
+        }
+
+        let mid = &spectrum.traces[0].levels;
+        let side = &spectrum.traces[1].levels;
+
+        assert!(mid[loudest_point(mid)] > -12.0);
+        assert!(side.iter().all(|level| *level < -100.0));
+    }
+}
@@ -0,0 +1,412 @@
+//! Stars and tags given to files, saved in a file next to the configuration file.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+
+use iced::{
+    widget::{button, column, row, text, text_input},
+    Element, Task,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config, ui};
+
+const TAGS_FILE_NAME: &str = "tags.toml";
+
+static TAGS_INPUT: LazyLock<text_input::Id> = LazyLock::new(|| text_input::Id::new("tags"));
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum TagsError {
+    #[error("Failed to read tags file '{}': {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: Arc<std::io::Error>,
+    },
+    #[error("Invalid tags file '{}': {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Failed to serialize the tags: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Failed to write tags file '{}': {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: Arc<std::io::Error>,
+    },
+}
+
+/// The content of the tags file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagsFile {
+    starred: BTreeSet<PathBuf>,
+    tags: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Loaded(Result<TagsFile, TagsError>),
+    ToggleStar(PathBuf),
+    /// Open a dialog to edit the tags of the path.
+    Edit(PathBuf),
+    EditChanged(String),
+    EditSubmitted,
+    Cancel,
+    Saved(Result<(), TagsError>),
+}
+
+#[derive(Default)]
+pub struct Tags {
+    file: TagsFile,
+    /// The path whose tags are edited, with the tags typed, separated by commas.
+    editing: Option<(PathBuf, String)>,
+    /// A save is in progress, the next one waits for it so the saves are not reordered.
+    saving: bool,
+    /// The tags changed during a save.
+    modified: bool,
+}
+
+impl Tags {
+    pub fn update(&mut self, message: Message) -> Task<crate::Message> {
+        match message {
+            Message::Loaded(Ok(file)) => {
+                self.file = file;
+            }
+            Message::Loaded(Err(error)) => {
+                log::error!("{}", error);
+            }
+            Message::ToggleStar(path) => {
+                if !self.file.starred.remove(&path) {
+                    self.file.starred.insert(path);
+                }
+
+                return self.save();
+            }
+            Message::Edit(path) => {
+                let tags = self.tags(&path).collect::<Vec<_>>().join(", ");
+
+                self.editing = Some((path, tags));
+                return Task::batch([
+                    text_input::focus(TAGS_INPUT.clone()),
+                    text_input::select_all(TAGS_INPUT.clone()),
+                ]);
+            }
+            Message::EditChanged(tags) => {
+                if let Some((_, edited)) = self.editing.as_mut() {
+                    *edited = tags;
+                }
+            }
+            Message::EditSubmitted => {
+                if let Some((path, tags)) = self.editing.take() {
+                    let tags = parse_tags(&tags);
+
+                    if tags.is_empty() {
+                        self.file.tags.remove(&path);
+                    } else {
+                        self.file.tags.insert(path, tags);
+                    }
+
+                    return self.save();
+                }
+            }
+            Message::Cancel => {
+                self.editing = None;
+            }
+            Message::Saved(result) => {
+                self.saving = false;
+
+                if let Err(error) = result {
+                    log::error!("{}", error);
+                }
+
+                if std::mem::take(&mut self.modified) {
+                    return self.save();
+                }
+            }
+        }
+
+        Task::none()
+    }
+
+    pub fn is_starred(&self, path: &Path) -> bool {
+        self.file.starred.contains(path)
+    }
+
+    pub fn tags(&self, path: &Path) -> impl Iterator<Item = &str> {
+        self.file
+            .tags
+            .get(path)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// The name of a file or directory as displayed, after a star when it is starred and
+    /// followed by its tags.
+    pub fn label(&self, path: &Path, name: impl Display) -> String {
+        let star = if self.is_starred(path) { "★ " } else { "" };
+        let tags = self.tags(path).collect::<Vec<_>>();
+
+        if tags.is_empty() {
+            format!("{star}{name}")
+        } else {
+            format!("{star}{name}  ({})", tags.join(", "))
+        }
+    }
+
+    /// The stars and tags of the path and of the paths under it follow a rename or a move.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Task<crate::Message> {
+        let renamed = |path: &Path| {
+            path.strip_prefix(from).ok().map(|relative| {
+                if relative.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(relative)
+                }
+            })
+        };
+        let starred = self
+            .file
+            .starred
+            .iter()
+            .filter_map(|path| Some((path.clone(), renamed(path)?)))
+            .collect::<Vec<_>>();
+        let tagged = self
+            .file
+            .tags
+            .keys()
+            .filter_map(|path| Some((path.clone(), renamed(path)?)))
+            .collect::<Vec<_>>();
+
+        if starred.is_empty() && tagged.is_empty() {
+            return Task::none();
+        }
+
+        for (from, to) in starred {
+            self.file.starred.remove(&from);
+            self.file.starred.insert(to);
+        }
+
+        for (from, to) in tagged {
+            if let Some(tags) = self.file.tags.remove(&from) {
+                self.file.tags.insert(to, tags);
+            }
+        }
+
+        self.save()
+    }
+
+    /// The tags of a path are edited in a modal dialog.
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Show the dialog editing the tags over the content.
+    pub fn view<'a>(&'a self, content: Element<'a, crate::Message>) -> Element<'a, crate::Message> {
+        let Some((path, tags)) = self.editing.as_ref() else {
+            return content;
+        };
+        let dialog = column![
+            text(format!(
+                "Tags of '{}', separated by commas",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            )),
+            text_input("Tags", tags)
+                .id(TAGS_INPUT.clone())
+                .on_input(|tags| crate::Message::Tags(Message::EditChanged(tags)))
+                .on_submit(crate::Message::Tags(Message::EditSubmitted)),
+            row![
+                button("Cancel").on_press(crate::Message::Tags(Message::Cancel)),
+                button("Save").on_press(crate::Message::Tags(Message::EditSubmitted)),
+            ]
+            .spacing(8),
+        ];
+
+        ui::dialog(content, dialog, crate::Message::Tags(Message::Cancel))
+    }
+
+    fn save(&mut self) -> Task<crate::Message> {
+        if self.saving {
+            self.modified = true;
+            return Task::none();
+        }
+
+        let Some(path) = path() else {
+            return Task::none();
+        };
+
+        self.saving = true;
+        Task::perform(save(path, self.file.clone()), |result| {
+            crate::Message::Tags(Message::Saved(result))
+        })
+    }
+}
+
+/// The context menu items to star and tag a file or directory.
+pub fn menu_items<'a>(path: &Path, is_starred: bool) -> [Element<'a, crate::Message>; 2] {
+    let star = if is_starred { "Remove star" } else { "Star" };
+
+    [
+        ui::menu_item(
+            star,
+            crate::Message::Tags(Message::ToggleStar(path.to_path_buf())),
+        ),
+        ui::menu_item(
+            "Edit tags…",
+            crate::Message::Tags(Message::Edit(path.to_path_buf())),
+        ),
+    ]
+}
+
+/// Path of the tags file, next to the configuration file.
+pub fn path() -> Option<PathBuf> {
+    config::path().map(|path| path.with_file_name(TAGS_FILE_NAME))
+}
+
+/// Load the tags file, there are no stars and no tags without it.
+pub async fn load() -> Result<TagsFile, TagsError> {
+    match path() {
+        Some(path) => load_from(path).await,
+        None => Ok(TagsFile::default()),
+    }
+}
+
+async fn load_from(path: PathBuf) -> Result<TagsFile, TagsError> {
+    match tokio::fs::read_to_string(&path).await {
+        Ok(content) => toml::from_str(&content).map_err(|source| TagsError::Parse { path, source }),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(TagsFile::default()),
+        Err(error) => Err(TagsError::Read {
+            path,
+            source: Arc::new(error),
+        }),
+    }
+}
+
+/// The file is written next to the tags file then renamed, it is never left half written.
+async fn save(path: PathBuf, file: TagsFile) -> Result<(), TagsError> {
+    let content = toml::to_string(&file)?;
+    let temporary = path.with_extension("toml.tmp");
+    let write_error = |path: &Path| {
+        let path = path.to_path_buf();
+
+        move |source| TagsError::Write {
+            path,
+            source: Arc::new(source),
+        }
+    };
+
+    if let Some(directory) = path.parent() {
+        tokio::fs::create_dir_all(directory)
+            .await
+            .map_err(write_error(directory))?;
+    }
+
+    tokio::fs::write(&temporary, content)
+        .await
+        .map_err(write_error(&temporary))?;
+    tokio::fs::rename(&temporary, &path)
+        .await
+        .map_err(write_error(&path))
+}
+
+/// The tags separated by commas, trimmed and without the empty ones.
+fn parse_tags(text: &str) -> BTreeSet<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use temp_dir_builder::TempDirectoryBuilder;
+
+    use super::{load_from, parse_tags, save, Message, Tags, TagsFile};
+
+    /// A store with the tags of the file edited, without saving it.
+    fn tag(tags: &mut Tags, path: &str, text: &str) {
+        let _ = tags.update(Message::Edit(PathBuf::from(path)));
+        let _ = tags.update(Message::EditChanged(text.to_string()));
+        let _ = tags.update(Message::EditSubmitted);
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" kick, 909 ,,kick"),
+            ["909", "kick"].map(String::from).into()
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+
+    #[test]
+    fn test_star_and_tag() {
+        let mut tags = Tags::default();
+        let path = Path::new("/samples/kick.wav");
+
+        let _ = tags.update(Message::ToggleStar(path.to_path_buf()));
+        tag(&mut tags, "/samples/kick.wav", "drums, 909");
+
+        assert!(tags.is_starred(path));
+        assert_eq!(tags.label(path, "kick.wav"), "★ kick.wav  (909, drums)");
+        assert!(!tags.is_editing());
+
+        let _ = tags.update(Message::ToggleStar(path.to_path_buf()));
+        tag(&mut tags, "/samples/kick.wav", "");
+
+        assert_eq!(tags.label(path, "kick.wav"), "kick.wav");
+        assert_eq!(tags.file, TagsFile::default());
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut tags = Tags::default();
+
+        let _ = tags.update(Message::ToggleStar(PathBuf::from("/samples/drums")));
+        tag(&mut tags, "/samples/drums/kick.wav", "909");
+        tag(&mut tags, "/samples/drumsticks.wav", "foley");
+
+        let _ = tags.rename(
+            Path::new("/samples/drums"),
+            Path::new("/samples/percussion"),
+        );
+
+        assert!(tags.is_starred(Path::new("/samples/percussion")));
+        assert!(!tags.is_starred(Path::new("/samples/drums")));
+        assert_eq!(
+            tags.tags(Path::new("/samples/percussion/kick.wav"))
+                .collect::<Vec<_>>(),
+            ["909"]
+        );
+        assert_eq!(
+            tags.tags(Path::new("/samples/drumsticks.wav"))
+                .collect::<Vec<_>>(),
+            ["foley"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let test_dir = TempDirectoryBuilder::default().build().unwrap();
+        let path = test_dir.path().join("sex").join("tags.toml");
+        let mut tags = Tags::default();
+
+        assert_eq!(load_from(path.clone()).await.unwrap(), TagsFile::default());
+
+        let _ = tags.update(Message::ToggleStar(PathBuf::from("/samples/kick.wav")));
+        tag(&mut tags, "/samples/snare.wav", "909, acoustic");
+
+        save(path.clone(), tags.file.clone()).await.unwrap();
+
+        assert_eq!(load_from(path.clone()).await.unwrap(), tags.file);
+        assert!(!path.with_extension("toml.tmp").exists());
+    }
+}
@@ -1,21 +1,86 @@
+use std::ops::Range;
+
 use iced::{
+    advanced::{
+        layout, overlay, renderer,
+        widget::{self, Tree, Widget},
+        Clipboard, Layout, Shell,
+    },
     alignment::Vertical,
-    widget::{canvas::Stroke, container, svg, text::Wrapping, MouseArea, Row},
-    Color, Element, Padding, Theme,
+    keyboard::{self, key::Named},
+    mouse,
+    widget::{
+        button,
+        canvas::Stroke,
+        center, container, mouse_area, opaque,
+        scrollable::Viewport,
+        stack, svg, text,
+        text::{LineHeight, Wrapping},
+        text_input, Column, MouseArea, Row, Space, TextInput,
+    },
+    Color, Element, Event, Length, Padding, Point, Rectangle, Renderer, Size, Theme, Vector,
 };
 
 use crate::{ui, Message};
 
 pub(crate) const ICON_SIZE: u32 = 18;
+/// Height of the rows of a `virtual_list()`.
+pub(crate) const ROW_HEIGHT: f32 = 22.0;
+
+/// The rows built when the viewport of the list is not known yet.
+const DEFAULT_VISIBLE_ROWS: usize = 100;
+/// The rows built above and below the viewport.
+const OVERSCAN_ROWS: usize = 5;
+
+/// The rows of a list that are visible in the viewport of its scrollable.
+pub fn visible_rows(row_count: usize, viewport: Option<&Viewport>) -> Range<usize> {
+    let Some(viewport) = viewport else {
+        return 0..row_count.min(DEFAULT_VISIBLE_ROWS);
+    };
+    let first = (viewport.absolute_offset().y / ROW_HEIGHT) as usize;
+    let visible = (viewport.bounds().height / ROW_HEIGHT).ceil() as usize + OVERSCAN_ROWS;
+    let end = (first + visible).min(row_count);
+    // The viewport can be outdated when the list gets shorter, the last rows are built then.
+    let start = first
+        .saturating_sub(OVERSCAN_ROWS)
+        .min(end.saturating_sub(visible));
+
+    start..end
+}
+
+/// A list to put in a scrollable, only the rows visible in the viewport are built.
+/// The rows have the same height so the space of the hidden rows is reserved, the scrollable
+/// keeps the size of the whole list.
+pub fn virtual_list<'a>(
+    row_count: usize,
+    viewport: Option<&Viewport>,
+    row: impl Fn(usize) -> Element<'a, Message>,
+) -> Column<'a, Message> {
+    let rows = visible_rows(row_count, viewport);
+    let space = |rows: usize| Space::new(Length::Fill, Length::Fixed(rows as f32 * ROW_HEIGHT));
+    let mut column = Column::new().push(space(rows.start));
+
+    for index in rows.clone() {
+        column = column.push(
+            container(row(index))
+                .height(Length::Fixed(ROW_HEIGHT))
+                .align_y(Vertical::Center),
+        );
+    }
+
+    column.push(space(row_count - rows.end))
+}
+
+/// Size of the text of a `file_entry()`.
+const FILE_ENTRY_FONT_SIZE: u32 = 14;
 
 pub fn file_entry<'a>(
     text: impl ToString,
     select_message: Message,
     icon: Option<svg::Handle>,
     selected: bool,
+    dimmed: bool,
 ) -> Element<'a, Message> {
-    const FONT_SIZE: u32 = 14;
-
     let mut row = Row::new();
 
     row = row.push_maybe(icon.map(|handle| {
@@ -34,11 +99,15 @@ pub fn file_entry<'a>(
             left: 0.,
         })
     }));
-    row = row.push(
-        iced::widget::text(text.to_string())
-            .size(FONT_SIZE)
-            .wrapping(Wrapping::None),
-    );
+    let mut label = iced::widget::text(text.to_string())
+        .size(FILE_ENTRY_FONT_SIZE)
+        .wrapping(Wrapping::None);
+
+    if dimmed {
+        label = label.style(dimmed_style);
+    }
+
+    row = row.push(label);
     row = row.align_y(Vertical::Center);
 
     let mut selectable_part = container(row).padding(Padding {
@@ -57,6 +126,391 @@ pub fn file_entry<'a>(
         .into()
 }
 
+/// A text input to edit a `file_entry()` in place, its height fits in a row of a `virtual_list()`.
+pub fn file_entry_input<'a>(placeholder: &str, value: &str) -> TextInput<'a, Message> {
+    const LINE_HEIGHT: f32 = 1.3;
+
+    let font_size = FILE_ENTRY_FONT_SIZE as f32;
+    let vertical_padding = ((ROW_HEIGHT - font_size * LINE_HEIGHT) / 2.0).max(0.0);
+
+    text_input(placeholder, value)
+        .size(font_size)
+        .line_height(LineHeight::Relative(LINE_HEIGHT))
+        .padding(Padding {
+            top: vertical_padding,
+            right: 4.,
+            bottom: vertical_padding,
+            left: 4.,
+        })
+}
+
+/// A clickable label that is highlighted when active.
+pub fn toggle<'a>(label: impl ToString, active: bool, message: Message) -> Element<'a, Message> {
+    const FONT_SIZE: u32 = 12;
+
+    let mut content =
+        container(iced::widget::text(label.to_string()).size(FONT_SIZE)).padding(Padding {
+            top: 2.,
+            right: 4.,
+            bottom: 2.,
+            left: 4.,
+        });
+
+    if active {
+        content = content.style(selected_style);
+    }
+
+    MouseArea::new(content).on_press(message).into()
+}
+
+/// Show a menu at the cursor position when the content is right-clicked.
+/// The items are only built when the menu is open, see `menu_item()`.
+pub fn context_menu<'a>(
+    content: impl Into<Element<'a, Message>>,
+    items: impl Fn() -> Vec<Element<'a, Message>> + 'a,
+) -> Element<'a, Message> {
+    Element::new(ContextMenu {
+        content: content.into(),
+        items: Box::new(items),
+    })
+}
+
+/// An entry of a context menu, the menu is closed when it is clicked.
+pub fn menu_item<'a>(label: impl ToString, message: Message) -> Element<'a, Message> {
+    const FONT_SIZE: u32 = 14;
+
+    button(iced::widget::text(label.to_string()).size(FONT_SIZE))
+        .style(button::text)
+        .padding(Padding::from([2.0, 8.0]))
+        .width(Length::Fill)
+        .on_press(message)
+        .into()
+}
+
+/// A modal dialog over the content, `dismiss` is sent when the user clicks outside of it.
+pub fn dialog<'a>(
+    content: Element<'a, Message>,
+    dialog: Column<'a, Message>,
+    dismiss: Message,
+) -> Element<'a, Message> {
+    let dialog = container(dialog.spacing(16))
+        .padding(16)
+        .style(container::bordered_box);
+
+    stack![
+        content,
+        opaque(
+            mouse_area(center(opaque(dialog)).style(|_theme| container::Style {
+                background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.5).into()),
+                ..Default::default()
+            }))
+            .on_press(dismiss)
+        ),
+    ]
+    .into()
+}
+
+struct ContextMenu<'a> {
+    content: Element<'a, Message>,
+    items: Box<dyn Fn() -> Vec<Element<'a, Message>> + 'a>,
+}
+
+#[derive(Default)]
+struct ContextMenuState {
+    /// Where the menu was opened, relative to the content.
+    open_at: Option<Point>,
+    /// The state of the menu widgets, while the menu is open.
+    menu: Option<Tree>,
+}
+
+impl ContextMenuState {
+    fn close(&mut self) {
+        self.open_at = None;
+        self.menu = None;
+    }
+}
+
+impl<'a> ContextMenu<'a> {
+    const WIDTH: f32 = 180.0;
+
+    fn menu(&self) -> Element<'a, Message> {
+        container(Column::with_children((self.items)()).width(Self::WIDTH))
+            .padding(4)
+            .style(container::bordered_box)
+            .into()
+    }
+}
+
+impl Widget<Message, Theme, Renderer> for ContextMenu<'_> {
+    fn tag(&self) -> widget::tree::Tag {
+        widget::tree::Tag::of::<ContextMenuState>()
+    }
+
+    fn state(&self) -> widget::tree::State {
+        widget::tree::State::new(ContextMenuState::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &mut self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.content
+            .as_widget_mut()
+            .layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            viewport,
+        );
+    }
+
+    fn operate(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation,
+    ) {
+        self.content
+            .as_widget_mut()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget_mut().update(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+
+        if shell.is_event_captured() {
+            return;
+        }
+
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event {
+            if let Some(position) = cursor.position_over(layout.bounds()) {
+                let state = tree.state.downcast_mut::<ContextMenuState>();
+
+                state.open_at = Some(position);
+                state.menu = None;
+                shell.capture_event();
+                shell.invalidate_layout();
+                shell.request_redraw();
+            }
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'b>,
+        renderer: &Renderer,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        let state = tree.state.downcast_mut::<ContextMenuState>();
+        let menu = state.open_at.map(|open_at| (open_at, self.menu()));
+        let content = self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout,
+            renderer,
+            viewport,
+            translation,
+        );
+        let menu = menu.map(|(open_at, menu)| {
+            match state.menu.as_mut() {
+                Some(menu_tree) => menu_tree.diff(menu.as_widget()),
+                None => state.menu = Some(Tree::new(menu.as_widget())),
+            }
+
+            overlay::Element::new(Box::new(MenuOverlay {
+                position: open_at + translation,
+                menu,
+                state,
+            }))
+        });
+
+        if content.is_some() || menu.is_some() {
+            Some(overlay::Group::with_children(content.into_iter().chain(menu).collect()).overlay())
+        } else {
+            None
+        }
+    }
+}
+
+struct MenuOverlay<'a, 'b> {
+    position: Point,
+    menu: Element<'a, Message>,
+    state: &'b mut ContextMenuState,
+}
+
+impl overlay::Overlay<Message, Theme, Renderer> for MenuOverlay<'_, '_> {
+    fn layout(&mut self, renderer: &Renderer, bounds: Size) -> layout::Node {
+        let tree = self.state.menu.as_mut().expect("the menu is open");
+        let node = self.menu.as_widget_mut().layout(
+            tree,
+            renderer,
+            &layout::Limits::new(Size::ZERO, bounds),
+        );
+        let size = node.size();
+
+        // Keep the menu inside the window.
+        node.move_to(Point::new(
+            self.position.x.min(bounds.width - size.width).max(0.0),
+            self.position.y.min(bounds.height - size.height).max(0.0),
+        ))
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+    ) {
+        let tree = self.state.menu.as_ref().expect("the menu is open");
+
+        self.menu.as_widget().draw(
+            tree,
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            &layout.bounds(),
+        );
+    }
+
+    fn update(
+        &mut self,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        let bounds = layout.bounds();
+        let close = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(_)) => !cursor.is_over(bounds),
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key: keyboard::Key::Named(Named::Escape),
+                ..
+            }) => true,
+            _ => false,
+        };
+
+        if close {
+            self.state.close();
+            shell.capture_event();
+            shell.invalidate_layout();
+            shell.request_redraw();
+            return;
+        }
+
+        let tree = self.state.menu.as_mut().expect("the menu is open");
+
+        self.menu.as_widget_mut().update(
+            tree, event, layout, cursor, renderer, clipboard, shell, &bounds,
+        );
+
+        // The items publish their message when the button is released.
+        if let Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) = event {
+            if cursor.is_over(bounds) {
+                self.state.close();
+                shell.invalidate_layout();
+                shell.request_redraw();
+            }
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let tree = self.state.menu.as_ref().expect("the menu is open");
+
+        self.menu
+            .as_widget()
+            .mouse_interaction(tree, layout, cursor, &layout.bounds(), renderer)
+    }
+}
+
+fn dimmed_style(theme: &Theme) -> text::Style {
+    text::Style {
+        color: Some(theme.extended_palette().background.strong.color),
+    }
+}
+
 fn selected_style(theme: &Theme) -> container::Style {
     container::Style {
         background: Some(iced::Background::Color(
@@ -76,3 +530,22 @@ pub fn separation_line_stroke(theme: &Theme) -> Stroke<'_> {
 pub fn main_color(theme: &Theme) -> Color {
     theme.extended_palette().primary.weak.color
 }
+
+/// The frequencies labelled on the axis of the spectrum and of the spectrogram.
+pub const FREQUENCY_GRID: [f32; 9] = [
+    50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0,
+];
+
+/// The position of a frequency on a logarithmic axis, 0 for `min` and 1 for `max`.
+pub fn frequency_position(frequency: f32, min: f32, max: f32) -> f32 {
+    (frequency / min).ln() / (max / min).ln()
+}
+
+/// A frequency of the grid, in Hz or kHz: "500" or "2k".
+pub fn format_frequency(frequency: f32) -> String {
+    if frequency >= 1000.0 {
+        format!("{}k", frequency / 1000.0)
+    } else {
+        format!("{}", frequency)
+    }
+}
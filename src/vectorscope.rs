@@ -84,7 +84,10 @@ impl canvas::Program<crate::Message> for Vectorscope {
 
 #[cfg(test)]
 mod tests {
-    use crate::{tests::simulator, SEx};
+    use crate::{
+        tests::{simulator, snapshot_path},
+        SEx,
+    };
 
     #[test]
     fn test_vectorscope() -> Result<(), iced_test::Error> {
@@ -98,7 +101,7 @@ mod tests {
         let mut ui = simulator(&app);
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_vectorscope")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_vectorscope"))?);
 
         Ok(())
     }
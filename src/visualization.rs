@@ -23,23 +23,29 @@ impl Visualization {
     pub fn update(&mut self, message: Message) -> Task<crate::Message> {
         match message {
             Message::AudioBuffer(channels, samples) => {
-                let rms = Self::compute_rms(channels, &samples);
                 let points = Self::vectorscope(channels, &samples);
                 let mono = Arc::new(Self::mono(channels, &samples));
+                let channel_buffers = Arc::new(Self::channels(channels, &samples));
 
                 Task::batch([
-                    Task::done(crate::Message::VuMeter(vu_meter::Message::Rms(rms))),
+                    Task::done(crate::Message::VuMeter(vu_meter::Message::Buffer(
+                        channels,
+                        Arc::new(samples),
+                    ))),
                     Task::done(crate::Message::Vectorscope(vectorscope::Message::Points(
                         points,
                     ))),
                     Task::done(crate::Message::Scope(scope::Message::Buffer(mono.clone()))),
                     Task::done(crate::Message::Spectrum(spectrum::Message::Buffer(
-                        mono.clone(),
+                        channel_buffers,
                     ))),
                     Task::done(crate::Message::Tuner(tuner::Message::Buffer(mono))),
                 ])
             }
             Message::SampleRateChanged(sample_rate) => Task::batch([
+                Task::done(crate::Message::VuMeter(
+                    vu_meter::Message::SampleRateChanged(sample_rate),
+                )),
                 Task::done(crate::Message::Spectrum(
                     spectrum::Message::SampleRateChanged(sample_rate),
                 )),
@@ -47,33 +53,15 @@ impl Visualization {
                     sample_rate,
                 ))),
             ]),
-            Message::SampleSelectionChanged => Task::batch([Task::done(crate::Message::Tuner(
-                tuner::Message::SampleSelectionChanged,
-            ))]),
-        }
-    }
-
-    fn compute_rms(channels: ChannelCount, buffer: &[f32]) -> Vec<f32> {
-        let channels = channels as usize;
-
-        if buffer.is_empty() || channels == 0 {
-            return Vec::new();
-        }
-
-        let mut rms_per_channels = vec![0f32; channels];
-
-        for (i, sample) in buffer.iter().enumerate() {
-            rms_per_channels[i % channels] += sample * sample;
-        }
-
-        let frames_count = (buffer.len() / channels) as f32;
-
-        for rms in rms_per_channels.iter_mut().take(channels) {
-            *rms /= frames_count;
-            *rms = rms.sqrt();
+            Message::SampleSelectionChanged => Task::batch([
+                Task::done(crate::Message::VuMeter(
+                    vu_meter::Message::SampleSelectionChanged,
+                )),
+                Task::done(crate::Message::Tuner(
+                    tuner::Message::SampleSelectionChanged,
+                )),
+            ]),
         }
-
-        rms_per_channels
     }
 
     fn vectorscope(channels: ChannelCount, samples: &[f32]) -> Vec<(f32, f32)> {
@@ -104,6 +92,26 @@ impl Visualization {
         result
     }
 
+    /// The samples of each channel.
+    fn channels(channels: ChannelCount, samples: &[f32]) -> Vec<Vec<f32>> {
+        let channels = channels as usize;
+
+        if channels == 0 || samples.is_empty() {
+            return Vec::new();
+        }
+
+        (0..channels)
+            .map(|channel| {
+                samples
+                    .iter()
+                    .skip(channel)
+                    .step_by(channels)
+                    .copied()
+                    .collect()
+            })
+            .collect()
+    }
+
     fn mono(channels: u16, samples: &[f32]) -> Vec<f32> {
         if samples.is_empty() {
             return Vec::new();
@@ -133,16 +141,10 @@ mod tests {
     use super::Visualization;
 
     #[rstest]
+    #[case(1, &[0.1, 0.2], &[&[0.1, 0.2]])]
+    #[case(2, &[0.1, 0.2, 0.3, 0.4], &[&[0.1, 0.3], &[0.2, 0.4]])]
     #[case(2, &[], &[])]
-    #[case(1, &[0.5, 0.6, 0.7], &[0.605_530_1])]
-    #[case(2, &[0.5, 0.6, 0.7, 0.8], &[0.608_276_25, 0.707_106_77])]
-    fn test_compute_rms(#[case] channels: u16, #[case] buffer: &[f32], #[case] rms: &[f32]) {
-        let result = Visualization::compute_rms(channels, buffer);
-
-        assert_eq!(result.len(), rms.len());
-
-        for (result, expected) in result.iter().zip(rms.iter()) {
-            assert!((result - expected).abs() < 0.00000001);
-        }
+    fn test_channels(#[case] channels: u16, #[case] samples: &[f32], #[case] expected: &[&[f32]]) {
+        assert_eq!(Visualization::channels(channels, samples), expected);
     }
 }
@@ -1,52 +1,324 @@
+use std::sync::Arc;
+
+use crate::clipping::ClipDetector;
+use crate::loudness::{FileLoudness, LoudnessMeter, TruePeakMeter};
 use crate::ui;
-use iced::widget::canvas;
+use iced::futures::{channel::mpsc, SinkExt, Stream, StreamExt};
+use iced::widget::{canvas, MouseArea};
 use iced::{
-    mouse, widget::canvas::Cache, Element, Length, Point, Rectangle, Renderer, Size, Theme,
+    mouse, widget::canvas::Cache, Element, Length, Point, Rectangle, Renderer, Size, Subscription,
+    Theme,
 };
+use rodio::ChannelCount;
+
+/// The lowest level displayed, in dB.
+const MIN_DB: f32 = -60.0;
+/// The levels labelled on the scale, in dB.
+const SCALE: [f32; 8] = [0.0, -6.0, -12.0, -18.0, -24.0, -36.0, -48.0, -60.0];
+/// The speed at which the peaks fall, as a digital peak programme meter, in dB per second.
+const PEAK_FALL: f32 = 11.8;
+/// The time during which the highest peak stays displayed, in seconds.
+const PEAK_HOLD: f32 = 2.0;
+const SCALE_WIDTH: f32 = 22.0;
+const LINE_HEIGHT: f32 = 12.0;
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    /// RMS per channel
-    Rms(Vec<f32>),
+    Initialized(mpsc::Sender<MeterCommand>),
+    /// The samples of the channels, interleaved. An empty buffer when the playback stops.
+    Buffer(ChannelCount, Arc<Vec<f32>>),
+    /// The measures after a buffer, the measures of a previous generation are ignored.
+    Measured {
+        measures: Measures,
+        generation: usize,
+    },
+    SampleRateChanged(usize),
+    /// The measures since the start of the file are reset.
+    SampleSelectionChanged,
+    /// The loudness of the whole file, measured when it is loaded.
+    FileLoudness(FileLoudness),
+    /// Turn the clip LED off.
+    ResetClip,
+}
+
+/// The loudness, the true peak and the clips are measured by the subscription, not to
+/// interpolate every sample played on the UI thread.
+pub enum MeterCommand {
+    /// Measure from the start the next buffers, played at this sample rate.
+    Reset {
+        sample_rate: usize,
+        generation: usize,
+    },
+    /// The number of channels and the samples, interleaved.
+    Buffer(usize, Arc<Vec<f32>>),
+}
+
+/// The measures since the file was selected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measures {
+    momentary: Option<f32>,
+    short_term: Option<f32>,
+    integrated: Option<f32>,
+    range: Option<f32>,
+    /// The highest true peak, in dBTP.
+    max_true_peak: f32,
+    /// Whether a sample of the last buffer clipped.
+    clipped: bool,
+}
+
+/// Measures the buffers played, the clips are found with the true peaks of the meter.
+struct Measurer {
+    loudness: LoudnessMeter,
+    true_peak: TruePeakMeter,
+    clip_detector: ClipDetector,
+    /// The highest absolute value between the samples.
+    max_true_peak: f32,
+}
+
+impl Measurer {
+    fn new(channels: usize, sample_rate: usize) -> Self {
+        Self {
+            loudness: LoudnessMeter::new(channels, sample_rate),
+            true_peak: TruePeakMeter::new(channels),
+            clip_detector: ClipDetector::new(channels),
+            max_true_peak: 0.0,
+        }
+    }
+
+    fn channels(&self) -> usize {
+        self.loudness.channels()
+    }
+
+    fn push(&mut self, samples: &[f32]) -> Measures {
+        self.loudness.push(samples);
+
+        for sample in samples {
+            let true_peak = self.true_peak.push_sample(*sample);
+
+            self.max_true_peak = self.max_true_peak.max(true_peak);
+            self.clip_detector.push_sample(*sample, true_peak);
+        }
+
+        Measures {
+            momentary: self.loudness.momentary(),
+            short_term: self.loudness.short_term(),
+            integrated: self.loudness.integrated(),
+            range: self.loudness.range(),
+            max_true_peak: db(self.max_true_peak),
+            clipped: !self.clip_detector.take_clips().is_empty(),
+        }
+    }
+}
+
+struct ChannelLevel {
+    /// The sample peak in dB, falling slowly after each peak.
+    peak: f32,
+    /// The highest recent peak in dB.
+    held_peak: f32,
+    /// The time since the held peak, in seconds.
+    held_time: f32,
+}
+
+impl Default for ChannelLevel {
+    fn default() -> Self {
+        Self {
+            peak: f32::NEG_INFINITY,
+            held_peak: f32::NEG_INFINITY,
+            held_time: 0.0,
+        }
+    }
+}
+
+impl ChannelLevel {
+    fn update(&mut self, peak: f32, elapsed: f32) {
+        self.peak = peak.max(self.peak - PEAK_FALL * elapsed);
+        self.held_time += elapsed;
+
+        if peak >= self.held_peak || self.held_time > PEAK_HOLD {
+            self.held_peak = self.peak;
+            self.held_time = 0.0;
+        }
+    }
 }
 
 pub struct VuMeter {
-    levels_per_channel: Vec<f32>,
+    sample_rate: usize,
+    command_sender: Option<mpsc::Sender<MeterCommand>>,
+    /// Incremented when the measures are reset.
+    current_generation: usize,
+    /// `None` until the first buffer is measured.
+    measures: Option<Measures>,
+    /// Whether a sample clipped since the file was selected or the LED was reset.
+    clipped: bool,
+    levels: Vec<ChannelLevel>,
+    file_loudness: Option<FileLoudness>,
     cache: Cache,
 }
 
+fn db(value: f32) -> f32 {
+    20.0 * value.log10()
+}
+
+fn normalize(db: f32) -> f32 {
+    ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0)
+}
+
+fn format_level(level: Option<f32>) -> String {
+    match level {
+        Some(level) if level.is_finite() => format!("{:.1}", level),
+        _ => "-".to_string(),
+    }
+}
+
 impl VuMeter {
     pub fn new() -> Self {
         Self {
-            levels_per_channel: Vec::with_capacity(2),
+            sample_rate: 0,
+            command_sender: None,
+            current_generation: 0,
+            measures: None,
+            clipped: false,
+            levels: Vec::with_capacity(2),
+            file_loudness: None,
             cache: Cache::new(),
         }
     }
 
+    /// Clicking the meter resets the clip LED.
     pub fn view(&self) -> Element<crate::Message> {
-        canvas(self).width(Length::Fill).height(Length::Fill).into()
+        MouseArea::new(canvas(self).width(Length::Fill).height(Length::Fill))
+            .on_press(crate::Message::VuMeter(Message::ResetClip))
+            .into()
     }
 
     pub fn update(&mut self, message: Message) {
         match message {
-            Message::Rms(rms_per_channel) => {
-                if rms_per_channel.len() != self.levels_per_channel.len() {
-                    self.levels_per_channel.resize(rms_per_channel.len(), 0f32);
-                }
-
-                for (rms, level) in rms_per_channel
-                    .into_iter()
-                    .zip(self.levels_per_channel.iter_mut())
-                {
-                    let db = 20.0 * rms.max(f32::EPSILON).log10();
+            Message::Initialized(command_sender) => {
+                self.command_sender = Some(command_sender);
+                self.reset();
+            }
+            Message::Buffer(channels, samples) => {
+                let channels = channels as usize;
 
-                    *level = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+                if channels == 0 || samples.is_empty() || self.sample_rate == 0 {
+                    self.levels.clear();
+                } else {
+                    self.update_levels(channels, &samples);
+                    self.send_command(MeterCommand::Buffer(channels, samples));
+                }
+            }
+            Message::Measured {
+                measures,
+                generation,
+            } => {
+                if generation == self.current_generation {
+                    self.clipped |= measures.clipped;
+                    self.measures = Some(measures);
+                }
+            }
+            Message::SampleRateChanged(sample_rate) => {
+                if sample_rate != self.sample_rate {
+                    self.sample_rate = sample_rate;
+                    self.reset();
                 }
             }
+            Message::SampleSelectionChanged => {
+                self.reset();
+                self.file_loudness = None;
+            }
+            Message::FileLoudness(loudness) => {
+                self.file_loudness = Some(loudness);
+            }
+            Message::ResetClip => {
+                self.clipped = false;
+            }
         }
 
         self.cache.clear();
     }
+
+    pub fn subscription(&self) -> Subscription<crate::Message> {
+        Subscription::run(measuring).map(crate::Message::VuMeter)
+    }
+
+    fn reset(&mut self) {
+        self.current_generation += 1;
+        self.measures = None;
+        self.clipped = false;
+        self.send_command(MeterCommand::Reset {
+            sample_rate: self.sample_rate,
+            generation: self.current_generation,
+        });
+    }
+
+    fn send_command(&mut self, command: MeterCommand) {
+        if let Some(command_sender) = self.command_sender.as_mut() {
+            // A buffer is not measured if the subscription is late.
+            let _ = command_sender.try_send(command);
+        }
+    }
+
+    /// The sample peaks, cheap enough to follow the buffers played.
+    fn update_levels(&mut self, channels: usize, samples: &[f32]) {
+        if self.levels.len() != channels {
+            self.levels.clear();
+            self.levels.resize_with(channels, ChannelLevel::default);
+        }
+
+        let elapsed = (samples.len() / channels) as f32 / self.sample_rate as f32;
+
+        for (channel, level) in self.levels.iter_mut().enumerate() {
+            let peak = samples
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .fold(0f32, |peak, sample| peak.max(sample.abs()));
+
+            level.update(db(peak), elapsed);
+        }
+    }
+}
+
+fn measuring() -> impl Stream<Item = Message> {
+    iced::stream::channel(8, async move |mut output| {
+        let (command_sender, mut command_receiver) = mpsc::channel::<MeterCommand>(64);
+        let mut sample_rate = 0;
+        let mut generation = 0;
+        let mut measurer: Option<Measurer> = None;
+
+        output
+            .send(Message::Initialized(command_sender))
+            .await
+            .unwrap();
+
+        while let Some(command) = command_receiver.next().await {
+            match command {
+                MeterCommand::Reset {
+                    sample_rate: new_sample_rate,
+                    generation: new_generation,
+                } => {
+                    sample_rate = new_sample_rate;
+                    generation = new_generation;
+                    measurer = None;
+                }
+                MeterCommand::Buffer(channels, samples) => {
+                    if measurer.as_ref().map(Measurer::channels) != Some(channels) {
+                        measurer = Some(Measurer::new(channels, sample_rate));
+                    }
+
+                    if let Some(measurer) = measurer.as_mut() {
+                        output
+                            .send(Message::Measured {
+                                measures: measurer.push(&samples),
+                                generation,
+                            })
+                            .await
+                            .unwrap();
+                    }
+                }
+            }
+        }
+    })
 }
 
 impl canvas::Program<crate::Message> for VuMeter {
@@ -61,17 +333,102 @@ impl canvas::Program<crate::Message> for VuMeter {
         _cursor: mouse::Cursor,
     ) -> Vec<canvas::Geometry<Renderer>> {
         let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
-            let width = frame.width() / self.levels_per_channel.len() as f32;
+            let palette = theme.extended_palette();
+            let grid_color = palette.background.strong.color;
+            let label_color = palette.background.base.text;
+            let label = |content: String, position: Point| canvas::Text {
+                content,
+                position,
+                color: label_color,
+                size: 10f32.into(),
+                ..Default::default()
+            };
 
-            for (i, level) in self.levels_per_channel.iter().enumerate() {
-                let height = level * frame.height();
-                let y = frame.height() - height;
+            // Loudness readouts
+            let measures = self.measures.as_ref();
+            let readouts = [
+                format!("M {}", format_level(measures.and_then(|m| m.momentary))),
+                format!("S {}", format_level(measures.and_then(|m| m.short_term))),
+                format!("I {}", format_level(measures.and_then(|m| m.integrated))),
+                format!("LRA {}", format_level(measures.and_then(|m| m.range))),
+                format!("TP {}", format_level(measures.map(|m| m.max_true_peak))),
+                format!(
+                    "File {}",
+                    format_level(self.file_loudness.and_then(|l| l.integrated))
+                ),
+            ];
+            let led_y = 4.0 + readouts.len() as f32 * LINE_HEIGHT;
+            let top = led_y + 2.0 * LINE_HEIGHT;
 
+            for (index, readout) in readouts.into_iter().enumerate() {
+                frame.fill_text(label(
+                    readout,
+                    Point::new(2.0, 2.0 + index as f32 * LINE_HEIGHT),
+                ));
+            }
+
+            // Clip LED
+            frame.fill_rectangle(
+                Point::new(2.0, led_y),
+                Size::new((frame.width() - 4.0).max(0.0), LINE_HEIGHT),
+                if self.clipped {
+                    palette.danger.base.color
+                } else {
+                    palette.background.weak.color
+                },
+            );
+            frame.fill_text(canvas::Text {
+                color: if self.clipped {
+                    palette.danger.base.text
+                } else {
+                    label_color
+                },
+                ..label("CLIP".to_string(), Point::new(4.0, led_y + 1.0))
+            });
+
+            // Scale
+            let height = (frame.height() - top).max(0.0);
+            let y = |db: f32| top + height * (1.0 - normalize(db));
+
+            for db in SCALE {
                 frame.fill_rectangle(
-                    Point::new(i as f32 * width, y),
-                    Size::new(width, height),
+                    Point::new(SCALE_WIDTH - 4.0, y(db)),
+                    Size::new(frame.width() - SCALE_WIDTH + 4.0, 1.0),
+                    grid_color,
+                );
+                frame.fill_text(label(
+                    format!("{}", db),
+                    Point::new(
+                        2.0,
+                        (y(db) - 6.0).min(frame.height() - LINE_HEIGHT).max(top),
+                    ),
+                ));
+            }
+
+            // Peaks
+            if self.levels.is_empty() {
+                return;
+            }
+
+            let width = (frame.width() - SCALE_WIDTH) / self.levels.len() as f32;
+
+            for (i, level) in self.levels.iter().enumerate() {
+                let x = SCALE_WIDTH + i as f32 * width;
+                let bar_height = normalize(level.peak) * height;
+
+                frame.fill_rectangle(
+                    Point::new(x, top + height - bar_height),
+                    Size::new(width - 1.0, bar_height),
                     ui::main_color(theme),
                 );
+
+                if level.held_peak > MIN_DB {
+                    frame.fill_rectangle(
+                        Point::new(x, y(level.held_peak)),
+                        Size::new(width - 1.0, 2.0),
+                        label_color,
+                    );
+                }
             }
         });
 
@@ -81,20 +438,43 @@ impl canvas::Program<crate::Message> for VuMeter {
 
 #[cfg(test)]
 mod tests {
-    use crate::{tests::simulator, SEx};
+    use std::sync::Arc;
+
+    use crate::{
+        tests::{simulator, snapshot_path},
+        vu_meter, SEx,
+    };
+
+    use super::Measurer;
+
+    /// Play a buffer at these levels, then receive its measures as from the subscription.
+    fn play(app: &mut SEx, levels: &[f32]) {
+        let samples: Vec<f32> = (0..4800).flat_map(|_| levels.iter().copied()).collect();
+        let measures = Measurer::new(levels.len(), 48000).push(&samples);
+
+        let _ = app.update(crate::Message::VuMeter(vu_meter::Message::Buffer(
+            levels.len() as u16,
+            Arc::new(samples),
+        )));
+        let _ = app.update(crate::Message::VuMeter(vu_meter::Message::Measured {
+            measures,
+            generation: app.vu_meter.current_generation,
+        }));
+    }
 
     #[test]
     fn test_vu_meter_mono() -> Result<(), iced_test::Error> {
         let (mut app, _) = SEx::new();
 
-        let _ = app.update(crate::Message::VuMeter(crate::vu_meter::Message::Rms(
-            vec![1.0],
-        )));
+        let _ = app.update(crate::Message::VuMeter(
+            vu_meter::Message::SampleRateChanged(48000),
+        ));
+        play(&mut app, &[1.0]);
         let mut ui = simulator(&app);
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_vu_meter_mono")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_vu_meter_mono"))?);
 
         Ok(())
     }
@@ -103,14 +483,15 @@ mod tests {
     fn test_vu_meter_stereo() -> Result<(), iced_test::Error> {
         let (mut app, _) = SEx::new();
 
-        let _ = app.update(crate::Message::VuMeter(crate::vu_meter::Message::Rms(
-            vec![0.5, 0.9],
-        )));
+        let _ = app.update(crate::Message::VuMeter(
+            vu_meter::Message::SampleRateChanged(48000),
+        ));
+        play(&mut app, &[0.5, 0.9]);
         let mut ui = simulator(&app);
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_vu_meter_stereo")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_vu_meter_stereo"))?);
 
         Ok(())
     }
@@ -119,14 +500,99 @@ mod tests {
     fn test_vu_meter_more_channels() -> Result<(), iced_test::Error> {
         let (mut app, _) = SEx::new();
 
-        let _ = app.update(crate::Message::VuMeter(crate::vu_meter::Message::Rms(
-            vec![0.5, 0.6, 0.7],
-        )));
+        let _ = app.update(crate::Message::VuMeter(
+            vu_meter::Message::SampleRateChanged(48000),
+        ));
+        play(&mut app, &[0.5, 0.6, 0.7]);
+        let mut ui = simulator(&app);
+
+        let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
+
+        assert!(snapshot.matches_hash(snapshot_path("test_vu_meter_more_channels"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_peak_hold() {
+        let mut meter = vu_meter::VuMeter::new();
+
+        meter.update(vu_meter::Message::SampleRateChanged(48000));
+        meter.update(vu_meter::Message::Buffer(1, Arc::new(vec![0.5; 4800])));
+        meter.update(vu_meter::Message::Buffer(1, Arc::new(vec![0.0; 4800])));
+
+        let level = &meter.levels[0];
+
+        // 0.1 s later, the peak has fallen but the held peak stays.
+        assert!((level.peak - (-6.02 - 1.18)).abs() < 0.01);
+        assert!((level.held_peak + 6.02).abs() < 0.01);
+
+        for _ in 0..30 {
+            meter.update(vu_meter::Message::Buffer(1, Arc::new(vec![0.0; 4800])));
+        }
+
+        // The held peak is released after 2 s.
+        assert!(meter.levels[0].held_peak < -20.0);
+
+        meter.update(vu_meter::Message::SampleSelectionChanged);
+
+        assert!(meter.measures.is_none());
+    }
+
+    #[test]
+    fn test_clip_led() {
+        let mut meter = vu_meter::VuMeter::new();
+        let mut measurer = Measurer::new(1, 48000);
+
+        meter.update(vu_meter::Message::SampleRateChanged(48000));
+
+        let generation = meter.current_generation;
+        let mut measure = |meter: &mut vu_meter::VuMeter, level: f32, generation: usize| {
+            meter.update(vu_meter::Message::Measured {
+                measures: measurer.push(&[level; 4800]),
+                generation,
+            });
+        };
+
+        measure(&mut meter, 0.5, generation);
+
+        assert!(!meter.clipped);
+
+        measure(&mut meter, 1.0, generation);
+        measure(&mut meter, 0.5, generation);
+
+        // The LED stays on once the clip is over.
+        assert!(meter.clipped);
+        assert!(meter
+            .measures
+            .as_ref()
+            .is_some_and(|measures| measures.max_true_peak > -0.1));
+
+        meter.update(vu_meter::Message::ResetClip);
+
+        assert!(!meter.clipped);
+
+        // The measures of the file selected before are ignored.
+        meter.update(vu_meter::Message::SampleSelectionChanged);
+        measure(&mut meter, 1.0, generation);
+
+        assert!(!meter.clipped);
+        assert!(meter.measures.is_none());
+    }
+
+    #[test]
+    fn test_vu_meter_clipped() -> Result<(), iced_test::Error> {
+        let (mut app, _) = SEx::new();
+
+        let _ = app.update(crate::Message::VuMeter(
+            vu_meter::Message::SampleRateChanged(48000),
+        ));
+        play(&mut app, &[1.0, 0.5]);
         let mut ui = simulator(&app);
 
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_vu_meter_more_channels")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_vu_meter_clipped"))?);
 
         Ok(())
     }
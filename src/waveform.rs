@@ -1,20 +1,21 @@
 use std::{
-    fs::File,
-    io::BufReader,
+    fs, io,
+    ops::Range,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use iced::widget::canvas;
 use iced::{
     event,
     futures::{channel::mpsc, FutureExt, SinkExt, Stream, StreamExt},
+    keyboard::{self, Modifiers},
     mouse,
     widget::{canvas::Cache, container, MouseArea},
     window, Element, Event, Length, Point, Rectangle, Renderer, Size, Subscription, Task, Theme,
 };
 use log::debug;
-use rodio::{Decoder, Source};
+use rodio::Source;
 
 pub enum WaveformCommand {
     LoadFile {
@@ -39,29 +40,75 @@ pub enum Message {
         samples: Vec<f32>,
         generation: usize,
     },
+    /// Clipped samples or overs between the samples, in frames.
+    ClipsFound {
+        clips: Vec<Range<usize>>,
+        generation: usize,
+    },
+    /// The loudness of the whole file, measured while it is decoded.
+    LoudnessMeasured {
+        loudness: FileLoudness,
+        generation: usize,
+    },
     PlayPosition(f32),
+    /// A click seeks when the button is released, unless the cursor moved to drag the file out.
+    /// With Shift pressed, the cursor selects a region, which is dragged out instead of the file.
     Click,
+    /// The mouse button is released, anywhere in the window, so a selection dragged out of the
+    /// waveform still ends.
+    Released,
     CursorMoved(Point),
+    ModifiersChanged(Modifiers),
     Resized,
     BoundsChanged(Option<Rectangle>),
+    /// The file played is played again when it ends.
+    LoopingChanged(bool),
 }
 
 #[derive(Default)]
 pub struct Waveform {
     waveform_cache: Cache,
     samples: Vec<f32>,
+    /// The clipped frames, drawn in red.
+    clips: Vec<Range<usize>>,
     total_samples: Option<usize>,
     play_position: f32,
     command_sender: Option<mpsc::Sender<WaveformCommand>>,
     current_generation: usize,
     bounds: Option<Rectangle>,
     cursor_position: Option<Point>,
+    path: Option<PathBuf>,
+    drag: DragGesture,
+    /// Where the mouse button was pressed, as a fraction of the width.
+    press_position: Option<f32>,
+    /// The region selected with Shift, as fractions of the width.
+    selection: Option<Range<f32>>,
+    /// The region is being selected from this position.
+    selecting_from: Option<f32>,
+    modifiers: Modifiers,
+    looping: bool,
+}
+
+/// The regions narrower than this, in pixels, are discarded.
+const MIN_SELECTION_WIDTH: f32 = 2.0;
+
+#[derive(thiserror::Error, Debug)]
+enum RegionError {
+    #[error("Failed to decode '{}': {source}", path.display())]
+    Decode {
+        path: PathBuf,
+        source: audio::OpenDecoderError,
+    },
+    #[error("The duration of '{}' is unknown", .0.display())]
+    UnknownDuration(PathBuf),
+    #[error("Failed to write '{}': {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
 }
 
 enum State {
     Idle,
     Decoding {
-        decoder: Box<Decoder<BufReader<File>>>,
+        decoder: Box<AudioDecoder>,
         sample_rate: usize,
         generation: usize,
     },
@@ -69,6 +116,12 @@ enum State {
 
 impl Waveform {
     pub fn show(&mut self, path: impl AsRef<Path>) {
+        if self.path.as_deref() != Some(path.as_ref()) {
+            self.selection = None;
+        }
+
+        self.path = Some(path.as_ref().to_path_buf());
+
         if let Some(sender) = self.command_sender.as_mut() {
             sender.try_send(WaveformCommand::StopLoading).unwrap();
 
@@ -83,13 +136,22 @@ impl Waveform {
         }
     }
 
+    /// The file displayed.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
     pub fn clear(&mut self) {
+        self.path = None;
+        self.selection = None;
+
         if let Some(sender) = self.command_sender.as_mut() {
             self.current_generation += 1;
 
             sender.try_send(WaveformCommand::StopLoading).unwrap();
         }
         self.samples.clear();
+        self.clips.clear();
     }
 
     pub fn update(&mut self, message: Message) -> Task<crate::Message> {
@@ -100,6 +162,7 @@ impl Waveform {
             }
             Message::LoadingStarted(samples_count) => {
                 self.samples.clear();
+                self.clips.clear();
                 self.total_samples = samples_count;
                 self.waveform_cache.clear();
 
@@ -117,8 +180,28 @@ impl Waveform {
                     self.waveform_cache.clear();
                 }
             }
+            Message::ClipsFound {
+                mut clips,
+                generation,
+            } => {
+                if self.current_generation == generation {
+                    self.clips.append(&mut clips);
+                    self.waveform_cache.clear();
+                }
+            }
+            Message::LoudnessMeasured {
+                loudness,
+                generation,
+            } => {
+                if self.current_generation == generation {
+                    return Task::done(crate::Message::VuMeter(vu_meter::Message::FileLoudness(
+                        loudness,
+                    )));
+                }
+            }
             Message::Clear => {
                 self.samples.clear();
+                self.clips.clear();
                 self.waveform_cache.clear();
                 self.total_samples = None;
             }
@@ -126,23 +209,67 @@ impl Waveform {
                 self.play_position = position;
             }
             Message::Click => {
-                if let Some(cursor_position) = self.cursor_position.as_ref() {
-                    if let Some(bounds) = self.bounds.as_ref() {
-                        let position = cursor_position.x / bounds.width;
+                let Some(position) = self.cursor_fraction() else {
+                    return Task::none();
+                };
+
+                if self.modifiers.shift() {
+                    self.selecting_from = Some(position);
+                    self.selection = Some(position..position);
+                } else {
+                    self.drag.press();
+                    self.press_position = Some(position);
+                }
+            }
+            Message::Released => {
+                self.drag.release();
 
-                        return Task::done(crate::Message::Audio(audio::Message::SetPosition(
-                            position,
-                        )));
-                    }
+                if self.selecting_from.take().is_some() {
+                    let width = self.bounds.map_or(0.0, |bounds| bounds.width);
+
+                    self.selection = self.selection.take().filter(|selection| {
+                        (selection.end - selection.start) * width >= MIN_SELECTION_WIDTH
+                    });
+                } else if let Some(position) = self.press_position.take() {
+                    self.selection = None;
+
+                    return Task::done(crate::Message::Audio(audio::Message::SetPosition(
+                        position,
+                    )));
                 }
             }
             Message::CursorMoved(position) => {
                 self.cursor_position = Some(position);
+
+                // Dragging the waveform drags the file out, or the selected region if the drag
+                // starts inside it. The click does not seek then.
+                if let Some(from) = self.selecting_from {
+                    if let Some(to) = self.cursor_fraction() {
+                        self.selection = Some(from.min(to)..from.max(to));
+                    }
+                } else if self.drag.moved(position) {
+                    let press_position = self.press_position.take();
+
+                    if let Some(path) = self.path.clone() {
+                        return match self.selection.clone().filter(|selection| {
+                            press_position.is_some_and(|position| selection.contains(&position))
+                        }) {
+                            Some(region) => drag_region(path, region),
+                            None => drag_out::drag_files(vec![path]),
+                        };
+                    }
+                }
+            }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
             }
             Message::Resized => return self.update_bounds(),
             Message::BoundsChanged(rectangle) => {
                 self.bounds = rectangle;
             }
+            Message::LoopingChanged(looping) => {
+                self.looping = looping;
+            }
         }
 
         Task::none()
@@ -166,18 +293,120 @@ impl Waveform {
                     Event::Window(window::Event::Resized { .. }) => {
                         Some(crate::Message::Waveform(Message::Resized))
                     }
+                    Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => Some(
+                        crate::Message::Waveform(Message::ModifiersChanged(modifiers)),
+                    ),
+                    Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                        Some(crate::Message::Waveform(Message::Released))
+                    }
                     _ => None,
                 }
             }),
         ])
     }
 
+    /// The position of the cursor as a fraction of the width.
+    fn cursor_fraction(&self) -> Option<f32> {
+        let (cursor_position, bounds) = (self.cursor_position?, self.bounds?);
+
+        Some((cursor_position.x / bounds.width).clamp(0.0, 1.0))
+    }
+
     pub fn update_bounds(&self) -> Task<crate::Message> {
         container::visible_bounds(WAVEFORM_CONTAINER.clone())
             .map(|rectangle| crate::Message::Waveform(Message::BoundsChanged(rectangle)))
     }
 }
 
+/// Render the region of the file to a temporary file, then drag it out.
+fn drag_region(path: PathBuf, region: Range<f32>) -> Task<crate::Message> {
+    Task::future(async move {
+        tokio::task::spawn_blocking(move || write_region(&path, region))
+            .await
+            .map_err(|error| error.to_string())?
+            .map_err(|error| error.to_string())
+    })
+    .then(|result: Result<PathBuf, String>| match result {
+        Ok(path) => drag_out::drag_files(vec![path]),
+        Err(error) => {
+            log::error!("Failed to render the region: {}", error);
+            Task::none()
+        }
+    })
+}
+
+/// Write the region of the file, as fractions of its duration, to a WAV file in the temporary
+/// directory.
+fn write_region(path: &Path, region: Range<f32>) -> Result<PathBuf, RegionError> {
+    let mut decoder = audio::open_decoder(path).map_err(|source| RegionError::Decode {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let duration = decoder
+        .total_duration()
+        .ok_or_else(|| RegionError::UnknownDuration(path.to_path_buf()))?
+        .as_secs_f64();
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    let start = duration * region.start as f64;
+    let end = duration * region.end as f64;
+    let start_frame = (start * sample_rate as f64) as usize;
+    let frames = ((end - start) * sample_rate as f64) as usize;
+
+    // Not every decoder can seek, the samples before the region are decoded then.
+    if decoder.try_seek(Duration::from_secs_f64(start)).is_err() {
+        decoder
+            .by_ref()
+            .take(start_frame * channels as usize)
+            .for_each(drop);
+    }
+
+    let samples: Vec<f32> = decoder.take(frames * channels as usize).collect();
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let output = std::env::temp_dir()
+        .join("sex")
+        .join(format!("{name} {start:.3}-{end:.3}.wav"));
+
+    output
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| write_wav(&output, channels, sample_rate, &samples))
+        .map_err(|source| RegionError::Write {
+            path: output.clone(),
+            source,
+        })?;
+
+    Ok(output)
+}
+
+/// Write interleaved samples to a 32 bits float WAV file.
+fn write_wav(path: &Path, channels: u16, sample_rate: u32, samples: &[f32]) -> io::Result<()> {
+    const FORMAT_IEEE_FLOAT: u16 = 3;
+    const SAMPLE_SIZE: u16 = 4;
+
+    let data_size = (samples.len() * SAMPLE_SIZE as usize) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data_size).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(FORMAT_IEEE_FLOAT.to_le_bytes());
+    bytes.extend(channels.to_le_bytes());
+    bytes.extend(sample_rate.to_le_bytes());
+    bytes.extend((sample_rate * (channels * SAMPLE_SIZE) as u32).to_le_bytes());
+    bytes.extend((channels * SAMPLE_SIZE).to_le_bytes());
+    bytes.extend((SAMPLE_SIZE * 8).to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data_size.to_le_bytes());
+
+    for sample in samples {
+        bytes.extend(sample.to_le_bytes());
+    }
+
+    fs::write(path, bytes)
+}
+
 fn waveform_loading() -> impl Stream<Item = Message> {
     iced::stream::channel(8, async move |mut output| {
         let (command_sender, mut command_receiver) = mpsc::channel::<WaveformCommand>(8);
@@ -208,15 +437,22 @@ fn waveform_loading() -> impl Stream<Item = Message> {
                     let mut buffer = Vec::with_capacity(buffer_size);
                     let mut channel = 0;
                     let mut accumulator = 0f32;
+                    let mut loudness = LoudnessMeter::new(decoder.channels() as usize, sample_rate);
+                    let mut true_peak = TruePeakMeter::new(decoder.channels() as usize);
+                    let mut clip_detector = ClipDetector::new(decoder.channels() as usize);
+                    let mut stopped = false;
 
                     while let Some(sample) = decoder.next() {
                         if let Some(WaveformCommand::StopLoading) =
                             command_receiver.next().now_or_never().flatten()
                         {
                             buffer.clear();
+                            stopped = true;
                             break;
                         }
 
+                        loudness.push_sample(sample);
+                        clip_detector.push_sample(sample, true_peak.push_sample(sample));
                         accumulator += sample;
                         channel += 1;
 
@@ -228,6 +464,8 @@ fn waveform_loading() -> impl Stream<Item = Message> {
                             if buffer.len() == buffer_size {
                                 total_samples += buffer.len();
 
+                                send_clips(&mut clip_detector, generation, &mut output).await;
+
                                 output
                                     .send(Message::SamplesReady {
                                         samples: buffer.clone(),
@@ -244,6 +482,8 @@ fn waveform_loading() -> impl Stream<Item = Message> {
                     if !buffer.is_empty() {
                         total_samples += buffer.len();
 
+                        send_clips(&mut clip_detector, generation, &mut output).await;
+
                         output
                             .send(Message::SamplesReady {
                                 samples: buffer.clone(),
@@ -253,6 +493,16 @@ fn waveform_loading() -> impl Stream<Item = Message> {
                             .unwrap();
                     }
 
+                    if !stopped {
+                        output
+                            .send(Message::LoudnessMeasured {
+                                loudness: loudness.file_loudness(),
+                                generation,
+                            })
+                            .await
+                            .unwrap();
+                    }
+
                     let duration = Instant::now() - loading_start_time;
                     let duration = duration.as_millis();
 
@@ -275,39 +525,48 @@ fn waveform_loading() -> impl Stream<Item = Message> {
     })
 }
 
+async fn send_clips(
+    clip_detector: &mut ClipDetector,
+    generation: usize,
+    output: &mut mpsc::Sender<Message>,
+) {
+    let clips = clip_detector.take_clips();
+
+    if !clips.is_empty() {
+        output
+            .send(Message::ClipsFound { clips, generation })
+            .await
+            .unwrap();
+    }
+}
+
 async fn process_command(command: WaveformCommand, output: &mut mpsc::Sender<Message>) -> State {
     match command {
         WaveformCommand::LoadFile { path, generation } => {
-            match File::open(&path) {
-                Ok(file) => {
-                    if let Ok(decoder) = Decoder::new(BufReader::new(file)) {
-                        let samples_count = decoder.total_duration().map(|duration| {
-                            let sample_rate = decoder.sample_rate() as u128;
-                            let samples_count = duration.as_nanos() * sample_rate;
-
-                            (samples_count / 1_000_000_000) as usize
-                        });
-                        let sample_rate = decoder.sample_rate() as usize;
-
-                        debug!("Sample count: {:?}", samples_count);
-
-                        output
-                            .send(Message::LoadingStarted(samples_count))
-                            .await
-                            .unwrap();
-
-                        return State::Decoding {
-                            decoder: Box::new(decoder),
-                            sample_rate,
-                            generation,
-                        };
-                    }
+            match audio::open_decoder(&path) {
+                Ok(decoder) => {
+                    let samples_count = decoder.total_duration().map(|duration| {
+                        let sample_rate = decoder.sample_rate() as u128;
+                        let samples_count = duration.as_nanos() * sample_rate;
+
+                        (samples_count / 1_000_000_000) as usize
+                    });
+                    let sample_rate = decoder.sample_rate() as usize;
+
+                    debug!("Sample count: {:?}", samples_count);
+
+                    output
+                        .send(Message::LoadingStarted(samples_count))
+                        .await
+                        .unwrap();
+
+                    return State::Decoding {
+                        decoder: Box::new(decoder),
+                        sample_rate,
+                        generation,
+                    };
                 }
-                Err(error) => log::error!(
-                    "Failed to open file '{}' for reading: {}",
-                    path.display(),
-                    error
-                ),
+                Err(error) => log::error!("Failed to decode file '{}': {}", path.display(), error),
             }
 
             output.send(Message::Clear).await.unwrap();
@@ -360,11 +619,36 @@ impl canvas::Program<crate::Message> for Waveform {
                         )
                     }
                 }
+
+                // Draw clips
+                let clip_color = theme.extended_palette().danger.base.color;
+
+                for clip in self.clips.iter() {
+                    let x = (clip.start / samples_in_block) as f32;
+                    let width = ((clip.end - 1) / samples_in_block) as f32 - x + 1.0;
+
+                    frame.fill_rectangle(
+                        Point::new(x, 0.0),
+                        Size::new(width, frame.height()),
+                        clip_color.scale_alpha(0.6),
+                    );
+                }
             }
         });
 
         let mut overlay_frame = canvas::Frame::new(renderer, bounds.size());
 
+        if let Some(selection) = self.selection.as_ref() {
+            overlay_frame.fill_rectangle(
+                Point::new(selection.start * overlay_frame.width(), 0.0),
+                Size::new(
+                    (selection.end - selection.start) * overlay_frame.width(),
+                    overlay_frame.height(),
+                ),
+                ui::main_color(theme).scale_alpha(0.3),
+            );
+        }
+
         if !self.samples.is_empty() {
             // Draw play position
             overlay_frame.fill_rectangle(
@@ -374,6 +658,16 @@ impl canvas::Program<crate::Message> for Waveform {
             );
         }
 
+        if self.looping {
+            overlay_frame.fill_text(canvas::Text {
+                content: "Loop".into(),
+                position: Point::new(4f32, 4f32),
+                color: theme.extended_palette().secondary.base.color,
+                size: 12f32.into(),
+                ..Default::default()
+            });
+        }
+
         // Draw cursor position
         if let Some(cursor_position) = cursor.position_in(bounds) {
             overlay_frame.fill_rectangle(
@@ -389,7 +683,13 @@ impl canvas::Program<crate::Message> for Waveform {
 
 use std::sync::LazyLock;
 
-use crate::{audio, ui};
+use crate::{
+    audio::{self, AudioDecoder},
+    clipping::ClipDetector,
+    drag_out::{self, DragGesture},
+    loudness::{FileLoudness, LoudnessMeter, TruePeakMeter},
+    ui, vu_meter,
+};
 
 static WAVEFORM_CONTAINER: LazyLock<container::Id> =
     LazyLock::new(|| container::Id::new("waveform"));
@@ -399,13 +699,17 @@ mod tests {
     use std::{io::Cursor, path::Path, pin::pin};
 
     use crate::{
-        tests::{generate_sine, simulator},
-        waveform::{self, waveform_loading, WaveformCommand},
+        tests::{generate_sine, simulator, snapshot_path},
+        waveform::{self, waveform_loading, write_region, WaveformCommand},
         SEx,
     };
-    use iced::futures::{SinkExt, StreamExt};
+    use iced::{
+        futures::{SinkExt, StreamExt},
+        keyboard::Modifiers,
+        Point, Rectangle, Size,
+    };
     use iced_test::Error;
-    use rodio::Decoder;
+    use rodio::{Decoder, Source};
 
     #[test]
     fn test_waveform() -> Result<(), Error> {
@@ -427,7 +731,7 @@ mod tests {
         let mut ui = simulator(&app);
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_waveform")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_waveform"))?);
 
         Ok(())
     }
@@ -452,7 +756,38 @@ mod tests {
         let mut ui = simulator(&app);
         let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
 
-        assert!(snapshot.matches_hash("snapshots/test_waveform_progressive")?);
+        assert!(snapshot.matches_hash(snapshot_path("test_waveform_progressive"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_waveform_clips() -> Result<(), Error> {
+        let (mut app, _task) = SEx::new();
+
+        const SIZE: usize = 1000;
+        let buffer = generate_sine(SIZE).collect();
+
+        let _ = app.update(crate::Message::Waveform(
+            crate::waveform::Message::LoadingStarted(Some(SIZE)),
+        ));
+        let _ = app.update(crate::Message::Waveform(
+            crate::waveform::Message::SamplesReady {
+                samples: buffer,
+                generation: 0,
+            },
+        ));
+        let _ = app.update(crate::Message::Waveform(
+            crate::waveform::Message::ClipsFound {
+                clips: vec![100..200, 600..602],
+                generation: 0,
+            },
+        ));
+
+        let mut ui = simulator(&app);
+        let snapshot = ui.snapshot(&iced::Theme::CatppuccinFrappe)?;
+
+        assert!(snapshot.matches_hash(snapshot_path("test_waveform_clips"))?);
 
         Ok(())
     }
@@ -468,6 +803,50 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn test_select_region() {
+        let mut waveform = waveform::Waveform::default();
+
+        let _ = waveform.update(waveform::Message::BoundsChanged(Some(Rectangle::new(
+            Point::ORIGIN,
+            Size::new(200.0, 100.0),
+        ))));
+        let _ = waveform.update(waveform::Message::ModifiersChanged(Modifiers::SHIFT));
+        let _ = waveform.update(waveform::Message::CursorMoved(Point::new(50.0, 50.0)));
+        let _ = waveform.update(waveform::Message::Click);
+        let _ = waveform.update(waveform::Message::CursorMoved(Point::new(150.0, 50.0)));
+        // The button is released outside the waveform, its last cursor position is kept.
+        let _ = waveform.update(waveform::Message::Released);
+        let _ = waveform.update(waveform::Message::CursorMoved(Point::new(100.0, 50.0)));
+
+        assert_eq!(waveform.selection, Some(0.25..0.75));
+    }
+
+    #[test]
+    fn test_write_region() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("audio/test_sine_mono.wav");
+        let region = write_region(&path, 0.25..0.75).unwrap();
+        let decoder = Decoder::builder()
+            .with_data(Cursor::new(std::fs::read(&region).unwrap()))
+            .build()
+            .unwrap();
+
+        assert_eq!(decoder.channels(), 1);
+
+        let written: Vec<f32> = decoder.collect();
+        let samples = load_samples_mono();
+        let start = samples.len() / 4;
+
+        // The file lasts 2 s, the region lasts 1 s from 0.5 s.
+        assert!(written.len().abs_diff(samples.len() / 2) <= 1);
+        assert!(written
+            .iter()
+            .zip(&samples[start..])
+            .all(|(written, sample)| (written - sample).abs() < 1e-4));
+
+        std::fs::remove_file(region).unwrap();
+    }
+
     #[tokio::test]
     async fn test_waveform_loading() {
         let test_file_path = Path::new(file!())
@@ -503,6 +882,15 @@ mod tests {
                         buffer.append(&mut samples);
                     }
                     waveform::Message::LoadingStarted(_) => (),
+                    waveform::Message::LoudnessMeasured {
+                        loudness,
+                        generation,
+                    } => {
+                        assert_eq!(generation, 0);
+                        assert!(loudness.integrated.is_some());
+                        // The file lasts 2 s.
+                        assert_eq!(loudness.range, None);
+                    }
                     waveform::Message::LoadingFinished => {
                         assert_eq!(buffer, load_samples_mono());
                         return;
@@ -0,0 +1,1517 @@
+//! Decoding of the WavPack files, which the decoding library used by rodio does not implement.
+//! Lossless, hybrid and floating point files are decoded. The correction file of a hybrid file
+//! (`.wvc`) is not read, so hybrid files are decoded lossy. DSD files are not supported.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Source};
+
+const HEADER_SIZE: usize = 32;
+/// The part of the header counted in its block size.
+const HEADER_SIZE_COUNTED: u32 = 24;
+/// A larger block is considered corrupted.
+const MAX_BLOCK_SIZE: u32 = 1 << 24;
+const MIN_VERSION: u16 = 0x402;
+const MAX_VERSION: u16 = 0x410;
+/// The decorrelation passes of a block.
+const MAX_TERMS: usize = 16;
+/// The history of the decorrelation samples, the longest positive term.
+const MAX_TERM: usize = 8;
+/// Above this number of ones, the count of ones is coded as a number.
+const LIMIT_ONES: u32 = 16;
+/// The shift and the rounding of the slow level, used by the hybrid mode.
+const SLS: i32 = 8;
+const SLO: i32 = 1 << (SLS - 1);
+
+// The flags of the block header.
+const BYTES_STORED: u32 = 0x3;
+const MONO_FLAG: u32 = 0x4;
+const HYBRID_FLAG: u32 = 0x8;
+const JOINT_STEREO: u32 = 0x10;
+const FLOAT_DATA: u32 = 0x80;
+const INT32_DATA: u32 = 0x100;
+const HYBRID_BITRATE: u32 = 0x200;
+const HYBRID_BALANCE: u32 = 0x400;
+const INITIAL_BLOCK: u32 = 0x800;
+const FINAL_BLOCK: u32 = 0x1000;
+const SHIFT_LSB: u32 = 13;
+const SHIFT_MASK: u32 = 0x1f << SHIFT_LSB;
+const SRATE_LSB: u32 = 23;
+const SRATE_MASK: u32 = 0xf << SRATE_LSB;
+/// A stereo block whose channels are identical, stored as mono.
+const FALSE_STEREO: u32 = 0x4000_0000;
+const DSD_FLAG: u32 = 0x8000_0000;
+/// The samples of the block are a single channel.
+const MONO_DATA: u32 = MONO_FLAG | FALSE_STEREO;
+
+const SAMPLE_RATES: [u32; 15] = [
+    6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+    192000,
+];
+
+// The ids of the metadata of a block.
+const ID_UNIQUE: u8 = 0x3f;
+const ID_ODD_SIZE: u8 = 0x40;
+const ID_LARGE: u8 = 0x80;
+const ID_DECORR_TERMS: u8 = 0x2;
+const ID_DECORR_WEIGHTS: u8 = 0x3;
+const ID_DECORR_SAMPLES: u8 = 0x4;
+const ID_ENTROPY_VARS: u8 = 0x5;
+const ID_HYBRID_PROFILE: u8 = 0x6;
+const ID_FLOAT_INFO: u8 = 0x8;
+const ID_INT32_INFO: u8 = 0x9;
+const ID_WV_BITSTREAM: u8 = 0xa;
+const ID_WVX_BITSTREAM: u8 = 0xc;
+const ID_SAMPLE_RATE: u8 = 0x27;
+
+// The flags of the floating point information.
+const FLOAT_SHIFT_ONES: u8 = 0x1;
+const FLOAT_SHIFT_SAME: u8 = 0x2;
+const FLOAT_SHIFT_SENT: u8 = 0x4;
+const FLOAT_ZEROS_SENT: u8 = 0x8;
+const FLOAT_NEG_ZEROS: u8 = 0x10;
+
+/// The fraction of 2^(i / 256) - 1, in 1/256.
+const EXP2_TABLE: [u8; 256] = [
+    0x00, 0x01, 0x01, 0x02, 0x03, 0x03, 0x04, 0x05, 0x06, 0x06, 0x07, 0x08, 0x08, 0x09, 0x0a, 0x0b,
+    0x0b, 0x0c, 0x0d, 0x0e, 0x0e, 0x0f, 0x10, 0x10, 0x11, 0x12, 0x13, 0x13, 0x14, 0x15, 0x16, 0x16,
+    0x17, 0x18, 0x19, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1d, 0x1e, 0x1f, 0x20, 0x20, 0x21, 0x22, 0x23,
+    0x24, 0x24, 0x25, 0x26, 0x27, 0x28, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2c, 0x2d, 0x2e, 0x2f, 0x30,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3a, 0x3b, 0x3c, 0x3d,
+    0x3e, 0x3f, 0x40, 0x41, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x48, 0x49, 0x4a, 0x4b,
+    0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a,
+    0x5b, 0x5c, 0x5d, 0x5e, 0x5e, 0x5f, 0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79,
+    0x7a, 0x7b, 0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x87, 0x88, 0x89, 0x8a,
+    0x8b, 0x8c, 0x8d, 0x8e, 0x8f, 0x90, 0x91, 0x92, 0x93, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b,
+    0x9c, 0x9d, 0x9f, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad,
+    0xaf, 0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xbc, 0xbd, 0xbe, 0xbf, 0xc0,
+    0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc8, 0xc9, 0xca, 0xcb, 0xcd, 0xce, 0xcf, 0xd0, 0xd2, 0xd3, 0xd4,
+    0xd6, 0xd7, 0xd8, 0xd9, 0xdb, 0xdc, 0xdd, 0xde, 0xe0, 0xe1, 0xe2, 0xe4, 0xe5, 0xe6, 0xe8, 0xe9,
+    0xea, 0xec, 0xed, 0xee, 0xf0, 0xf1, 0xf2, 0xf4, 0xf5, 0xf6, 0xf8, 0xf9, 0xfa, 0xfc, 0xfd, 0xff,
+];
+
+/// The fraction of log2(1 + i / 256), in 1/256.
+const LOG2_TABLE: [u8; 256] = [
+    0x00, 0x01, 0x03, 0x04, 0x06, 0x07, 0x09, 0x0a, 0x0b, 0x0d, 0x0e, 0x10, 0x11, 0x12, 0x14, 0x15,
+    0x16, 0x18, 0x19, 0x1a, 0x1c, 0x1d, 0x1e, 0x20, 0x21, 0x22, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2a,
+    0x2c, 0x2d, 0x2e, 0x2f, 0x31, 0x32, 0x33, 0x34, 0x36, 0x37, 0x38, 0x39, 0x3b, 0x3c, 0x3d, 0x3e,
+    0x3f, 0x41, 0x42, 0x43, 0x44, 0x45, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4d, 0x4e, 0x4f, 0x50, 0x51,
+    0x52, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x5c, 0x5d, 0x5e, 0x5f, 0x60, 0x61, 0x62, 0x63,
+    0x64, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f, 0x70, 0x71, 0x72, 0x74, 0x75,
+    0x76, 0x77, 0x78, 0x79, 0x7a, 0x7b, 0x7c, 0x7d, 0x7e, 0x7f, 0x80, 0x81, 0x82, 0x83, 0x84, 0x85,
+    0x86, 0x87, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x8d, 0x8e, 0x8f, 0x90, 0x91, 0x92, 0x93, 0x94, 0x95,
+    0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b, 0x9b, 0x9c, 0x9d, 0x9e, 0x9f, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4,
+    0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xa9, 0xaa, 0xab, 0xac, 0xad, 0xae, 0xaf, 0xb0, 0xb1, 0xb2, 0xb2,
+    0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xb9, 0xba, 0xbb, 0xbc, 0xbd, 0xbe, 0xbf, 0xc0, 0xc0,
+    0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xcb, 0xcb, 0xcc, 0xcd, 0xce,
+    0xcf, 0xd0, 0xd0, 0xd1, 0xd2, 0xd3, 0xd4, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd8, 0xd9, 0xda, 0xdb,
+    0xdc, 0xdc, 0xdd, 0xde, 0xdf, 0xe0, 0xe0, 0xe1, 0xe2, 0xe3, 0xe4, 0xe4, 0xe5, 0xe6, 0xe7, 0xe7,
+    0xe8, 0xe9, 0xea, 0xea, 0xeb, 0xec, 0xed, 0xee, 0xee, 0xef, 0xf0, 0xf1, 0xf1, 0xf2, 0xf3, 0xf4,
+    0xf4, 0xf5, 0xf6, 0xf7, 0xf7, 0xf8, 0xf9, 0xf9, 0xfa, 0xfb, 0xfc, 0xfc, 0xfd, 0xfe, 0xff, 0xff,
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum WavPackError {
+    #[error("Not a WavPack stream")]
+    NotWavPack,
+    #[error("Unsupported WavPack stream: {0}")]
+    Unsupported(&'static str),
+    #[error("Invalid WavPack block: {0}")]
+    InvalidBlock(&'static str),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Decodes a WavPack stream, with any number of channels.
+pub struct WavPackDecoder<R: Read + Seek> {
+    data: R,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    /// The index of the first frame of the stream, usually 0.
+    first_index: u64,
+    /// The frames of the audio, `None` if the header does not give it.
+    frames_count: Option<u64>,
+    /// The frame of the next sample kept, from the start of the audio.
+    frame: u64,
+    /// The frames still to decode and drop, the frames before a seek position.
+    skipped: u64,
+    /// The interleaved samples of the last blocks decoded.
+    buffer: Vec<f32>,
+    /// The next sample of `buffer`.
+    index: usize,
+}
+
+impl<R: Read + Seek> WavPackDecoder<R> {
+    pub fn new(mut data: R) -> Result<Self, WavPackError> {
+        // The format is given by the first blocks holding samples, the blocks before them only
+        // hold metadata such as the header of the original file.
+        let (first, body) = loop {
+            let header = read_header(&mut data)?.ok_or(WavPackError::NotWavPack)?;
+            let body = read_body(&mut data, &header)?;
+
+            if header.block_samples > 0 {
+                break (header, body);
+            }
+        };
+
+        if first.flags & DSD_FLAG != 0 {
+            return Err(WavPackError::Unsupported("DSD audio"));
+        }
+
+        let sample_rate = match (first.flags & SRATE_MASK) >> SRATE_LSB {
+            index if (index as usize) < SAMPLE_RATES.len() => SAMPLE_RATES[index as usize],
+            _ => metadata(&body)?
+                .find(|(id, _)| *id == ID_SAMPLE_RATE)
+                .and_then(|(_, data)| {
+                    (data.len() >= 3).then(|| u32::from_le_bytes([data[0], data[1], data[2], 0]))
+                })
+                .filter(|sample_rate| *sample_rate > 0)
+                .ok_or(WavPackError::InvalidBlock("missing sample rate"))?,
+        };
+        let bits_per_sample = if first.flags & FLOAT_DATA != 0 {
+            32
+        } else {
+            ((first.flags & BYTES_STORED) + 1) * 8 - ((first.flags & SHIFT_MASK) >> SHIFT_LSB)
+        };
+
+        // The other blocks of the first frame give the other channels.
+        let mut channels = first.channels();
+        let mut header = first.clone();
+
+        while header.flags & FINAL_BLOCK == 0 {
+            header =
+                read_header(&mut data)?.ok_or(WavPackError::InvalidBlock("missing final block"))?;
+            data.seek(SeekFrom::Current(header.body_size() as i64))?;
+            channels += header.channels();
+        }
+
+        data.rewind()?;
+
+        Ok(Self {
+            data,
+            channels,
+            sample_rate,
+            bits_per_sample,
+            first_index: first.block_index,
+            frames_count: first.total_samples,
+            frame: 0,
+            skipped: 0,
+            buffer: Vec::new(),
+            index: 0,
+        })
+    }
+
+    /// The bits of a sample, before it is decoded to a float.
+    pub fn bits_per_sample(&self) -> u32 {
+        self.bits_per_sample
+    }
+
+    /// Decode the blocks of the next frame, the blocks of all the channels at the same time.
+    /// Returns `None` at the end of the stream.
+    fn read_frame(&mut self) -> Result<Option<Vec<f32>>, WavPackError> {
+        let first = loop {
+            let Some(header) = read_header(&mut self.data)? else {
+                return Ok(None);
+            };
+
+            if header.block_samples > 0 && header.flags & INITIAL_BLOCK != 0 {
+                break header;
+            }
+
+            self.data
+                .seek(SeekFrom::Current(header.body_size() as i64))?;
+        };
+        let frames = first.block_samples as usize;
+        let channels = self.channels as usize;
+        let mut samples = vec![0.0; frames * channels];
+        let mut header = first;
+        let mut channel = 0;
+
+        loop {
+            let body = read_body(&mut self.data, &header)?;
+            let block_channels = header.channels() as usize;
+
+            if header.block_samples as usize != frames || channel + block_channels > channels {
+                return Err(WavPackError::InvalidBlock("blocks of a frame do not match"));
+            }
+
+            let block = decode_block(&header, &body)?;
+
+            for (frame, values) in block.chunks_exact(block_channels).enumerate() {
+                let start = frame * channels + channel;
+
+                samples[start..start + block_channels].copy_from_slice(values);
+            }
+
+            channel += block_channels;
+
+            if header.flags & FINAL_BLOCK != 0 {
+                break;
+            }
+
+            header = read_header(&mut self.data)?
+                .ok_or(WavPackError::InvalidBlock("missing final block"))?;
+        }
+
+        if channel != channels {
+            return Err(WavPackError::InvalidBlock("blocks of a frame do not match"));
+        }
+
+        Ok(Some(samples))
+    }
+
+    /// Drop the frames to skip and the frames after the end of the audio.
+    fn trim(&mut self) {
+        let channels = self.channels as usize;
+        let frames = self.buffer.len() / channels;
+        let skipped = self.skipped.min(frames as u64) as usize;
+        let remaining = self.frames_count.map_or(usize::MAX, |count| {
+            count.saturating_sub(self.frame) as usize
+        });
+        let kept = (frames - skipped).min(remaining);
+
+        self.skipped -= skipped as u64;
+        self.frame += kept as u64;
+        self.buffer.truncate((skipped + kept) * channels);
+        self.buffer.drain(..skipped * channels);
+    }
+
+    /// Decode the next frame in the buffer, false at the end of the audio.
+    fn decode_next(&mut self) -> Result<bool, WavPackError> {
+        self.buffer.clear();
+        self.index = 0;
+
+        if self.frames_count.is_some_and(|count| self.frame >= count) {
+            return Ok(false);
+        }
+
+        let Some(samples) = self.read_frame()? else {
+            return Ok(false);
+        };
+
+        self.buffer = samples;
+        self.trim();
+
+        Ok(true)
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<(), WavPackError> {
+        let mut target = (position.as_secs_f64() * self.sample_rate as f64) as u64;
+
+        if let Some(count) = self.frames_count {
+            target = target.min(count);
+        }
+
+        self.buffer.clear();
+        self.index = 0;
+        self.data.rewind()?;
+
+        // The blocks are decoded independently, the frame holding the target is decoded next.
+        loop {
+            let start = self.data.stream_position()?;
+            let Some(header) = read_header(&mut self.data)? else {
+                self.frame = self.frames_count.unwrap_or(target);
+                self.skipped = 0;
+
+                return Ok(());
+            };
+            let index = header.block_index.saturating_sub(self.first_index);
+
+            if header.block_samples > 0
+                && header.flags & INITIAL_BLOCK != 0
+                && index + header.block_samples as u64 > target
+            {
+                self.data.seek(SeekFrom::Start(start))?;
+                self.skipped = target.saturating_sub(index);
+                self.frame = target;
+
+                return Ok(());
+            }
+
+            self.data
+                .seek(SeekFrom::Current(header.body_size() as i64))?;
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for WavPackDecoder<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(sample) = self.buffer.get(self.index) {
+                self.index += 1;
+
+                return Some(*sample);
+            }
+
+            match self.decode_next() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(error) => {
+                    log::error!("Failed to decode a WavPack stream: {}", error);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Source for WavPackDecoder<R> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.frames_count
+            .map(|count| Duration::from_secs_f64(count as f64 / self.sample_rate as f64))
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.seek(position).map_err(|error| {
+            log::error!("Failed to seek in a WavPack stream: {}", error);
+
+            SeekError::NotSupported {
+                underlying_source: std::any::type_name::<Self>(),
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct BlockHeader {
+    /// The size of the block after its first 8 bytes.
+    size: u32,
+    total_samples: Option<u64>,
+    block_index: u64,
+    block_samples: u32,
+    flags: u32,
+}
+
+impl BlockHeader {
+    fn body_size(&self) -> u32 {
+        self.size - HEADER_SIZE_COUNTED
+    }
+
+    /// The channels decoded from the block.
+    fn channels(&self) -> u16 {
+        if self.flags & MONO_FLAG != 0 {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+/// Read the header of the next block, `None` at the end of the stream or at a tag following the
+/// blocks.
+fn read_header(data: &mut impl Read) -> Result<Option<BlockHeader>, WavPackError> {
+    let mut header = [0; HEADER_SIZE];
+
+    match data.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+
+    if !header.starts_with(b"wvpk") {
+        return Ok(None);
+    }
+
+    let u32_at = |start: usize| u32::from_le_bytes(header[start..start + 4].try_into().unwrap());
+    let size = u32_at(4);
+    let version = u16::from_le_bytes([header[8], header[9]]);
+    let total_samples = u32_at(12);
+
+    if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+        return Err(WavPackError::Unsupported("unknown version"));
+    }
+
+    if !(HEADER_SIZE_COUNTED..=MAX_BLOCK_SIZE).contains(&size) {
+        return Err(WavPackError::InvalidBlock("invalid size"));
+    }
+
+    Ok(Some(BlockHeader {
+        size,
+        // The upper bits are counted in steps of 2^32 - 1, as -1 means an unknown length.
+        total_samples: (total_samples != u32::MAX)
+            .then(|| ((header[11] as u64) << 32) - header[11] as u64 + total_samples as u64),
+        block_index: ((header[10] as u64) << 32) + u32_at(16) as u64,
+        block_samples: u32_at(20),
+        flags: u32_at(24),
+    }))
+}
+
+fn read_body(data: &mut impl Read, header: &BlockHeader) -> Result<Vec<u8>, WavPackError> {
+    let mut body = vec![0; header.body_size() as usize];
+
+    data.read_exact(&mut body)?;
+
+    Ok(body)
+}
+
+/// The metadata of a block, with their id and their data.
+fn metadata(body: &[u8]) -> Result<impl Iterator<Item = (u8, &[u8])>, WavPackError> {
+    let mut items = Vec::new();
+    let mut rest = body;
+
+    while !rest.is_empty() {
+        let id = rest[0];
+        let (words, header_size) = if id & ID_LARGE != 0 {
+            match rest {
+                [_, a, b, c, ..] => (u32::from_le_bytes([*a, *b, *c, 0]) as usize, 4),
+                _ => return Err(WavPackError::InvalidBlock("truncated metadata")),
+            }
+        } else {
+            match rest {
+                [_, size, ..] => (*size as usize, 2),
+                _ => return Err(WavPackError::InvalidBlock("truncated metadata")),
+            }
+        };
+        let size = words * 2;
+
+        if rest.len() < header_size + size {
+            return Err(WavPackError::InvalidBlock("truncated metadata"));
+        }
+
+        let length = if id & ID_ODD_SIZE != 0 && size > 0 {
+            size - 1
+        } else {
+            size
+        };
+
+        items.push((id & ID_UNIQUE, &rest[header_size..header_size + length]));
+        rest = &rest[header_size + size..];
+    }
+
+    Ok(items.into_iter())
+}
+
+#[derive(Default)]
+struct FloatInfo {
+    flags: u8,
+    shift: u8,
+    max_exponent: u8,
+}
+
+#[derive(Default)]
+struct Int32Info {
+    sent_bits: u8,
+    zeros: u8,
+    ones: u8,
+    dups: u8,
+}
+
+/// Decode a block, returns its samples interleaved, one or two channels.
+fn decode_block(header: &BlockHeader, body: &[u8]) -> Result<Vec<f32>, WavPackError> {
+    let flags = header.flags;
+    let mono = flags & MONO_DATA != 0;
+
+    if flags & DSD_FLAG != 0 {
+        return Err(WavPackError::Unsupported("DSD audio"));
+    }
+
+    let mut passes = Vec::new();
+    let mut words = Words::default();
+    let mut bits = None;
+    let mut extra_bits = None;
+    let mut float_info = None;
+    let mut int32_info = Int32Info::default();
+    let mut has_entropy_vars = false;
+
+    for (id, data) in metadata(body)? {
+        match id {
+            ID_DECORR_TERMS => passes = read_decorr_terms(data, mono)?,
+            ID_DECORR_WEIGHTS => read_decorr_weights(data, &mut passes, mono)?,
+            ID_DECORR_SAMPLES => read_decorr_samples(data, &mut passes, mono)?,
+            ID_ENTROPY_VARS => {
+                words.read_entropy_vars(data, mono)?;
+                has_entropy_vars = true;
+            }
+            ID_HYBRID_PROFILE => words.read_hybrid_profile(data, flags)?,
+            ID_FLOAT_INFO => match data {
+                [flags, shift, max_exponent, _] => {
+                    float_info = Some(FloatInfo {
+                        flags: *flags,
+                        shift: *shift,
+                        max_exponent: *max_exponent,
+                    })
+                }
+                _ => return Err(WavPackError::InvalidBlock("invalid float information")),
+            },
+            ID_INT32_INFO => match data {
+                [sent_bits, zeros, ones, dups] => {
+                    int32_info = Int32Info {
+                        sent_bits: *sent_bits,
+                        zeros: *zeros,
+                        ones: *ones,
+                        dups: *dups,
+                    }
+                }
+                _ => return Err(WavPackError::InvalidBlock("invalid int32 information")),
+            },
+            ID_WV_BITSTREAM => bits = Some(BitReader::new(data)),
+            // The bits lost by the conversion of floats or of 32 bit integers, after a CRC.
+            ID_WVX_BITSTREAM if data.len() > 4 => extra_bits = Some(BitReader::new(&data[4..])),
+            _ => {}
+        }
+    }
+
+    let (Some(mut bits), true) = (bits, has_entropy_vars) else {
+        return Err(WavPackError::InvalidBlock("missing samples"));
+    };
+    let count = header.block_samples as usize * if mono { 1 } else { 2 };
+    let mut samples = Vec::with_capacity(count);
+
+    for index in 0..count {
+        let channel = if mono { 0 } else { index & 1 };
+
+        samples.push(words.read_word(&mut bits, channel, flags)?);
+    }
+
+    for pass in passes.iter_mut() {
+        if mono {
+            pass.decorrelate_mono(&mut samples);
+        } else {
+            pass.decorrelate_stereo(&mut samples);
+        }
+    }
+
+    if !mono && flags & JOINT_STEREO != 0 {
+        for frame in samples.chunks_exact_mut(2) {
+            frame[1] = frame[1].wrapping_sub(frame[0] >> 1);
+            frame[0] = frame[0].wrapping_add(frame[1]);
+        }
+    }
+
+    let samples = if flags & FLOAT_DATA != 0 {
+        let info = float_info.ok_or(WavPackError::InvalidBlock("missing float information"))?;
+
+        samples
+            .into_iter()
+            .map(|value| float_value(value, &info, extra_bits.as_mut()))
+            .collect::<Vec<_>>()
+    } else {
+        let bits_stored = ((flags & BYTES_STORED) + 1) * 8;
+        let scale = 1.0 / (1u64 << (bits_stored - 1)) as f32;
+
+        fix_integers(&mut samples, flags, &int32_info, extra_bits.as_mut());
+        samples
+            .into_iter()
+            .map(|value| value as f32 * scale)
+            .collect()
+    };
+
+    // A false stereo block holds the samples of both channels once.
+    if flags & FALSE_STEREO != 0 && flags & MONO_FLAG == 0 {
+        return Ok(samples.into_iter().flat_map(|sample| [sample; 2]).collect());
+    }
+
+    Ok(samples)
+}
+
+/// Restore the bits of the integers removed by the encoder: the shift of the samples, and for
+/// 32 bit integers, the bits sent separately and the repeated low bits.
+fn fix_integers(
+    samples: &mut [i32],
+    flags: u32,
+    info: &Int32Info,
+    extra_bits: Option<&mut BitReader>,
+) {
+    let mut shift = (flags & SHIFT_MASK) >> SHIFT_LSB;
+    let lossy = flags & HYBRID_FLAG != 0;
+    let bits_stored = ((flags & BYTES_STORED) + 1) * 8;
+
+    if flags & INT32_DATA != 0 {
+        let (mut zeros, mut ones, mut dups) = (info.zeros, info.ones, info.dups);
+        let sent_bits = info.sent_bits as u32;
+
+        if extra_bits.is_none() && sent_bits == 0 && lossy && bits_stored == 32 {
+            // The low bits of a lossy sample are not exact, at least 8 bits are shifted.
+            while shift < 8 {
+                if zeros > 0 {
+                    zeros -= 1;
+                } else if ones > 0 {
+                    ones -= 1;
+                } else if dups > 0 {
+                    dups -= 1;
+                } else {
+                    break;
+                }
+
+                shift += 1;
+            }
+        }
+
+        let restore_low_bits = |value: i32| {
+            if zeros > 0 {
+                value.wrapping_shl(zeros as u32)
+            } else if ones > 0 {
+                value
+                    .wrapping_add(1)
+                    .wrapping_shl(ones as u32)
+                    .wrapping_sub(1)
+            } else if dups > 0 {
+                let low = value & 1;
+
+                value
+                    .wrapping_add(low)
+                    .wrapping_shl(dups as u32)
+                    .wrapping_sub(low)
+            } else {
+                value
+            }
+        };
+
+        if let Some(extra_bits) = extra_bits {
+            for sample in samples.iter_mut() {
+                let low = extra_bits.read_bits(sent_bits) as i32;
+
+                *sample = restore_low_bits(sample.wrapping_shl(sent_bits) | low);
+            }
+        } else if sent_bits == 0 && zeros + ones + dups > 0 {
+            for sample in samples.iter_mut() {
+                *sample = restore_low_bits(*sample);
+            }
+        } else {
+            shift += (zeros + info.sent_bits + ones + dups) as u32;
+        }
+    }
+
+    if lossy {
+        // The errors of the lossy decoding may exceed the range of the samples.
+        let min_value = (i32::MIN >> (32 - bits_stored)) >> shift;
+        let max_value = (i32::MAX >> (32 - bits_stored)) >> shift;
+
+        for sample in samples.iter_mut() {
+            *sample = (*sample).clamp(min_value, max_value).wrapping_shl(shift);
+        }
+    } else if shift > 0 {
+        for sample in samples.iter_mut() {
+            *sample = sample.wrapping_shl(shift);
+        }
+    }
+}
+
+/// Build a float from its mantissa decoded as an integer, and the bits lost by the conversion.
+fn float_value(value: i32, info: &FloatInfo, mut extra_bits: Option<&mut BitReader>) -> f32 {
+    let lossy = extra_bits.is_none();
+    let mut read_bits = |count: u32| {
+        extra_bits
+            .as_mut()
+            .map(|extra_bits| extra_bits.read_bits(count))
+    };
+    let mut exponent = info.max_exponent as u32;
+    let mut sign = 0;
+    let mut mantissa;
+
+    if value == 0 {
+        mantissa = 0;
+        exponent = 0;
+
+        if info.flags & FLOAT_ZEROS_SENT != 0 {
+            if read_bits(1) == Some(1) {
+                mantissa = read_bits(23).unwrap_or_default();
+
+                if info.max_exponent >= 25 {
+                    exponent = read_bits(8).unwrap_or_default();
+                }
+
+                sign = read_bits(1).unwrap_or_default();
+            } else if info.flags & FLOAT_NEG_ZEROS != 0 {
+                sign = read_bits(1).unwrap_or_default();
+            }
+        }
+    } else {
+        let mut value = value.wrapping_shl(info.shift as u32);
+
+        if value < 0 {
+            value = value.wrapping_neg();
+            sign = 1;
+        }
+
+        mantissa = value as u32;
+
+        if lossy && mantissa >= 0x100_0000 {
+            // The errors of the lossy decoding may exceed the mantissa.
+            while mantissa & 0xf00_0000 != 0 {
+                mantissa >>= 1;
+                exponent += 1;
+            }
+        } else if mantissa == 0x100_0000 {
+            // An infinity or a NaN.
+            mantissa = if read_bits(1) == Some(1) {
+                read_bits(23).unwrap_or_default()
+            } else {
+                0
+            };
+            exponent = 255;
+        } else {
+            let mut shift_count = 0;
+
+            if exponent > 0 {
+                while mantissa & 0x80_0000 == 0 {
+                    exponent -= 1;
+
+                    if exponent == 0 {
+                        break;
+                    }
+
+                    shift_count += 1;
+                    mantissa <<= 1;
+                }
+            }
+
+            if shift_count > 0 {
+                let low_bits = (1 << shift_count) - 1;
+
+                if info.flags & FLOAT_SHIFT_ONES != 0
+                    || (info.flags & FLOAT_SHIFT_SAME != 0 && read_bits(1) == Some(1))
+                {
+                    mantissa |= low_bits;
+                } else if info.flags & FLOAT_SHIFT_SENT != 0 {
+                    mantissa |= read_bits(shift_count).unwrap_or_default() & low_bits;
+                }
+            }
+        }
+    }
+
+    f32::from_bits(sign << 31 | (exponent & 0xff) << 23 | mantissa & 0x7f_ffff)
+}
+
+/// Reads the bits of a bitstream from the least significant bit of each byte.
+/// The bits after the end of the data are zeros.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> bool {
+        let bit = self
+            .data
+            .get(self.position / 8)
+            .is_some_and(|byte| byte >> (self.position % 8) & 1 == 1);
+
+        self.position += 1;
+        bit
+    }
+
+    /// Read a number of up to 32 bits, the first bit read is the least significant.
+    fn read_bits(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, bit| value | (self.read_bit() as u32) << bit)
+    }
+
+    /// Count the ones before the next zero, up to `limit` ones.
+    fn read_ones(&mut self, limit: u32) -> u32 {
+        let mut ones = 0;
+
+        while ones < limit && self.read_bit() {
+            ones += 1;
+        }
+
+        ones
+    }
+
+    /// Read a number coded by its count of bits in unary, then its bits below the highest one.
+    fn read_elias(&mut self) -> Option<u32> {
+        let bits = self.read_ones(33);
+
+        match bits {
+            0 | 1 => Some(bits),
+            33 => None,
+            _ => Some(self.read_bits(bits - 1) | 1 << (bits - 1)),
+        }
+    }
+
+    /// Read a number from 0 to `max`, the smallest numbers have one bit less.
+    fn read_code(&mut self, max: u32) -> u32 {
+        if max < 2 {
+            return if max == 1 { self.read_bit() as u32 } else { 0 };
+        }
+
+        let bits = 32 - max.leading_zeros();
+        let extras = ((1u64 << bits) - max as u64 - 1) as u32;
+        let code = self.read_bits(bits - 1);
+
+        if code >= extras {
+            (code << 1) - extras + self.read_bit() as u32
+        } else {
+            code
+        }
+    }
+}
+
+/// The state of the entropy decoder of a channel.
+#[derive(Default)]
+struct Entropy {
+    median: [u32; 3],
+    slow_level: i32,
+    error_limit: u32,
+}
+
+impl Entropy {
+    fn median(&self, index: usize) -> u32 {
+        (self.median[index] >> 4) + 1
+    }
+
+    fn increase_median(&mut self, index: usize) {
+        let divisor = 128 >> index;
+
+        self.median[index] =
+            self.median[index].wrapping_add(self.median[index].wrapping_add(divisor) / divisor * 5);
+    }
+
+    fn decrease_median(&mut self, index: usize) {
+        let divisor = 128 >> index;
+
+        self.median[index] = self.median[index]
+            .wrapping_sub(self.median[index].wrapping_add(divisor - 2) / divisor * 2);
+    }
+
+    fn decay_slow_level(&mut self) {
+        self.slow_level -= (self.slow_level + SLO) >> SLS;
+    }
+}
+
+/// The entropy decoder of the residuals of the decorrelation.
+#[derive(Default)]
+struct Words {
+    channels: [Entropy; 2],
+    bitrate_delta: [u32; 2],
+    bitrate_acc: [u32; 2],
+    /// The next count of ones is at least one.
+    holding_one: bool,
+    /// The next count of ones is zero, it is not read.
+    holding_zero: bool,
+    /// The zeros left in a run of zeros.
+    zeros_acc: u32,
+}
+
+impl Words {
+    fn read_entropy_vars(&mut self, data: &[u8], mono: bool) -> Result<(), WavPackError> {
+        let channels = if mono { 1 } else { 2 };
+
+        if data.len() != 6 * channels {
+            return Err(WavPackError::InvalidBlock("invalid entropy variables"));
+        }
+
+        for (index, value) in data.chunks_exact(2).enumerate() {
+            self.channels[index / 3].median[index % 3] =
+                exp2s(u16::from_le_bytes([value[0], value[1]]) as i32) as u32;
+        }
+
+        Ok(())
+    }
+
+    fn read_hybrid_profile(&mut self, data: &[u8], flags: u32) -> Result<(), WavPackError> {
+        let channels = if flags & MONO_DATA != 0 { 1 } else { 2 };
+        let mut values = data
+            .chunks_exact(2)
+            .map(|value| u16::from_le_bytes([value[0], value[1]]));
+
+        if !data.len().is_multiple_of(2) {
+            return Err(WavPackError::InvalidBlock("invalid hybrid profile"));
+        }
+
+        let mut next = || {
+            values
+                .next()
+                .ok_or(WavPackError::InvalidBlock("invalid hybrid profile"))
+        };
+
+        if flags & HYBRID_BITRATE != 0 {
+            for channel in 0..channels {
+                self.channels[channel].slow_level = exp2s(next()? as i32);
+            }
+        }
+
+        for channel in 0..channels {
+            self.bitrate_acc[channel] = (next()? as u32) << 16;
+        }
+
+        if let Ok(value) = next() {
+            self.bitrate_delta[0] = exp2s(value as i16 as i32) as u32;
+
+            if channels == 2 {
+                self.bitrate_delta[1] = exp2s(next()? as i16 as i32) as u32;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The largest error of the hybrid mode, from the bitrate and, if the bitrate is variable,
+    /// from the level of the signal.
+    fn update_error_limit(&mut self, flags: u32) {
+        let mut bitrates = [0; 2];
+        let channels = if flags & MONO_DATA != 0 { 1 } else { 2 };
+
+        for ((acc, delta), bitrate) in self
+            .bitrate_acc
+            .iter_mut()
+            .zip(self.bitrate_delta)
+            .zip(&mut bitrates)
+            .take(channels)
+        {
+            *acc = acc.wrapping_add(delta);
+            *bitrate = (*acc >> 16) as i32;
+        }
+
+        if flags & HYBRID_BITRATE == 0 {
+            for (entropy, bitrate) in self.channels.iter_mut().zip(bitrates).take(channels) {
+                entropy.error_limit = exp2s(bitrate) as u32;
+            }
+
+            return;
+        }
+
+        let slow_logs = self
+            .channels
+            .each_ref()
+            .map(|entropy| (entropy.slow_level + SLO) >> SLS);
+
+        if channels == 2 && flags & HYBRID_BALANCE != 0 {
+            let balance = (slow_logs[1] - slow_logs[0] + bitrates[1] + 1) >> 1;
+
+            bitrates = if balance > bitrates[0] {
+                [0, bitrates[0] * 2]
+            } else if -balance > bitrates[0] {
+                [bitrates[0] * 2, 0]
+            } else {
+                [bitrates[0] - balance, bitrates[0] + balance]
+            };
+        }
+
+        for ((entropy, slow_log), bitrate) in self
+            .channels
+            .iter_mut()
+            .zip(slow_logs)
+            .zip(bitrates)
+            .take(channels)
+        {
+            let level = slow_log - bitrate;
+
+            entropy.error_limit = if level > -0x100 {
+                exp2s(level + 0x100) as u32
+            } else {
+                0
+            };
+        }
+    }
+
+    /// Read the next residual of a channel.
+    fn read_word(
+        &mut self,
+        bits: &mut BitReader,
+        channel: usize,
+        flags: u32,
+    ) -> Result<i32, WavPackError> {
+        // While both channels are silent, the runs of zeros are coded by their length.
+        if self.channels[0].median[0] < 2
+            && self.channels[1].median[0] < 2
+            && !self.holding_zero
+            && !self.holding_one
+        {
+            if self.zeros_acc > 0 {
+                self.zeros_acc -= 1;
+
+                if self.zeros_acc > 0 {
+                    self.channels[channel].decay_slow_level();
+                    return Ok(0);
+                }
+            } else {
+                self.zeros_acc = bits
+                    .read_elias()
+                    .ok_or(WavPackError::InvalidBlock("invalid run of zeros"))?;
+
+                if self.zeros_acc > 0 {
+                    self.channels[channel].decay_slow_level();
+
+                    for entropy in self.channels.iter_mut() {
+                        entropy.median = [0; 3];
+                    }
+
+                    return Ok(0);
+                }
+            }
+        }
+
+        // A count of ones also tells whether the count of the next residual is zero.
+        let ones_count = if self.holding_zero {
+            self.holding_zero = false;
+            0
+        } else {
+            let mut ones_count = bits.read_ones(LIMIT_ONES + 1);
+
+            if ones_count > LIMIT_ONES {
+                return Err(WavPackError::InvalidBlock("invalid count of ones"));
+            }
+
+            if ones_count == LIMIT_ONES {
+                ones_count += bits
+                    .read_elias()
+                    .ok_or(WavPackError::InvalidBlock("invalid count of ones"))?;
+            }
+
+            let count = (ones_count >> 1) + self.holding_one as u32;
+
+            self.holding_one = ones_count & 1 == 1;
+            self.holding_zero = !self.holding_one;
+            count
+        };
+
+        if flags & HYBRID_FLAG != 0 && channel == 0 {
+            self.update_error_limit(flags);
+        }
+
+        let entropy = &mut self.channels[channel];
+        let (mut low, mut high);
+
+        if ones_count == 0 {
+            low = 0;
+            high = entropy.median(0) - 1;
+            entropy.decrease_median(0);
+        } else {
+            low = entropy.median(0);
+            entropy.increase_median(0);
+
+            if ones_count == 1 {
+                high = low.wrapping_add(entropy.median(1) - 1);
+                entropy.decrease_median(1);
+            } else {
+                low = low.wrapping_add(entropy.median(1));
+                entropy.increase_median(1);
+
+                if ones_count == 2 {
+                    high = low.wrapping_add(entropy.median(2) - 1);
+                    entropy.decrease_median(2);
+                } else {
+                    low = low.wrapping_add((ones_count - 2).wrapping_mul(entropy.median(2)));
+                    high = low.wrapping_add(entropy.median(2) - 1);
+                    entropy.increase_median(2);
+                }
+            }
+        }
+
+        low &= 0x7fff_ffff;
+        high = (high & 0x7fff_ffff).max(low);
+
+        let mut mid = (high + low + 1) >> 1;
+
+        if entropy.error_limit == 0 {
+            mid = low + bits.read_code(high - low);
+        } else {
+            // In the hybrid mode, the value is only known within the error limit.
+            while high - low > entropy.error_limit {
+                if bits.read_bit() {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+
+                mid = (high + low + 1) >> 1;
+            }
+        }
+
+        let negative = bits.read_bit();
+
+        if flags & HYBRID_BITRATE != 0 {
+            entropy.decay_slow_level();
+            entropy.slow_level = entropy.slow_level.wrapping_add(log2(mid));
+        }
+
+        Ok(if negative { !(mid as i32) } else { mid as i32 })
+    }
+}
+
+/// A pass of the decorrelation, which predicts each sample from the previous samples.
+#[derive(Default, Clone)]
+struct DecorrPass {
+    /// 1 to 8: the sample this number of samples before, 17 and 18: an extrapolation of the two
+    /// previous samples, -1 to -3: the sample of the other channel.
+    term: i32,
+    delta: i32,
+    weight_a: i32,
+    weight_b: i32,
+    samples_a: [i32; MAX_TERM],
+    samples_b: [i32; MAX_TERM],
+}
+
+impl DecorrPass {
+    fn decorrelate_mono(&mut self, samples: &mut [i32]) {
+        let delta = self.delta;
+
+        match self.term {
+            17 | 18 => {
+                for sample in samples.iter_mut() {
+                    let predicted = extrapolate(self.term, &self.samples_a);
+
+                    self.samples_a[1] = self.samples_a[0];
+                    self.samples_a[0] =
+                        apply_weight(self.weight_a, predicted).wrapping_add(*sample);
+                    update_weight(&mut self.weight_a, delta, predicted, *sample);
+                    *sample = self.samples_a[0];
+                }
+            }
+            term => {
+                let mut m = 0;
+                let mut k = term as usize & (MAX_TERM - 1);
+
+                for sample in samples.iter_mut() {
+                    let previous = self.samples_a[m];
+
+                    self.samples_a[k] = apply_weight(self.weight_a, previous).wrapping_add(*sample);
+                    update_weight(&mut self.weight_a, delta, previous, *sample);
+                    *sample = self.samples_a[k];
+                    m = (m + 1) & (MAX_TERM - 1);
+                    k = (k + 1) & (MAX_TERM - 1);
+                }
+            }
+        }
+    }
+
+    fn decorrelate_stereo(&mut self, samples: &mut [i32]) {
+        let delta = self.delta;
+
+        match self.term {
+            17 | 18 => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let predicted = extrapolate(self.term, &self.samples_a);
+
+                    self.samples_a[1] = self.samples_a[0];
+                    self.samples_a[0] =
+                        apply_weight(self.weight_a, predicted).wrapping_add(frame[0]);
+                    update_weight(&mut self.weight_a, delta, predicted, frame[0]);
+                    frame[0] = self.samples_a[0];
+
+                    let predicted = extrapolate(self.term, &self.samples_b);
+
+                    self.samples_b[1] = self.samples_b[0];
+                    self.samples_b[0] =
+                        apply_weight(self.weight_b, predicted).wrapping_add(frame[1]);
+                    update_weight(&mut self.weight_b, delta, predicted, frame[1]);
+                    frame[1] = self.samples_b[0];
+                }
+            }
+            -1 => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let left =
+                        apply_weight(self.weight_a, self.samples_a[0]).wrapping_add(frame[0]);
+
+                    update_weight_clip(&mut self.weight_a, delta, self.samples_a[0], frame[0]);
+                    frame[0] = left;
+                    self.samples_a[0] = apply_weight(self.weight_b, left).wrapping_add(frame[1]);
+                    update_weight_clip(&mut self.weight_b, delta, left, frame[1]);
+                    frame[1] = self.samples_a[0];
+                }
+            }
+            -2 => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let right =
+                        apply_weight(self.weight_b, self.samples_b[0]).wrapping_add(frame[1]);
+
+                    update_weight_clip(&mut self.weight_b, delta, self.samples_b[0], frame[1]);
+                    frame[1] = right;
+                    self.samples_b[0] = apply_weight(self.weight_a, right).wrapping_add(frame[0]);
+                    update_weight_clip(&mut self.weight_a, delta, right, frame[0]);
+                    frame[0] = self.samples_b[0];
+                }
+            }
+            -3 => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let left =
+                        apply_weight(self.weight_a, self.samples_a[0]).wrapping_add(frame[0]);
+
+                    update_weight_clip(&mut self.weight_a, delta, self.samples_a[0], frame[0]);
+
+                    let right =
+                        apply_weight(self.weight_b, self.samples_b[0]).wrapping_add(frame[1]);
+
+                    update_weight_clip(&mut self.weight_b, delta, self.samples_b[0], frame[1]);
+                    self.samples_b[0] = left;
+                    self.samples_a[0] = right;
+                    frame[0] = left;
+                    frame[1] = right;
+                }
+            }
+            term => {
+                let mut m = 0;
+                let mut k = term as usize & (MAX_TERM - 1);
+
+                for frame in samples.chunks_exact_mut(2) {
+                    let previous = self.samples_a[m];
+
+                    self.samples_a[k] =
+                        apply_weight(self.weight_a, previous).wrapping_add(frame[0]);
+                    update_weight(&mut self.weight_a, delta, previous, frame[0]);
+                    frame[0] = self.samples_a[k];
+
+                    let previous = self.samples_b[m];
+
+                    self.samples_b[k] =
+                        apply_weight(self.weight_b, previous).wrapping_add(frame[1]);
+                    update_weight(&mut self.weight_b, delta, previous, frame[1]);
+                    frame[1] = self.samples_b[k];
+                    m = (m + 1) & (MAX_TERM - 1);
+                    k = (k + 1) & (MAX_TERM - 1);
+                }
+            }
+        }
+    }
+}
+
+/// The terms are stored in the order of the encoding, the reverse of the decoding.
+fn read_decorr_terms(data: &[u8], mono: bool) -> Result<Vec<DecorrPass>, WavPackError> {
+    if data.len() > MAX_TERMS {
+        return Err(WavPackError::InvalidBlock("too many decorrelation terms"));
+    }
+
+    data.iter()
+        .rev()
+        .map(|byte| {
+            let term = (byte & 0x1f) as i32 - 5;
+            let valid = match term {
+                1..=8 | 17 | 18 => true,
+                -3..=-1 => !mono,
+                _ => false,
+            };
+
+            valid
+                .then(|| DecorrPass {
+                    term,
+                    delta: (byte >> 5 & 0x7) as i32,
+                    ..Default::default()
+                })
+                .ok_or(WavPackError::InvalidBlock("invalid decorrelation term"))
+        })
+        .collect()
+}
+
+/// The weights of the last passes decoded, the first passes encoded. The other weights are zero.
+fn read_decorr_weights(
+    data: &[u8],
+    passes: &mut [DecorrPass],
+    mono: bool,
+) -> Result<(), WavPackError> {
+    let channels = if mono { 1 } else { 2 };
+
+    if !data.len().is_multiple_of(channels) || data.len() / channels > passes.len() {
+        return Err(WavPackError::InvalidBlock("invalid decorrelation weights"));
+    }
+
+    for (weights, pass) in data.chunks_exact(channels).zip(passes.iter_mut().rev()) {
+        pass.weight_a = restore_weight(weights[0] as i8);
+
+        if !mono {
+            pass.weight_b = restore_weight(weights[1] as i8);
+        }
+    }
+
+    Ok(())
+}
+
+/// The samples preceding the block for the last passes decoded, the first passes encoded.
+fn read_decorr_samples(
+    data: &[u8],
+    passes: &mut [DecorrPass],
+    mono: bool,
+) -> Result<(), WavPackError> {
+    if !data.len().is_multiple_of(2) {
+        return Err(WavPackError::InvalidBlock("invalid decorrelation samples"));
+    }
+
+    let mut values = data
+        .chunks_exact(2)
+        .map(|value| exp2s(i16::from_le_bytes([value[0], value[1]]) as i32))
+        .peekable();
+
+    for pass in passes.iter_mut().rev() {
+        if values.peek().is_none() {
+            break;
+        }
+
+        let mut next = || {
+            values
+                .next()
+                .ok_or(WavPackError::InvalidBlock("invalid decorrelation samples"))
+        };
+
+        match pass.term {
+            17 | 18 => {
+                pass.samples_a[0] = next()?;
+                pass.samples_a[1] = next()?;
+
+                if !mono {
+                    pass.samples_b[0] = next()?;
+                    pass.samples_b[1] = next()?;
+                }
+            }
+            term if term < 0 => {
+                pass.samples_a[0] = next()?;
+                pass.samples_b[0] = next()?;
+            }
+            term => {
+                for index in 0..term as usize {
+                    pass.samples_a[index] = next()?;
+
+                    if !mono {
+                        pass.samples_b[index] = next()?;
+                    }
+                }
+            }
+        }
+    }
+
+    if values.next().is_some() {
+        return Err(WavPackError::InvalidBlock("invalid decorrelation samples"));
+    }
+
+    Ok(())
+}
+
+/// The prediction of the terms 17 and 18 from the two previous samples.
+fn extrapolate(term: i32, samples: &[i32; MAX_TERM]) -> i32 {
+    if term == 17 {
+        samples[0].wrapping_mul(2).wrapping_sub(samples[1])
+    } else {
+        samples[0].wrapping_mul(3).wrapping_sub(samples[1]) >> 1
+    }
+}
+
+/// Multiply a sample by a weight in 1/1024.
+fn apply_weight(weight: i32, sample: i32) -> i32 {
+    if sample == sample as i16 as i32 {
+        (weight * sample + 512) >> 10
+    } else {
+        // The product could overflow, the low and the high bits are multiplied separately.
+        (((sample & 0xffff).wrapping_mul(weight) >> 9)
+            .wrapping_add(((sample & !0xffff) >> 9).wrapping_mul(weight))
+            .wrapping_add(1))
+            >> 1
+    }
+}
+
+/// Move a weight towards the sign of the correlation of the sample and of the residual.
+fn update_weight(weight: &mut i32, delta: i32, sample: i32, residual: i32) {
+    if sample != 0 && residual != 0 {
+        let sign = (sample ^ residual) >> 31;
+
+        *weight = (delta ^ sign).wrapping_add(weight.wrapping_sub(sign));
+    }
+}
+
+/// Like `update_weight`, with the weight kept within -1024 and 1024.
+fn update_weight_clip(weight: &mut i32, delta: i32, sample: i32, residual: i32) {
+    if sample != 0 && residual != 0 {
+        let sign = (sample ^ residual) >> 31;
+
+        *weight = ((*weight ^ sign) + (delta - sign)).min(1024);
+        *weight = (*weight ^ sign) - sign;
+    }
+}
+
+/// The weights are stored in 1/128, rounded.
+fn restore_weight(weight: i8) -> i32 {
+    let weight = (weight as i32) << 3;
+
+    if weight > 0 {
+        weight + ((weight + 64) >> 7)
+    } else {
+        weight
+    }
+}
+
+/// 2^(log / 256), the logarithm of the values stored in the metadata.
+fn exp2s(log: i32) -> i32 {
+    if log < 0 {
+        return -exp2s(-log);
+    }
+
+    let value = EXP2_TABLE[(log & 0xff) as usize] as i32 | 0x100;
+    let exponent = log >> 8;
+
+    if exponent <= 9 {
+        value >> (9 - exponent)
+    } else {
+        value.wrapping_shl((exponent - 9) as u32)
+    }
+}
+
+/// log2(value + 1) in 1/256, approximated.
+fn log2(value: u32) -> i32 {
+    let value = value.wrapping_add(value >> 9);
+    let bits = 32 - value.leading_zeros();
+    let fraction = if bits <= 9 {
+        value << (9 - bits)
+    } else {
+        value >> (bits - 9)
+    };
+
+    ((bits << 8) + LOG2_TABLE[(fraction & 0xff) as usize] as u32) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::BufReader, path::Path, time::Duration};
+
+    use rodio::Source;
+
+    use super::WavPackDecoder;
+
+    /// The samples of the WAV file encoded in `test_sine_mono.wv`.
+    fn wav_samples() -> Vec<f32> {
+        let wav =
+            std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("audio/test_sine_mono.wav"))
+                .unwrap();
+        let data = wav.windows(4).position(|id| id == b"data").unwrap() + 8;
+
+        wav[data..]
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0)
+            .collect()
+    }
+
+    fn open() -> WavPackDecoder<BufReader<File>> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("audio/test_sine_mono.wv");
+
+        WavPackDecoder::new(BufReader::new(File::open(path).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        let decoder = open();
+
+        assert_eq!(decoder.channels(), 1);
+        assert_eq!(decoder.sample_rate(), 48000);
+        assert_eq!(decoder.bits_per_sample(), 16);
+        assert_eq!(decoder.total_duration(), Some(Duration::from_secs(2)));
+        assert_eq!(decoder.collect::<Vec<_>>(), wav_samples());
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut decoder = open();
+
+        decoder.try_seek(Duration::from_millis(1500)).unwrap();
+
+        assert_eq!(decoder.collect::<Vec<_>>(), wav_samples()[72000..]);
+    }
+}